@@ -0,0 +1,18 @@
+-- Add up migration script here
+CREATE TABLE "user_emails" (
+  id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+  user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  email VARCHAR(255) NOT NULL UNIQUE,
+  verified BOOLEAN NOT NULL DEFAULT FALSE,
+  is_primary BOOLEAN NOT NULL DEFAULT FALSE,
+  verification_token VARCHAR(255),
+  token_expires_at TIMESTAMP WITH TIME ZONE,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+  updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX user_emails_user_id_idx ON user_emails (user_id);
+CREATE UNIQUE INDEX user_emails_primary_idx ON user_emails (user_id) WHERE is_primary;
+
+INSERT INTO user_emails (user_id, email, verified, is_primary)
+SELECT id, email, verified, TRUE FROM users;
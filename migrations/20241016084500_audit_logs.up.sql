@@ -0,0 +1,15 @@
+-- Add up migration script here
+CREATE TABLE "audit_logs" (
+  id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+  user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+  event_type VARCHAR(50) NOT NULL,
+  ip_address VARCHAR(45),
+  user_agent TEXT,
+  success BOOLEAN NOT NULL DEFAULT TRUE,
+  details TEXT,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX audit_logs_user_id_idx ON audit_logs (user_id);
+CREATE INDEX audit_logs_event_type_idx ON audit_logs (event_type);
+CREATE INDEX audit_logs_created_at_idx ON audit_logs (created_at DESC);
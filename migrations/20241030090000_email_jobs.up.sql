@@ -0,0 +1,17 @@
+-- Add up migration script here
+CREATE TABLE email_jobs (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    to_email VARCHAR(255) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    template_path VARCHAR(255) NOT NULL,
+    placeholders TEXT NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL,
+    next_attempt_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX email_jobs_status_next_attempt_at_idx ON email_jobs (status, next_attempt_at);
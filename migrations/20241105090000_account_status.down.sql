@@ -0,0 +1,6 @@
+-- Add down migration script here
+ALTER TABLE users
+    DROP COLUMN IF EXISTS status_reason,
+    DROP COLUMN IF EXISTS status;
+
+DROP TYPE IF EXISTS account_status;
@@ -0,0 +1,6 @@
+-- Add up migration script here
+CREATE TYPE account_status AS ENUM ('pending_approval', 'active', 'suspended', 'deactivated');
+
+ALTER TABLE users
+    ADD COLUMN status account_status NOT NULL DEFAULT 'active',
+    ADD COLUMN status_reason VARCHAR(255);
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE users ADD COLUMN max_sessions INTEGER CHECK (max_sessions > 0);
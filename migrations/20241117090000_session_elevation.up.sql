@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE sessions ADD COLUMN elevated_until TIMESTAMP WITH TIME ZONE;
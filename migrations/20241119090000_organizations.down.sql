@@ -0,0 +1,13 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS users_org_id_idx;
+
+ALTER TABLE user_emails DROP CONSTRAINT IF EXISTS user_emails_org_email_key;
+ALTER TABLE user_emails ADD CONSTRAINT user_emails_email_key UNIQUE (email);
+
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_org_email_key;
+ALTER TABLE users ADD CONSTRAINT users_email_key UNIQUE (email);
+
+ALTER TABLE user_emails DROP COLUMN IF EXISTS org_id;
+ALTER TABLE users DROP COLUMN IF EXISTS org_id;
+
+DROP TABLE IF EXISTS organizations;
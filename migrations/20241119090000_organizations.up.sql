@@ -0,0 +1,23 @@
+-- Add up migration script here
+CREATE TABLE "organizations" (
+  id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+  slug VARCHAR(63) NOT NULL UNIQUE,
+  name VARCHAR(100) NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+INSERT INTO organizations (id, slug, name)
+VALUES ('00000000-0000-0000-0000-000000000000', 'default', 'Default');
+
+ALTER TABLE users
+  ADD COLUMN org_id UUID NOT NULL DEFAULT '00000000-0000-0000-0000-000000000000' REFERENCES organizations(id);
+ALTER TABLE user_emails
+  ADD COLUMN org_id UUID NOT NULL DEFAULT '00000000-0000-0000-0000-000000000000' REFERENCES organizations(id);
+
+ALTER TABLE users DROP CONSTRAINT users_email_key;
+ALTER TABLE users ADD CONSTRAINT users_org_email_key UNIQUE (org_id, email);
+
+ALTER TABLE user_emails DROP CONSTRAINT user_emails_email_key;
+ALTER TABLE user_emails ADD CONSTRAINT user_emails_org_email_key UNIQUE (org_id, email);
+
+CREATE INDEX users_org_id_idx ON users (org_id);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS users_consumed_verification_token_idx;
+
+ALTER TABLE users DROP COLUMN IF EXISTS consumed_verification_token;
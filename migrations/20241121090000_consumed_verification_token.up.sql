@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE users ADD COLUMN consumed_verification_token VARCHAR(255);
+
+CREATE INDEX users_consumed_verification_token_idx ON users (consumed_verification_token);
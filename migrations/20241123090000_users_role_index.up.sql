@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- Role filters bind their value as user_role, so the plain column is indexable.
+CREATE INDEX users_org_role_idx ON users (org_id, role) WHERE deleted_at IS NULL;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS users_pending_anonymization_idx;
+
+ALTER TABLE users DROP COLUMN IF EXISTS anonymized_at;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE users ADD COLUMN anonymized_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX users_pending_anonymization_idx ON users (deleted_at) WHERE deleted_at IS NOT NULL AND anonymized_at IS NULL;
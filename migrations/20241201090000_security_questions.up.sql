@@ -0,0 +1,11 @@
+-- Add up migration script here
+CREATE TABLE security_questions (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    question VARCHAR(255) NOT NULL,
+    answer_hash VARCHAR(255) NOT NULL,
+    answer_pepper_id VARCHAR(32),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX security_questions_user_id_idx ON security_questions (user_id);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE sessions DROP COLUMN IF EXISTS network_hash;
+ALTER TABLE sessions DROP COLUMN IF EXISTS user_agent_hash;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE sessions ADD COLUMN user_agent_hash VARCHAR(64);
+ALTER TABLE sessions ADD COLUMN network_hash VARCHAR(64);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS users_unverified_created_at_idx;
+ALTER TABLE users DROP COLUMN IF EXISTS last_verification_reminder_at;
+ALTER TABLE users DROP COLUMN IF EXISTS verification_reminders_sent;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+ALTER TABLE users ADD COLUMN verification_reminders_sent INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN last_verification_reminder_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX users_unverified_created_at_idx ON users (created_at) WHERE NOT verified AND deleted_at IS NULL;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE users ADD COLUMN last_login_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX users_last_login_at_idx ON users (org_id, last_login_at);
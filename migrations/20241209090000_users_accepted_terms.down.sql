@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE users DROP COLUMN IF EXISTS accepted_terms_at;
+ALTER TABLE users DROP COLUMN IF EXISTS accepted_terms_version;
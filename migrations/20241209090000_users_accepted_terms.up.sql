@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE users ADD COLUMN accepted_terms_version VARCHAR(64);
+ALTER TABLE users ADD COLUMN accepted_terms_at TIMESTAMP WITH TIME ZONE;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE users ADD COLUMN frozen BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE users ADD COLUMN frozen_reason VARCHAR(255);
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE users ADD COLUMN consumed_verification_at TIMESTAMP WITH TIME ZONE;
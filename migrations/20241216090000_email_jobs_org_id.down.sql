@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS email_jobs_org_id_status_idx;
+ALTER TABLE email_jobs DROP COLUMN IF EXISTS org_id;
@@ -0,0 +1,14 @@
+-- Add up migration script here
+ALTER TABLE email_jobs
+  ADD COLUMN org_id UUID NOT NULL DEFAULT '00000000-0000-0000-0000-000000000000' REFERENCES organizations(id) ON DELETE CASCADE;
+
+-- Jobs queued before this column existed belong to whichever organization
+-- their recipient is registered in, anything unmatched stays in the default one.
+UPDATE email_jobs
+SET org_id = users.org_id
+FROM users
+WHERE users.email = email_jobs.to_email;
+
+ALTER TABLE email_jobs ALTER COLUMN org_id DROP DEFAULT;
+
+CREATE INDEX email_jobs_org_id_status_idx ON email_jobs (org_id, status);
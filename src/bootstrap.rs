@@ -0,0 +1,36 @@
+use crate::{config::Config, db::{DBClient, UserExt}, utils::password};
+
+pub async fn seed_admin(config: &Config, db_client: &DBClient) {
+    let Some(admin) = &config.bootstrap_admin else {
+        return;
+    };
+
+    if let Err(e) = config.password_policy.check(&admin.password, &admin.name, &admin.email) {
+        eprintln!("Bootstrap admin not created, BOOTSTRAP_ADMIN_PASSWORD was rejected: {}", e);
+        return;
+    }
+
+    let hashed_password = match password::hash(&admin.password, config.password_pepper.as_ref()) {
+        Ok(hashed_password) => hashed_password,
+        Err(e) => {
+            eprintln!("Bootstrap admin not created, failed to hash password: {}", e);
+            return;
+        }
+    };
+
+    let result = db_client
+        .create_bootstrap_admin(&admin.name, &admin.email, &hashed_password, config.current_pepper_id())
+        .await;
+
+    match result {
+        Ok(Some(user)) => {
+            println!("Created bootstrap admin {} ({})", user.email, user.id);
+            println!("WARNING: sign in and change the bootstrap admin password now, then unset BOOTSTRAP_ADMIN_PASSWORD");
+        }
+        Ok(None) => println!("An admin already exists, skipping bootstrap admin"),
+        Err(sqlx::Error::Database(db_err)) if db_err.is_unique_violation() => {
+            eprintln!("Bootstrap admin not created, {} already belongs to a non-admin user", admin.email);
+        }
+        Err(e) => eprintln!("Failed to create bootstrap admin: {}", e),
+    }
+}
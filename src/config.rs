@@ -1,23 +1,1285 @@
+use std::{collections::HashMap, net::IpAddr, str::FromStr, time::Duration};
+
+use axum::http::HeaderValue;
+use url::Url;
+
+use crate::{error::ErrorMessage, models::{User, UserRole}, utils::{ip::{IpMasking, IpNetwork}, names::NameBlocklist, password::{PasswordPolicy, Pepper}, state_store::RedisTarget, token::{JwtKey, JwtKeys}}};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Environment {
+    Dev,
+    Prod,
+}
+
+impl FromStr for Environment {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "dev" | "development" => Ok(Environment::Dev),
+            "prod" | "production" => Ok(Environment::Prod),
+            _ => Err(format!("Unknown environment: {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EmailVerificationMode {
+    Link,
+    Code,
+    Both,
+}
+
+impl EmailVerificationMode {
+    pub fn sends_link(self) -> bool {
+        matches!(self, EmailVerificationMode::Link | EmailVerificationMode::Both)
+    }
+
+    pub fn sends_code(self) -> bool {
+        matches!(self, EmailVerificationMode::Code | EmailVerificationMode::Both)
+    }
+}
+
+impl FromStr for EmailVerificationMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "link" => Ok(EmailVerificationMode::Link),
+            "code" => Ok(EmailVerificationMode::Code),
+            "both" => Ok(EmailVerificationMode::Both),
+            _ => Err(format!("Unknown email verification mode: {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PasswordResetMode {
+    Link,
+    Code,
+}
+
+impl FromStr for PasswordResetMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "link" => Ok(PasswordResetMode::Link),
+            "code" => Ok(PasswordResetMode::Code),
+            _ => Err(format!("Unknown password reset mode: {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenBinding {
+    Off,
+    UserAgent,
+    Network,
+}
+
+impl FromStr for TokenBinding {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "off" => Ok(TokenBinding::Off),
+            "user_agent" => Ok(TokenBinding::UserAgent),
+            "network" => Ok(TokenBinding::Network),
+            _ => Err(format!("Unknown token binding: {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorDetail {
+    Detailed,
+    Generic,
+}
+
+impl FromStr for ErrorDetail {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "detailed" => Ok(ErrorDetail::Detailed),
+            "generic" => Ok(ErrorDetail::Generic),
+            _ => Err(format!("Unknown error detail mode: {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorFormat {
+    Json,
+    Problem,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "json" => Ok(ErrorFormat::Json),
+            "problem" | "problem+json" => Ok(ErrorFormat::Problem),
+            _ => Err(format!("Unknown error format: {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SessionLimitPolicy {
+    Reject,
+    EvictOldest,
+}
+
+impl FromStr for SessionLimitPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "reject" => Ok(SessionLimitPolicy::Reject),
+            "evict_oldest" => Ok(SessionLimitPolicy::EvictOldest),
+            _ => Err(format!("Unknown session limit policy: {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum StateStoreBackend {
+    Memory,
+    Redis(RedisTarget),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuthMethod {
+    Password,
+    ApiKey,
+}
+
+impl AuthMethod {
+    pub const ALL: [AuthMethod; 2] = [AuthMethod::Password, AuthMethod::ApiKey];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            AuthMethod::Password => "password",
+            AuthMethod::ApiKey => "api_key",
+        }
+    }
+
+    fn signs_in(self) -> bool {
+        matches!(self, AuthMethod::Password)
+    }
+}
+
+impl FromStr for AuthMethod {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "password" => Ok(AuthMethod::Password),
+            "api_key" => Ok(AuthMethod::ApiKey),
+            _ => Err(format!("Unknown auth method: {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CaptchaProvider {
+    Recaptcha,
+    Hcaptcha,
+    Turnstile,
+}
+
+impl CaptchaProvider {
+    pub fn verify_endpoint(self) -> (&'static str, &'static str) {
+        match self {
+            CaptchaProvider::Recaptcha => ("www.google.com", "/recaptcha/api/siteverify"),
+            CaptchaProvider::Hcaptcha => ("api.hcaptcha.com", "/siteverify"),
+            CaptchaProvider::Turnstile => ("challenges.cloudflare.com", "/turnstile/v0/siteverify"),
+        }
+    }
+}
+
+impl FromStr for CaptchaProvider {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "recaptcha" => Ok(CaptchaProvider::Recaptcha),
+            "hcaptcha" => Ok(CaptchaProvider::Hcaptcha),
+            "turnstile" => Ok(CaptchaProvider::Turnstile),
+            _ => Err(format!("Unknown captcha provider: {}", value)),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct BootstrapAdmin {
+    pub name: String,
+    pub email: String,
+    pub password: String,
+}
+
+impl std::fmt::Debug for BootstrapAdmin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BootstrapAdmin").field("email", &self.email).finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CaptchaConfig {
+    pub provider: CaptchaProvider,
+    pub secret: String,
+    pub failure_threshold: u32,
+    pub window_seconds: u64,
+    pub blocked_ips: Vec<IpNetwork>,
+}
+
+impl CaptchaConfig {
+    pub fn window(&self) -> Duration {
+        Duration::from_secs(self.window_seconds)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RegistrationCap {
+    pub per_ip_per_day: u32,
+    pub exempt_ips: Vec<IpNetwork>,
+}
+
+impl RegistrationCap {
+    fn from_env() -> Option<Self> {
+        let per_ip_per_day: u32 = parse_env("REGISTRATION_DAILY_LIMIT_PER_IP").filter(|limit| *limit > 0)?;
+        let exempt_ips: Vec<IpNetwork> = std::env::var("REGISTRATION_LIMIT_EXEMPT_IPS")
+            .map(|value| {
+                value
+                    .split(',')
+                    .filter(|entry| !entry.trim().is_empty())
+                    .map(|entry| entry.parse().expect("REGISTRATION_LIMIT_EXEMPT_IPS must be a list of IPs or CIDRs"))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(RegistrationCap { per_ip_per_day, exempt_ips })
+    }
+
+    pub fn exempts(&self, ip: IpAddr) -> bool {
+        self.exempt_ips.iter().any(|network| network.contains(ip))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LoginThrottle {
+    pub max_failures: u32,
+    pub window_seconds: u64,
+}
+
+impl LoginThrottle {
+    fn from_env(prefix: &str, max_failures: u32, window_seconds: u64) -> Self {
+        LoginThrottle {
+            max_failures: parse_env(&format!("{}_MAX_FAILURES", prefix))
+                .filter(|failures| *failures > 0)
+                .unwrap_or(max_failures),
+            window_seconds: parse_env(&format!("{}_WINDOW_SECONDS", prefix))
+                .filter(|seconds| *seconds > 0)
+                .unwrap_or(window_seconds),
+        }
+    }
+
+    pub fn window(&self) -> Duration {
+        Duration::from_secs(self.window_seconds)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RequestLimit {
+    pub max_requests: u32,
+    pub window_seconds: u64,
+}
+
+impl RequestLimit {
+    fn from_env(prefix: &str, max_requests: u32, window_seconds: u64) -> Self {
+        RequestLimit {
+            max_requests: parse_env(&format!("{}_MAX_REQUESTS", prefix))
+                .filter(|requests| *requests > 0)
+                .unwrap_or(max_requests),
+            window_seconds: parse_env(&format!("{}_WINDOW_SECONDS", prefix))
+                .filter(|seconds| *seconds > 0)
+                .unwrap_or(window_seconds),
+        }
+    }
+
+    pub fn window(&self) -> Duration {
+        Duration::from_secs(self.window_seconds)
+    }
+}
+
+pub const CORS_ROUTE_GROUPS: &[&str] = &["auth", "users", "admin", "audit", "validate"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CorsOrigins {
+    Any,
+    List(Vec<String>),
+}
+
+impl CorsOrigins {
+    fn parse(key: &str, value: &str) -> Self {
+        if value.trim() == "*" {
+            return CorsOrigins::Any;
+        }
+
+        let origins = value
+            .split(',')
+            .map(str::trim)
+            .filter(|origin| !origin.is_empty())
+            .map(|origin| {
+                let url = Url::parse(origin)
+                    .unwrap_or_else(|e| panic!("{} origin {} must be a valid URL: {}", key, origin, e));
+
+                if !matches!(url.scheme(), "http" | "https") || url.host().is_none() || url.path() != "/" {
+                    panic!("{} origin {} must be an http or https origin without a path", key, origin);
+                }
+
+                url.origin().ascii_serialization()
+            })
+            .collect();
+
+        CorsOrigins::List(origins)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    pub global: CorsOrigins,
+    pub overrides: HashMap<String, CorsOrigins>,
+}
+
+impl CorsConfig {
+    fn from_env() -> Self {
+        let global = std::env::var("CORS_ALLOWED_ORIGINS")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| CorsOrigins::parse("CORS_ALLOWED_ORIGINS", &value))
+            .unwrap_or_else(|| CorsOrigins::List(vec!["http://localhost:3000".to_string()]));
+
+        let overrides = std::env::var("CORS_OVERRIDES")
+            .map(|value| {
+                value
+                    .split(';')
+                    .filter(|entry| !entry.trim().is_empty())
+                    .map(|entry| {
+                        let (group, origins) = entry
+                            .split_once('=')
+                            .expect("CORS_OVERRIDES must be a list of group=origins entries");
+                        let group = group.trim().to_lowercase();
+
+                        if !CORS_ROUTE_GROUPS.contains(&group.as_str()) {
+                            panic!("CORS_OVERRIDES references unknown route group {}, expected one of {}", group, CORS_ROUTE_GROUPS.join(", "));
+                        }
+
+                        (group, CorsOrigins::parse("CORS_OVERRIDES", origins))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        CorsConfig { global, overrides }
+    }
+
+    pub fn origins_for(&self, group: &str) -> &CorsOrigins {
+        self.overrides.get(group).unwrap_or(&self.global)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders {
+    pub enabled: bool,
+    pub strict_transport_security: Option<HeaderValue>,
+    pub frame_options: HeaderValue,
+    pub referrer_policy: HeaderValue,
+    pub content_security_policy: HeaderValue,
+}
+
+impl SecurityHeaders {
+    fn from_env(environment: Environment) -> Self {
+        let header_value = |key: &str, value: String| -> HeaderValue {
+            value.parse().unwrap_or_else(|_| panic!("{} is not a valid header value", key))
+        };
+        let non_empty = |key: &str| std::env::var(key).ok().filter(|value| !value.trim().is_empty());
+
+        // Browsers pin HSTS for the whole max-age, so a dev instance on plain HTTP must never send it.
+        let hsts_max_age: u64 = parse_env("HSTS_MAX_AGE_SECONDS").unwrap_or(31536000);
+        let strict_transport_security = (environment == Environment::Prod && hsts_max_age > 0).then(|| {
+            let include_subdomains: bool = parse_env("HSTS_INCLUDE_SUBDOMAINS").unwrap_or(false);
+            let value = if include_subdomains {
+                format!("max-age={}; includeSubDomains", hsts_max_age)
+            } else {
+                format!("max-age={}", hsts_max_age)
+            };
+            header_value("HSTS_MAX_AGE_SECONDS", value)
+        });
+
+        let frame_options = non_empty("FRAME_OPTIONS")
+            .map(|value| value.trim().to_uppercase())
+            .unwrap_or("DENY".to_string());
+        let frame_ancestors = match frame_options.as_str() {
+            "DENY" => "'none'",
+            "SAMEORIGIN" => "'self'",
+            _ => panic!("FRAME_OPTIONS must be DENY or SAMEORIGIN"),
+        };
+
+        let content_security_policy = non_empty("CONTENT_SECURITY_POLICY")
+            .unwrap_or_else(|| format!("default-src 'none'; frame-ancestors {}", frame_ancestors));
+        let referrer_policy = non_empty("REFERRER_POLICY")
+            .unwrap_or("no-referrer".to_string());
+
+        SecurityHeaders {
+            enabled: parse_env("SECURITY_HEADERS").unwrap_or(true),
+            strict_transport_security,
+            frame_options: header_value("FRAME_OPTIONS", frame_options),
+            referrer_policy: header_value("REFERRER_POLICY", referrer_policy),
+            content_security_policy: header_value("CONTENT_SECURITY_POLICY", content_security_policy),
+        }
+    }
+}
+
+pub const NAME_COLUMN_LENGTH: usize = 100;
+
+#[derive(Debug, Clone, Copy)]
+pub struct NameLength {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl Default for NameLength {
+    fn default() -> Self {
+        NameLength { min: 1, max: NAME_COLUMN_LENGTH }
+    }
+}
+
+impl NameLength {
+    fn from_env() -> Self {
+        let default = NameLength::default();
+        let min: usize = parse_env("NAME_MIN_LENGTH")
+            .filter(|min| *min > 0)
+            .unwrap_or(default.min);
+        let max: usize = parse_env("NAME_MAX_LENGTH").unwrap_or(default.max);
+
+        if max > NAME_COLUMN_LENGTH {
+            panic!("NAME_MAX_LENGTH must be at most {}, the size of the name column", NAME_COLUMN_LENGTH);
+        }
+
+        if min > max {
+            panic!("NAME_MIN_LENGTH must not be greater than NAME_MAX_LENGTH");
+        }
+
+        NameLength { min, max }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScrubField {
+    Name,
+    Email,
+    DisplayName,
+    AvatarUrl,
+    Locale,
+}
+
+impl ScrubField {
+    pub const ALL: [ScrubField; 5] = [
+        ScrubField::Name,
+        ScrubField::Email,
+        ScrubField::DisplayName,
+        ScrubField::AvatarUrl,
+        ScrubField::Locale,
+    ];
+}
+
+impl FromStr for ScrubField {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "name" => Ok(ScrubField::Name),
+            "email" => Ok(ScrubField::Email),
+            "display_name" => Ok(ScrubField::DisplayName),
+            "avatar_url" => Ok(ScrubField::AvatarUrl),
+            "locale" => Ok(ScrubField::Locale),
+            _ => Err(format!("Unknown scrub field: {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DeletedUserRetention {
+    pub days: i64,
+    pub fields: Vec<ScrubField>,
+}
+
+impl DeletedUserRetention {
+    fn from_env() -> Option<Self> {
+        let days: i64 = parse_env("DELETED_USER_RETENTION_DAYS").filter(|days| *days > 0)?;
+        let fields: Vec<ScrubField> = std::env::var("DELETED_USER_SCRUB_FIELDS")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| {
+                value
+                    .split(',')
+                    .filter(|entry| !entry.trim().is_empty())
+                    .map(|entry| entry.parse().expect("DELETED_USER_SCRUB_FIELDS must be a list of name, email, display_name, avatar_url or locale"))
+                    .collect()
+            })
+            .unwrap_or_else(|| ScrubField::ALL.to_vec());
+
+        Some(DeletedUserRetention { days, fields })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VerificationReminders {
+    pub delay_hours: i64,
+    pub max_reminders: i32,
+    pub max_age_hours: i64,
+}
+
+impl VerificationReminders {
+    fn from_env() -> Option<Self> {
+        if !parse_env::<bool>("VERIFICATION_REMINDERS_ENABLED").unwrap_or(false) {
+            return None;
+        }
+
+        let delay_hours: i64 = parse_env("VERIFICATION_REMINDER_DELAY_HOURS")
+            .filter(|hours| *hours > 0)
+            .unwrap_or(24);
+        let max_age_hours: i64 = parse_env("VERIFICATION_REMINDER_MAX_AGE_HOURS")
+            .filter(|hours| *hours > 0)
+            .unwrap_or(168);
+
+        if max_age_hours <= delay_hours {
+            panic!("VERIFICATION_REMINDER_MAX_AGE_HOURS must be larger than VERIFICATION_REMINDER_DELAY_HOURS");
+        }
+
+        Some(VerificationReminders {
+            delay_hours,
+            max_reminders: parse_env("VERIFICATION_REMINDER_COUNT")
+                .filter(|count| *count > 0)
+                .unwrap_or(1),
+            max_age_hours,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Compression {
+    pub gzip: bool,
+    pub br: bool,
+    pub min_bytes: u16,
+}
+
+impl Compression {
+    fn from_env() -> Self {
+        let codecs: Vec<String> = std::env::var("COMPRESSION_CODECS")
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(|codec| codec.trim().to_lowercase())
+                    .filter(|codec| !codec.is_empty())
+                    .collect()
+            })
+            .unwrap_or_else(|_| vec!["gzip".to_string(), "br".to_string()]);
+
+        if let Some(codec) = codecs.iter().find(|codec| !matches!(codec.as_str(), "gzip" | "br")) {
+            panic!("COMPRESSION_CODECS must be a list of gzip or br, got {}", codec);
+        }
+
+        Compression {
+            gzip: codecs.iter().any(|codec| codec == "gzip"),
+            br: codecs.iter().any(|codec| codec == "br"),
+            min_bytes: parse_env("COMPRESSION_MIN_BYTES").unwrap_or(1024),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SecurityLogSink {
+    Stdout,
+    File(String),
+    Syslog(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SecurityLogFormat {
+    Json,
+    Logfmt,
+}
+
+impl FromStr for SecurityLogFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "json" => Ok(SecurityLogFormat::Json),
+            "logfmt" => Ok(SecurityLogFormat::Logfmt),
+            _ => Err(format!("Unknown security log format: {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SecurityLogConfig {
+    pub sink: SecurityLogSink,
+    pub format: SecurityLogFormat,
+}
+
+impl SecurityLogConfig {
+    fn from_env() -> Option<Self> {
+        let sink = match std::env::var("SECURITY_LOG_SINK").unwrap_or_default().to_lowercase().as_str() {
+            "" | "off" => return None,
+            "stdout" => SecurityLogSink::Stdout,
+            "file" => SecurityLogSink::File(
+                std::env::var("SECURITY_LOG_FILE")
+                    .ok()
+                    .filter(|path| !path.trim().is_empty())
+                    .expect("SECURITY_LOG_FILE must be set when SECURITY_LOG_SINK=file")
+            ),
+            "syslog" => SecurityLogSink::Syslog(
+                std::env::var("SECURITY_LOG_SYSLOG_SOCKET")
+                    .ok()
+                    .filter(|path| !path.trim().is_empty())
+                    .unwrap_or_else(|| "/dev/log".to_string())
+            ),
+            other => panic!("SECURITY_LOG_SINK must be off, stdout, file or syslog, got {}", other),
+        };
+
+        let format: SecurityLogFormat = std::env::var("SECURITY_LOG_FORMAT")
+            .map(|value| value.parse().expect("SECURITY_LOG_FORMAT must be either json or logfmt"))
+            .unwrap_or(SecurityLogFormat::Json);
+
+        Some(SecurityLogConfig { sink, format })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SecurityQuestionsConfig {
+    pub required: usize,
+    pub challenge: usize,
+    pub max_failures: u32,
+    pub lockout_seconds: u64,
+}
+
+impl SecurityQuestionsConfig {
+    fn from_env() -> Option<Self> {
+        if !parse_env::<bool>("SECURITY_QUESTIONS_ENABLED").unwrap_or(false) {
+            return None;
+        }
+
+        let required: usize = parse_env("SECURITY_QUESTIONS_REQUIRED")
+            .filter(|count| *count > 0)
+            .unwrap_or(3);
+        let challenge: usize = parse_env("SECURITY_QUESTIONS_CHALLENGE")
+            .filter(|count| *count > 0)
+            .unwrap_or(2);
+
+        if challenge > required {
+            panic!("SECURITY_QUESTIONS_CHALLENGE cannot be larger than SECURITY_QUESTIONS_REQUIRED");
+        }
+
+        Some(SecurityQuestionsConfig {
+            required,
+            challenge,
+            max_failures: parse_env("SECURITY_QUESTIONS_MAX_FAILURES")
+                .filter(|failures| *failures > 0)
+                .unwrap_or(3),
+            lockout_seconds: parse_env("SECURITY_QUESTIONS_LOCKOUT_SECONDS")
+                .filter(|seconds| *seconds > 0)
+                .unwrap_or(86400),
+        })
+    }
+
+    pub fn lockout(&self) -> Duration {
+        Duration::from_secs(self.lockout_seconds)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LockoutAlerts {
+    pub notify_user: bool,
+    pub notify_interval_seconds: u64,
+    pub admin_email: Option<String>,
+    pub admin_threshold: u32,
+    pub admin_window_seconds: u64,
+}
+
+impl LockoutAlerts {
+    pub fn notify_interval(&self) -> Duration {
+        Duration::from_secs(self.notify_interval_seconds)
+    }
+
+    pub fn admin_window(&self) -> Duration {
+        Duration::from_secs(self.admin_window_seconds)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub database_url: String,
-    pub jwt_secret: String,
+    pub jwt_keys: JwtKeys,
     pub jwt_maxage: i64,
+    pub jwt_maxage_admin: Option<i64>,
+    pub jwt_maxage_user: Option<i64>,
     pub port: u16,
+    pub environment: Environment,
+    pub error_detail: ErrorDetail,
+    pub error_format: ErrorFormat,
+    pub cookie_domain: Option<String>,
+    pub tenant_base_domain: Option<String>,
+    pub reset_verify_rate_limit: u32,
+    pub email_availability_rate_limit: u32,
+    pub registration_cap: Option<RegistrationCap>,
+    pub introspection_secret: Option<String>,
+    pub introspection_max_batch: usize,
+    pub validate_rate_limit: u32,
+    pub password_max_age_days: Option<i64>,
+    pub password_min_age_hours: Option<i64>,
+    pub verification_grace_days: Option<i64>,
+    pub email_verification_mode: EmailVerificationMode,
+    pub password_reset_mode: PasswordResetMode,
+    pub trusted_proxies: Vec<IpNetwork>,
+    pub password_pepper: Option<Pepper>,
+    pub retired_password_peppers: Vec<Pepper>,
+    pub request_timeout_seconds: u64,
+    pub heavy_request_timeout_seconds: u64,
+    pub db_statement_timeout_seconds: u64,
+    pub db_heavy_statement_timeout_seconds: u64,
+    pub compression: Compression,
+    pub email_max_attempts: i32,
+    pub email_retry_base_seconds: u64,
+    pub user_stats_cache_seconds: u64,
+    pub dormant_after_days: i64,
+    pub login_throttle_known: LoginThrottle,
+    pub login_throttle_unknown: LoginThrottle,
+    pub login_throttle_ip: LoginThrottle,
+    pub login_throttle_email: LoginThrottle,
+    pub login_throttle_pair: LoginThrottle,
+    pub password_reset_limit_account: RequestLimit,
+    pub password_reset_limit_ip: RequestLimit,
+    pub external_base_url: String,
+    pub frontend_url: String,
+    pub already_verified_path: String,
+    pub verification_replay_window_minutes: i64,
+    pub base_path: String,
+    pub captcha: Option<CaptchaConfig>,
+    pub registration_requires_approval: bool,
+    pub terms_version: Option<String>,
+    pub self_reactivation: bool,
+    pub role_change_revokes_sessions: bool,
+    pub invite_codes_required: bool,
+    pub expose_email_tokens: bool,
+    pub login_response_includes_user: bool,
+    pub email_ignore_case: bool,
+    pub max_sessions_per_user: Option<i64>,
+    pub session_limit_policy: SessionLimitPolicy,
+    pub login_history_ip_masking: IpMasking,
+    pub password_policy: PasswordPolicy,
+    pub api_key_rotation_grace_seconds: i64,
+    pub bootstrap_admin: Option<BootstrapAdmin>,
+    pub verify_email_mx: bool,
+    pub lockout_alerts: LockoutAlerts,
+    pub email_change_undo_hours: Option<i64>,
+    pub trusted_device_days: Option<i64>,
+    pub impersonation_minutes: i64,
+    pub reauth_minutes: i64,
+    pub session_refresh_margin_seconds: i64,
+    pub token_binding: TokenBinding,
+    pub name_length: NameLength,
+    pub name_blocklist: NameBlocklist,
+    pub auth_methods: Vec<AuthMethod>,
+    pub cors: CorsConfig,
+    pub security_headers: SecurityHeaders,
+    pub state_store: StateStoreBackend,
+    pub deleted_user_retention: Option<DeletedUserRetention>,
+    pub security_questions: Option<SecurityQuestionsConfig>,
+    pub verification_reminders: Option<VerificationReminders>,
+    pub security_log: Option<SecurityLogConfig>,
 }
 
 impl Config {
     pub fn init() -> Config {
         let database_url: String = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
         let jwt_secret: String = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
+        let jwt_key_id: String = std::env::var("JWT_KEY_ID")
+            .ok()
+            .filter(|id| !id.trim().is_empty())
+            .unwrap_or("1".to_string());
+        let retired_jwt_keys: Vec<JwtKey> = std::env::var("JWT_SECRETS_RETIRED")
+            .map(|value| {
+                value
+                    .split(',')
+                    .filter(|entry| !entry.trim().is_empty())
+                    .map(|entry| {
+                        let (id, secret) = entry
+                            .trim()
+                            .split_once(':')
+                            .filter(|(id, secret)| !id.is_empty() && !secret.is_empty())
+                            .expect("JWT_SECRETS_RETIRED must be a list of id:secret pairs");
+                        JwtKey::new(id, secret)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if retired_jwt_keys.iter().any(|key| key.id == jwt_key_id) {
+            panic!("JWT_SECRETS_RETIRED must not reuse the current JWT_KEY_ID");
+        }
+        let jwt_leeway_seconds: u64 = parse_env("JWT_LEEWAY_SECONDS").unwrap_or(30);
         let jwt_maxage: String = std::env::var("JWT_MAXAGE").expect("JWT_MAXAGE must be set");
+        let jwt_maxage_admin: Option<i64> = parse_env("JWT_MAXAGE_ADMIN")
+            .filter(|minutes| *minutes > 0);
+        let jwt_maxage_user: Option<i64> = parse_env("JWT_MAXAGE_USER")
+            .filter(|minutes| *minutes > 0);
+        let environment: Environment = std::env::var("APP_ENV")
+            .map(|value| value.parse().expect("APP_ENV must be either dev or prod"))
+            .unwrap_or(Environment::Dev);
+        let error_detail: ErrorDetail = std::env::var("ERROR_DETAIL")
+            .map(|value| value.parse().expect("ERROR_DETAIL must be either detailed or generic"))
+            .unwrap_or(match environment {
+                Environment::Dev => ErrorDetail::Detailed,
+                Environment::Prod => ErrorDetail::Generic,
+            });
+        let error_format: ErrorFormat = std::env::var("ERROR_FORMAT")
+            .map(|value| value.parse().expect("ERROR_FORMAT must be either json or problem"))
+            .unwrap_or(ErrorFormat::Json);
+        let cookie_domain: Option<String> = std::env::var("COOKIE_DOMAIN")
+            .ok()
+            .filter(|domain| !domain.trim().is_empty());
+        let tenant_base_domain: Option<String> = std::env::var("TENANT_BASE_DOMAIN")
+            .ok()
+            .map(|domain| domain.trim().trim_start_matches('.').to_lowercase())
+            .filter(|domain| !domain.is_empty());
+        let reset_verify_rate_limit: u32 = parse_env("RESET_VERIFY_RATE_LIMIT").unwrap_or(10);
+        let email_availability_rate_limit: u32 = parse_env("EMAIL_AVAILABILITY_RATE_LIMIT").unwrap_or(10);
+        let registration_cap = RegistrationCap::from_env();
+        let introspection_secret: Option<String> = std::env::var("INTROSPECTION_SECRET")
+            .ok()
+            .filter(|secret| !secret.trim().is_empty());
+        let introspection_max_batch: usize = parse_env("INTROSPECTION_MAX_BATCH")
+            .filter(|size| *size > 0)
+            .unwrap_or(50);
+        let validate_rate_limit: u32 = parse_env("VALIDATE_RATE_LIMIT").unwrap_or(30);
+        let password_max_age_days: Option<i64> = parse_env("PASSWORD_MAX_AGE_DAYS")
+            .filter(|days| *days > 0);
+        let password_min_age_hours: Option<i64> = parse_env("PASSWORD_MIN_AGE_HOURS")
+            .filter(|hours| *hours > 0);
+        let verification_grace_days: Option<i64> = parse_env("VERIFICATION_GRACE_DAYS")
+            .filter(|days| *days >= 0);
+        let email_verification_mode: EmailVerificationMode = std::env::var("EMAIL_VERIFICATION_MODE")
+            .map(|value| value.parse().expect("EMAIL_VERIFICATION_MODE must be link, code or both"))
+            .unwrap_or(EmailVerificationMode::Link);
+        let password_reset_mode: PasswordResetMode = std::env::var("PASSWORD_RESET_MODE")
+            .map(|value| value.parse().expect("PASSWORD_RESET_MODE must be link or code"))
+            .unwrap_or(PasswordResetMode::Link);
+        let trusted_proxies: Vec<IpNetwork> = std::env::var("TRUSTED_PROXIES")
+            .map(|value| {
+                value
+                    .split(',')
+                    .filter(|entry| !entry.trim().is_empty())
+                    .map(|entry| entry.parse().expect("TRUSTED_PROXIES must be a list of IPs or CIDRs"))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let password_pepper: Option<Pepper> = std::env::var("PASSWORD_PEPPER")
+            .ok()
+            .map(|secret| {
+                if secret.is_empty() {
+                    panic!("PASSWORD_PEPPER must not be empty");
+                }
+                let id = std::env::var("PASSWORD_PEPPER_ID").expect("PASSWORD_PEPPER_ID must be set when PASSWORD_PEPPER is set");
+                Pepper::new(id, secret)
+            });
+        let retired_password_peppers: Vec<Pepper> = std::env::var("PASSWORD_PEPPERS_RETIRED")
+            .map(|value| {
+                value
+                    .split(',')
+                    .filter(|entry| !entry.trim().is_empty())
+                    .map(|entry| {
+                        let (id, secret) = entry
+                            .trim()
+                            .split_once(':')
+                            .filter(|(id, secret)| !id.is_empty() && !secret.is_empty())
+                            .expect("PASSWORD_PEPPERS_RETIRED must be a list of id:secret pairs");
+                        Pepper::new(id, secret)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let request_timeout_seconds: u64 = parse_env("REQUEST_TIMEOUT_SECONDS")
+            .filter(|seconds| *seconds > 0)
+            .unwrap_or(30);
+        let heavy_request_timeout_seconds: u64 = parse_env("HEAVY_REQUEST_TIMEOUT_SECONDS")
+            .filter(|seconds| *seconds > 0)
+            .unwrap_or(120)
+            .max(request_timeout_seconds);
+        let db_statement_timeout_seconds: u64 = parse_env("DB_STATEMENT_TIMEOUT_SECONDS")
+            .filter(|seconds| *seconds > 0)
+            .unwrap_or(request_timeout_seconds)
+            .min(heavy_request_timeout_seconds);
+        let db_heavy_statement_timeout_seconds: u64 = parse_env("DB_HEAVY_STATEMENT_TIMEOUT_SECONDS")
+            .filter(|seconds| *seconds > 0)
+            .unwrap_or(heavy_request_timeout_seconds)
+            .clamp(db_statement_timeout_seconds, heavy_request_timeout_seconds);
+        let compression = Compression::from_env();
+        let email_max_attempts: i32 = parse_env("EMAIL_MAX_ATTEMPTS")
+            .filter(|attempts| *attempts > 0)
+            .unwrap_or(5);
+        let email_retry_base_seconds: u64 = parse_env("EMAIL_RETRY_BASE_SECONDS").unwrap_or(30);
+        let user_stats_cache_seconds: u64 = parse_env("USER_STATS_CACHE_SECONDS").unwrap_or(60);
+        let dormant_after_days: i64 = parse_env("DORMANT_AFTER_DAYS")
+            .filter(|days| *days > 0)
+            .unwrap_or(90);
+        let login_throttle_known = LoginThrottle::from_env("LOGIN_KNOWN", 10, 300);
+        let login_throttle_unknown = LoginThrottle::from_env("LOGIN_UNKNOWN", 5, 900);
+        let login_throttle_ip = LoginThrottle::from_env("LOGIN_IP", 30, 900);
+        let login_throttle_email = LoginThrottle::from_env("LOGIN_EMAIL", 10, 900);
+        let login_throttle_pair = LoginThrottle::from_env("LOGIN_PAIR", 5, 900);
+        let password_reset_limit_account = RequestLimit::from_env("PASSWORD_RESET_ACCOUNT", 3, 3600);
+        let password_reset_limit_ip = RequestLimit::from_env("PASSWORD_RESET_IP", 20, 3600);
+        let external_base_url = parse_base_url("EXTERNAL_BASE_URL", "http://localhost:8000");
+        let frontend_url = parse_base_url("FRONTEND_URL", "http://localhost:5173");
+        let already_verified_path: String = std::env::var("ALREADY_VERIFIED_PATH")
+            .ok()
+            .filter(|path| path.starts_with('/'))
+            .unwrap_or_else(|| "/login?verified=already".to_string());
+        let verification_replay_window_minutes: i64 = parse_env("VERIFICATION_REPLAY_WINDOW_MINUTES")
+            .filter(|minutes| *minutes >= 0)
+            .unwrap_or(60);
+        let base_path = parse_base_path("BASE_PATH");
+        let max_sessions_per_user: Option<i64> = parse_env("MAX_SESSIONS_PER_USER")
+            .filter(|sessions| *sessions > 0);
+        let state_store = match std::env::var("STATE_STORE").unwrap_or_default().to_lowercase().as_str() {
+            "" | "memory" => StateStoreBackend::Memory,
+            "redis" => {
+                let url = std::env::var("REDIS_URL").expect("REDIS_URL must be set when STATE_STORE=redis");
+                StateStoreBackend::Redis(url.parse().unwrap_or_else(|e| panic!("REDIS_URL is invalid: {}", e)))
+            }
+            other => panic!("STATE_STORE must be either memory or redis, got {}", other),
+        };
+        let session_limit_policy: SessionLimitPolicy = std::env::var("SESSION_LIMIT_POLICY")
+            .map(|value| value.parse().expect("SESSION_LIMIT_POLICY must be either reject or evict_oldest"))
+            .unwrap_or(SessionLimitPolicy::Reject);
+        let login_history_ip_masking: IpMasking = std::env::var("LOGIN_HISTORY_IP_MASKING")
+            .map(|value| value.parse().expect("LOGIN_HISTORY_IP_MASKING must be none, partial or full"))
+            .unwrap_or(IpMasking::Partial);
+        let common_passwords: Option<String> = read_list_file("COMMON_PASSWORDS_FILE");
+        let app_name: Option<String> = std::env::var("APP_NAME")
+            .ok()
+            .filter(|name| !name.trim().is_empty());
+        let password_min_score: u8 = parse_env("PASSWORD_MIN_SCORE").unwrap_or(2);
+        if password_min_score > 4 {
+            panic!("PASSWORD_MIN_SCORE must be between 0 and 4");
+        }
+        let password_policy = PasswordPolicy::new(app_name.as_deref(), common_passwords.as_deref(), password_min_score);
+        let api_key_rotation_grace_seconds: i64 = parse_env("API_KEY_ROTATION_GRACE_SECONDS")
+            .filter(|seconds| *seconds >= 0)
+            .unwrap_or(900);
+        let bootstrap_admin: Option<BootstrapAdmin> = std::env::var("BOOTSTRAP_ADMIN_EMAIL")
+            .ok()
+            .filter(|email| !email.trim().is_empty())
+            .map(|email| BootstrapAdmin {
+                name: std::env::var("BOOTSTRAP_ADMIN_NAME")
+                    .ok()
+                    .filter(|name| !name.trim().is_empty())
+                    .unwrap_or("Admin".to_string()),
+                email: email.trim().to_string(),
+                password: std::env::var("BOOTSTRAP_ADMIN_PASSWORD")
+                    .ok()
+                    .filter(|password| !password.is_empty())
+                    .expect("BOOTSTRAP_ADMIN_PASSWORD must be set when BOOTSTRAP_ADMIN_EMAIL is set"),
+            });
+        let lockout_alerts = LockoutAlerts {
+            notify_user: parse_env("LOCKOUT_NOTIFY_USER").unwrap_or(true),
+            notify_interval_seconds: parse_env("LOCKOUT_NOTIFY_INTERVAL_SECONDS")
+                .filter(|seconds| *seconds > 0)
+                .unwrap_or(3600),
+            admin_email: std::env::var("LOCKOUT_ADMIN_EMAIL")
+                .ok()
+                .filter(|email| !email.trim().is_empty()),
+            admin_threshold: parse_env("LOCKOUT_ADMIN_THRESHOLD")
+                .filter(|lockouts| *lockouts > 0)
+                .unwrap_or(3),
+            admin_window_seconds: parse_env("LOCKOUT_ADMIN_WINDOW_SECONDS")
+                .filter(|seconds| *seconds > 0)
+                .unwrap_or(86400),
+        };
+        let email_change_undo_hours: Option<i64> = Some(parse_env("EMAIL_CHANGE_UNDO_HOURS").unwrap_or(72))
+            .filter(|hours| *hours > 0);
+        let trusted_device_days: Option<i64> = parse_env("TRUSTED_DEVICE_DAYS")
+            .filter(|days| *days > 0);
+        let impersonation_minutes: i64 = parse_env("IMPERSONATION_MINUTES")
+            .filter(|minutes| *minutes > 0)
+            .unwrap_or(15);
+        let reauth_minutes: i64 = parse_env("REAUTH_MINUTES")
+            .filter(|minutes| *minutes > 0)
+            .unwrap_or(5);
+        let session_refresh_margin_seconds: i64 = parse_env("SESSION_REFRESH_MARGIN_SECONDS")
+            .filter(|seconds| *seconds >= 0)
+            .unwrap_or(60);
+        let token_binding: TokenBinding = std::env::var("TOKEN_BINDING")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| value.parse().expect("TOKEN_BINDING must be off, user_agent or network"))
+            .unwrap_or(TokenBinding::Off);
+        let name_length = NameLength::from_env();
+        let reserved_names: Option<String> = read_list_file("RESERVED_NAMES_FILE");
+        let blocked_name_terms: Option<String> = read_list_file("BLOCKED_NAME_TERMS_FILE");
+        let name_blocklist = NameBlocklist::new(reserved_names.as_deref(), blocked_name_terms.as_deref());
+        let auth_methods: Vec<AuthMethod> = std::env::var("AUTH_METHODS")
+            .map(|value| {
+                value
+                    .split(',')
+                    .filter(|entry| !entry.trim().is_empty())
+                    .map(|entry| entry.parse().expect("AUTH_METHODS must be a list of password or api_key"))
+                    .collect()
+            })
+            .unwrap_or_else(|_| AuthMethod::ALL.to_vec());
+
+        if !auth_methods.iter().any(|method| method.signs_in()) {
+            panic!("AUTH_METHODS must enable at least one sign-in method (password), otherwise nobody can sign in");
+        }
+        let cors = CorsConfig::from_env();
+        let security_headers = SecurityHeaders::from_env(environment);
+        let deleted_user_retention = DeletedUserRetention::from_env();
+        let security_questions = SecurityQuestionsConfig::from_env();
+        let verification_reminders = VerificationReminders::from_env();
+        let security_log = SecurityLogConfig::from_env();
+        let verify_email_mx: bool = parse_env("VERIFY_EMAIL_MX").unwrap_or(false);
+        let registration_requires_approval: bool = parse_env("REGISTRATION_REQUIRES_APPROVAL").unwrap_or(false);
+        let terms_version: Option<String> = std::env::var("TERMS_VERSION")
+            .ok()
+            .map(|version| version.trim().to_string())
+            .filter(|version| !version.is_empty());
+        let self_reactivation: bool = parse_env("SELF_REACTIVATION").unwrap_or(true);
+        let role_change_revokes_sessions: bool = parse_env("ROLE_CHANGE_REVOKES_SESSIONS").unwrap_or(true);
+        let invite_codes_required: bool = parse_env("INVITE_CODES_REQUIRED").unwrap_or(false);
+        let expose_email_tokens: bool = parse_env("EXPOSE_EMAIL_TOKENS").unwrap_or(false);
+        let login_response_includes_user: bool = parse_env("LOGIN_RESPONSE_INCLUDE_USER").unwrap_or(false);
+        let email_ignore_case: bool = parse_env("EMAIL_IGNORE_CASE").unwrap_or(false);
+        let captcha: Option<CaptchaConfig> = std::env::var("CAPTCHA_PROVIDER")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| CaptchaConfig {
+                provider: value.parse().expect("CAPTCHA_PROVIDER must be recaptcha, hcaptcha or turnstile"),
+                secret: std::env::var("CAPTCHA_SECRET")
+                    .ok()
+                    .filter(|secret| !secret.trim().is_empty())
+                    .expect("CAPTCHA_SECRET must be set when CAPTCHA_PROVIDER is set"),
+                failure_threshold: parse_env("CAPTCHA_FAILURE_THRESHOLD").unwrap_or(3),
+                window_seconds: parse_env("CAPTCHA_WINDOW_SECONDS")
+                    .filter(|seconds| *seconds > 0)
+                    .unwrap_or(900),
+                blocked_ips: std::env::var("CAPTCHA_BLOCKED_IPS")
+                    .map(|value| {
+                        value
+                            .split(',')
+                            .filter(|entry| !entry.trim().is_empty())
+                            .map(|entry| entry.parse().expect("CAPTCHA_BLOCKED_IPS must be a list of IPs or CIDRs"))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            });
+
+        if environment == Environment::Prod && cookie_domain.is_none() {
+            panic!("COOKIE_DOMAIN must be set when APP_ENV is prod");
+        }
+
+        if environment == Environment::Prod && password_pepper.is_none() {
+            panic!("PASSWORD_PEPPER must be set when APP_ENV is prod");
+        }
+
+        if environment == Environment::Prod && expose_email_tokens {
+            panic!("EXPOSE_EMAIL_TOKENS must not be enabled when APP_ENV is prod");
+        }
 
         Config {
             database_url,
-            jwt_secret,
+            jwt_keys: JwtKeys::new(JwtKey::new(jwt_key_id, jwt_secret), retired_jwt_keys, jwt_leeway_seconds),
             jwt_maxage: jwt_maxage.parse::<i64>().unwrap(),
+            jwt_maxage_admin,
+            jwt_maxage_user,
             port: 8000,
+            environment,
+            error_detail,
+            error_format,
+            cookie_domain,
+            tenant_base_domain,
+            reset_verify_rate_limit,
+            email_availability_rate_limit,
+            registration_cap,
+            introspection_secret,
+            introspection_max_batch,
+            validate_rate_limit,
+            password_max_age_days,
+            password_min_age_hours,
+            verification_grace_days,
+            email_verification_mode,
+            password_reset_mode,
+            trusted_proxies,
+            password_pepper,
+            retired_password_peppers,
+            request_timeout_seconds,
+            heavy_request_timeout_seconds,
+            db_statement_timeout_seconds,
+            db_heavy_statement_timeout_seconds,
+            compression,
+            email_max_attempts,
+            email_retry_base_seconds,
+            user_stats_cache_seconds,
+            dormant_after_days,
+            login_throttle_known,
+            login_throttle_unknown,
+            login_throttle_ip,
+            login_throttle_email,
+            login_throttle_pair,
+            password_reset_limit_account,
+            password_reset_limit_ip,
+            external_base_url,
+            frontend_url,
+            already_verified_path,
+            verification_replay_window_minutes,
+            base_path,
+            captcha,
+            registration_requires_approval,
+            terms_version,
+            self_reactivation,
+            role_change_revokes_sessions,
+            invite_codes_required,
+            expose_email_tokens,
+            login_response_includes_user,
+            email_ignore_case,
+            max_sessions_per_user,
+            session_limit_policy,
+            login_history_ip_masking,
+            password_policy,
+            api_key_rotation_grace_seconds,
+            bootstrap_admin,
+            verify_email_mx,
+            lockout_alerts,
+            email_change_undo_hours,
+            trusted_device_days,
+            impersonation_minutes,
+            reauth_minutes,
+            session_refresh_margin_seconds,
+            token_binding,
+            name_length,
+            name_blocklist,
+            auth_methods,
+            cors,
+            security_headers,
+            state_store,
+            deleted_user_retention,
+            security_questions,
+            verification_reminders,
+            security_log,
         }
     }
+
+    pub fn auth_method_enabled(&self, method: AuthMethod) -> bool {
+        self.auth_methods.contains(&method)
+    }
+
+    pub fn api_url(&self, path: &str) -> String {
+        format!("{}{}/api{}", self.external_base_url, self.base_path, path)
+    }
+
+    pub fn frontend_link(&self, path: &str) -> String {
+        format!("{}{}", self.frontend_url, path)
+    }
+
+    pub fn jwt_maxage_for(&self, role: UserRole) -> i64 {
+        match role {
+            UserRole::Admin => self.jwt_maxage_admin,
+            UserRole::User => self.jwt_maxage_user,
+        }.unwrap_or(self.jwt_maxage)
+    }
+
+    pub fn session_limit_for(&self, user: &User) -> Option<i64> {
+        user.max_sessions.map(i64::from).or(self.max_sessions_per_user)
+    }
+
+    pub fn is_prod(&self) -> bool {
+        self.environment == Environment::Prod
+    }
+
+    pub fn current_pepper_id(&self) -> Option<&str> {
+        self.password_pepper.as_ref().map(|pepper| pepper.id.as_str())
+    }
+
+    pub fn pepper_for(&self, pepper_id: Option<&str>) -> Result<Option<&Pepper>, ErrorMessage> {
+        let Some(pepper_id) = pepper_id else {
+            return Ok(None);
+        };
+
+        self.password_pepper
+            .iter()
+            .chain(self.retired_password_peppers.iter())
+            .find(|pepper| pepper.id == pepper_id)
+            .map(Some)
+            .ok_or(ErrorMessage::UnknownPasswordPepper)
+    }
+}
+
+fn parse_base_url(key: &str, default: &str) -> String {
+    let value = std::env::var(key)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .unwrap_or(default.to_string());
+
+    let url = Url::parse(value.trim())
+        .unwrap_or_else(|e| panic!("{} must be a valid URL: {}", key, e));
+
+    if !matches!(url.scheme(), "http" | "https") || url.host().is_none() {
+        panic!("{} must be an http or https URL with a host", key);
+    }
+
+    if url.query().is_some() || url.fragment().is_some() {
+        panic!("{} must not contain a query or fragment", key);
+    }
+
+    url.as_str().trim_end_matches('/').to_string()
+}
+
+fn parse_base_path(key: &str) -> String {
+    let value = std::env::var(key).unwrap_or_default();
+    let value = value.trim().trim_end_matches('/');
+
+    if value.is_empty() {
+        return String::new();
+    }
+
+    let valid = value.starts_with('/')
+        && value[1..].split('/').all(|segment| {
+            !segment.is_empty() && segment.chars().all(|c| c.is_ascii_alphanumeric() || "-_.~".contains(c))
+        });
+
+    if !valid {
+        panic!("{} must be a path like /auth made of plain segments", key);
+    }
+
+    value.to_string()
+}
+
+fn read_list_file(key: &str) -> Option<String> {
+    std::env::var(key)
+        .ok()
+        .filter(|path| !path.trim().is_empty())
+        .map(|path| {
+            std::fs::read_to_string(&path)
+                .unwrap_or_else(|e| panic!("{} {} could not be read: {}", key, path, e))
+        })
+}
+
+fn parse_env<T: FromStr>(key: &str) -> Option<T> {
+    std::env::var(key).ok().and_then(|value| value.parse().ok())
 }
@@ -1,19 +1,78 @@
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
-use sqlx::{Pool, Postgres};
+use sqlx::{PgConnection, Pool, Postgres, QueryBuilder};
 use uuid::Uuid;
 
-use crate::models::{User, UserRole};
+use crate::config::ScrubField;
+use crate::utils::query::{FieldMap, ListQuery};
+use crate::models::{AccountStatus, ApiKey, AuditEventType, AuditLog, EmailJob, EmailVerificationCode, InviteCode, NotificationPreferences, Organization, PasswordResetCode, SecurityQuestion, Session, SessionBinding, TrustedDevice, User, UserEmail, UserRole, UserStats, VerificationReminder};
+
+// query_as! aliases are column names, so the Json override has to stay within
+// Postgres' 63 byte identifier limit.
+type NotificationPrefsJson = sqlx::types::Json<NotificationPreferences>;
+
+pub const USER_FILTER_FIELDS: FieldMap = FieldMap(&[
+    ("name", "name"),
+    ("email", "email"),
+    ("role", "role"),
+    ("status", "status::text"),
+    ("verified", "verified"),
+    ("locale", "locale"),
+    ("inactive_since", "COALESCE(last_login_at, created_at)"),
+]);
+
+pub const USER_SORT_FIELDS: FieldMap = FieldMap(&[
+    ("name", "name"),
+    ("email", "email"),
+    ("role", "role"),
+    ("created_at", "created_at"),
+    ("updated_at", "updated_at"),
+    ("last_login_at", "last_login_at"),
+]);
+
+pub const AUDIT_FILTER_FIELDS: FieldMap = FieldMap(&[
+    ("user_id", "user_id"),
+    ("event_type", "event_type"),
+    ("from", "created_at"),
+    ("to", "created_at"),
+]);
+
+pub const AUDIT_SORT_FIELDS: FieldMap = FieldMap(&[
+    ("created_at", "created_at"),
+    ("event_type", "event_type"),
+]);
+
+const USER_COLUMNS: &str = "id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role, status, status_reason, max_sessions, org_id, notification_preferences, last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason";
 
 #[derive(Debug, Clone)]
 pub struct DBClient {
     pool: Pool<Postgres>,
+    heavy_statement_timeout: String,
 }
 
 impl DBClient {
-    pub fn new(pool: Pool<Postgres>) -> Self {
-        DBClient { pool }
-    } 
+    pub fn new(pool: Pool<Postgres>, heavy_statement_timeout_seconds: u64) -> Self {
+        DBClient {
+            pool,
+            heavy_statement_timeout: format!("{}s", heavy_statement_timeout_seconds),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn pool(&self) -> &Pool<Postgres> {
+        &self.pool
+    }
+
+    // Bulk operations raise the statement timeout for the rest of their
+    // transaction, every other statement keeps the connection default.
+    async fn extend_statement_timeout(&self, conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+        sqlx::query_scalar!(
+            "SELECT set_config('statement_timeout', $1, true)",
+            self.heavy_statement_timeout
+        ).fetch_one(conn).await?;
+
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -26,22 +85,52 @@ pub trait UserExt {
         token: Option<&str>,
     ) -> Result<Option<User>, sqlx::Error>;
 
+    async fn get_user_in_org(
+        &self,
+        org_id: Uuid,
+        user_id: Uuid
+    ) -> Result<Option<User>, sqlx::Error>;
+
+    async fn get_user_org_id(
+        &self,
+        user_id: Uuid
+    ) -> Result<Option<Uuid>, sqlx::Error>;
+
+    async fn get_user_by_email(
+        &self,
+        org_id: Uuid,
+        email: &str
+    ) -> Result<Option<User>, sqlx::Error>;
+
     async fn get_users (
         &self,
-        page: u32,
-        limit: usize,
+        org_id: Uuid,
+        query: &ListQuery,
     ) -> Result<Vec<User>, sqlx::Error>;
 
+    #[allow(clippy::too_many_arguments)]
     async fn save_user<T: Into<String> + Send> (
         &self,
+        org_id: Uuid,
         name: T, 
         email: T, 
         password: T,
+        password_pepper_id: Option<&str>,
         verification_token: T,
         token_expires_at: DateTime<Utc>,
+        status: AccountStatus,
+        accepted_terms_version: Option<&str>,
     ) -> Result<User, sqlx::Error>;
 
-    async fn get_user_count(&self) -> Result<i64, sqlx::Error>;
+    async fn get_user_count(&self, org_id: Uuid, query: &ListQuery) -> Result<i64, sqlx::Error>;
+
+    async fn create_bootstrap_admin(
+        &self,
+        name: &str,
+        email: &str,
+        password: &str,
+        password_pepper_id: Option<&str>
+    ) -> Result<Option<User>, sqlx::Error>;
 
     async fn update_user_name<T: Into<String> + Send> (
         &self,
@@ -49,22 +138,104 @@ pub trait UserExt {
         name: T,
     ) -> Result<User, sqlx::Error>;
 
+    async fn update_user_profile(
+        &self,
+        user_id: Uuid,
+        name: Option<&str>,
+        display_name: Option<&str>,
+        locale: Option<&str>,
+        avatar_url: Option<&str>,
+    ) -> Result<User, sqlx::Error>;
+
     async fn update_user_role(
         &self,
         user_id: Uuid,
         role: UserRole
     ) -> Result<User, sqlx::Error>;
 
+    async fn update_user_status(
+        &self,
+        user_id: Uuid,
+        status: AccountStatus,
+        reason: Option<&str>
+    ) -> Result<User, sqlx::Error>;
+
+    async fn update_user_frozen(
+        &self,
+        user_id: Uuid,
+        frozen: bool,
+        reason: Option<&str>
+    ) -> Result<User, sqlx::Error>;
+
+    async fn reactivate_user(
+        &self,
+        user_id: Uuid
+    ) -> Result<Option<User>, sqlx::Error>;
+
+    async fn update_user_session_limit(
+        &self,
+        user_id: Uuid,
+        max_sessions: Option<i32>
+    ) -> Result<User, sqlx::Error>;
+
+    async fn update_notification_preferences(
+        &self,
+        user_id: Uuid,
+        preferences: NotificationPreferences
+    ) -> Result<User, sqlx::Error>;
+
+    async fn accept_terms(
+        &self,
+        user_id: Uuid,
+        version: &str
+    ) -> Result<User, sqlx::Error>;
+
+    async fn bulk_update_user_role(
+        &self,
+        user_ids: &[Uuid],
+        role: UserRole
+    ) -> Result<Vec<User>, sqlx::Error>;
+
+    async fn get_admin_count(&self, org_id: Uuid) -> Result<i64, sqlx::Error>;
+
+    async fn get_admin_emails(&self, org_id: Uuid) -> Result<Vec<String>, sqlx::Error>;
+
+    async fn reject_pending_user(
+        &self,
+        user_id: Uuid,
+        reason: Option<&str>
+    ) -> Result<Option<User>, sqlx::Error>;
+
     async fn update_user_password(
         &self,
         user_id: Uuid,
-        password: String
+        password: String,
+        password_pepper_id: Option<&str>
     ) -> Result<User, sqlx::Error>;
 
+    async fn rehash_user_password(
+        &self,
+        user_id: Uuid,
+        password: String,
+        password_pepper_id: Option<&str>
+    ) -> Result<(), sqlx::Error>;
+
     async fn verified_token(
         &self,
         token: &str
-    ) -> Result<(), sqlx::Error>;
+    ) -> Result<bool, sqlx::Error>;
+
+    async fn is_verification_token_consumed(
+        &self,
+        token: &str,
+        consumed_since: DateTime<Utc>
+    ) -> Result<bool, sqlx::Error>;
+
+    async fn merge_users(
+        &self,
+        source_id: Uuid,
+        target_id: Uuid
+    ) -> Result<User, sqlx::Error>;
 
     async fn add_verified_token(
         &self,
@@ -72,6 +243,24 @@ pub trait UserExt {
         token: &str,
         expires_at: DateTime<Utc>
     ) -> Result<(), sqlx::Error>;
+
+    async fn get_user_stats(&self, org_id: Uuid, active_since: DateTime<Utc>) -> Result<UserStats, sqlx::Error>;
+
+    async fn anonymize_deleted_users(
+        &self,
+        deleted_before: DateTime<Utc>,
+        fields: &[ScrubField]
+    ) -> Result<u64, sqlx::Error>;
+
+    async fn claim_verification_reminders(
+        &self,
+        created_after: DateTime<Utc>,
+        created_before: DateTime<Utc>,
+        reminded_before: DateTime<Utc>,
+        max_reminders: i32,
+        token_hashes: &[String],
+        token_expires_at: DateTime<Utc>
+    ) -> Result<Vec<VerificationReminder>, sqlx::Error>;
 }
 
 #[async_trait]
@@ -88,81 +277,193 @@ impl UserExt for DBClient {
         if let Some(user_id) = user_id {
             user = sqlx::query_as!(
                 User,
-                r#"Select id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, role as "role: UserRole" FROM users where id = $1"#,
+                r#"Select id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role as "role: UserRole", status as "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason FROM users where id = $1 AND deleted_at IS NULL"#,
                 user_id
             ).fetch_optional(&self.pool).await?;
         } else if let Some(name) = name {
             user = sqlx::query_as!(
                 User,
-                r#"Select id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, role as "role: UserRole" FROM users where name = $1"#,
+                r#"Select id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role as "role: UserRole", status as "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason FROM users where name = $1 AND deleted_at IS NULL"#,
                 name
             ).fetch_optional(&self.pool).await?;
         } else if let Some(email) = email {
             user = sqlx::query_as!(
                 User,
-                r#"Select id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, role as "role: UserRole" FROM users where email = $1"#,
+                r#"Select id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role as "role: UserRole", status as "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason FROM users where email = $1 AND deleted_at IS NULL"#,
                 email
             ).fetch_optional(&self.pool).await?;
         } else if let Some(token) = token {
             user = sqlx::query_as!(
                 User,
-                r#"Select id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, role as "role: UserRole" FROM users where verification_token = $1"#,
+                r#"Select id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role as "role: UserRole", status as "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason FROM users where verification_token = $1 AND deleted_at IS NULL"#,
                 token
             ).fetch_optional(&self.pool).await?;
         }  
         Ok(user)
     }
     
+    async fn get_user_in_org(
+        &self,
+        org_id: Uuid,
+        user_id: Uuid
+    ) -> Result<Option<User>, sqlx::Error> {
+        let user = sqlx::query_as!(
+            User,
+            r#"Select id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role as "role: UserRole", status as "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason FROM users where id = $1 AND org_id = $2 AND deleted_at IS NULL"#,
+            user_id,
+            org_id
+        ).fetch_optional(&self.pool).await?;
+
+        Ok(user)
+    }
+
+    // Deleted users keep their organization, events about them stay scoped to it.
+    async fn get_user_org_id(
+        &self,
+        user_id: Uuid
+    ) -> Result<Option<Uuid>, sqlx::Error> {
+        let org_id = sqlx::query_scalar!(
+            r#"SELECT org_id FROM users WHERE id = $1"#,
+            user_id
+        ).fetch_optional(&self.pool).await?;
+
+        Ok(org_id)
+    }
+
+    async fn get_user_by_email(
+        &self,
+        org_id: Uuid,
+        email: &str
+    ) -> Result<Option<User>, sqlx::Error> {
+        let user = sqlx::query_as!(
+            User,
+            r#"Select id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role as "role: UserRole", status as "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason FROM users where email = $1 AND org_id = $2 AND deleted_at IS NULL"#,
+            email,
+            org_id
+        ).fetch_optional(&self.pool).await?;
+
+        Ok(user)
+    }
+
     async fn get_users(
         &self,
-        page: u32,
-        limit: usize,
+        org_id: Uuid,
+        query: &ListQuery,
     ) -> Result<Vec<User>, sqlx::Error> {
-        let offset: u32 = (page-1)*limit as u32;
+        let mut builder = QueryBuilder::new(format!("SELECT {} FROM users WHERE deleted_at IS NULL AND org_id = ", USER_COLUMNS));
+        builder.push_bind(org_id);
+        query.push_filters(&mut builder);
+        query.push_sort_and_page(&mut builder);
 
-        let users = sqlx::query_as!(
-            User,
-            r#"SELECT id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, role as "role: UserRole" FROM users
-            ORDER BY created_at DESC LIMIT $1 OFFSET $2"#,
-            limit as i64,
-            offset as i64,
-        ).fetch_all(&self.pool).await?;
+        let users = builder.build_query_as::<User>().fetch_all(&self.pool).await?;
         Ok(users)
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn save_user<T: Into<String> + Send> (
         &self,
+        org_id: Uuid,
         name: T,
         email: T,
         password: T,
+        password_pepper_id: Option<&str>,
         verification_token: T,
-        token_expires_at: DateTime<Utc>
+        token_expires_at: DateTime<Utc>,
+        status: AccountStatus,
+        accepted_terms_version: Option<&str>
     ) -> Result<User, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
         let user = sqlx::query_as!(
             User,
             r#"
-            INSERT INTO users (name, email, password, verification_token, token_expires_at)
-            VALUES ($1, $2, $3, $4, $5)
-            RETURNING id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, role AS "role: UserRole"
+            INSERT INTO users (name, email, password, password_pepper_id, verification_token, token_expires_at, status, org_id, accepted_terms_version, accepted_terms_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, CASE WHEN $9::VARCHAR IS NULL THEN NULL ELSE Now() END)
+            RETURNING id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role AS "role: UserRole", status AS "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason
             "#,
             name.into(),
             email.into(),
             password.into(),
+            password_pepper_id,
             verification_token.into(),
-            token_expires_at
-        ).fetch_one(&self.pool)
+            token_expires_at,
+            status as AccountStatus,
+            org_id,
+            accepted_terms_version
+        ).fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO user_emails (user_id, email, is_primary, org_id)
+            VALUES ($1, $2, TRUE, $3)
+            "#,
+            user.id,
+            user.email,
+            user.org_id
+        ).execute(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         Ok(user)
     }
 
 
-    async fn get_user_count(&self) -> Result<i64, sqlx::Error> {
-        let count = sqlx::query_scalar!(
-            r#"SELECT COUNT(*) FROM users"#
-        ).fetch_one(&self.pool).await?;
+    async fn create_bootstrap_admin(
+        &self,
+        name: &str,
+        email: &str,
+        password: &str,
+        password_pepper_id: Option<&str>
+    ) -> Result<Option<User>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
 
-        Ok(count.unwrap_or(0))
+        sqlx::query!("SELECT pg_advisory_xact_lock(hashtext('bootstrap_admin'))")
+            .execute(&mut *tx)
+            .await?;
+
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            INSERT INTO users (name, email, password, password_pepper_id, verified, role, status, password_changed_at, org_id)
+            SELECT $1, $2, $3, $4, TRUE, 'admin', 'active', Now(), $5
+            WHERE NOT EXISTS (SELECT 1 FROM users WHERE role = 'admin' AND org_id = $5 AND deleted_at IS NULL)
+            RETURNING id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role AS "role: UserRole", status AS "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason
+            "#,
+            name,
+            email,
+            password,
+            password_pepper_id,
+            Organization::DEFAULT_ID
+        ).fetch_optional(&mut *tx)
+        .await?;
+
+        if let Some(user) = &user {
+            sqlx::query!(
+                r#"
+                INSERT INTO user_emails (user_id, email, verified, is_primary, org_id)
+                VALUES ($1, $2, TRUE, TRUE, $3)
+                "#,
+                user.id,
+                user.email,
+                user.org_id
+            ).execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    async fn get_user_count(&self, org_id: Uuid, query: &ListQuery) -> Result<i64, sqlx::Error> {
+        let mut builder = QueryBuilder::new("SELECT COUNT(*) FROM users WHERE deleted_at IS NULL AND org_id = ");
+        builder.push_bind(org_id);
+        query.push_filters(&mut builder);
+
+        let count = builder.build_query_scalar::<i64>().fetch_one(&self.pool).await?;
+        Ok(count)
     }
 
     async fn update_user_name<T: Into<String> + Send> (
@@ -176,7 +477,7 @@ impl UserExt for DBClient {
             UPDATE users
             SET name = $1, updated_at = Now()
             WHERE id = $2
-            RETURNING id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, role AS "role: UserRole"
+            RETURNING id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role AS "role: UserRole", status AS "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason
             "#,
             new_name.into(),
             user_id
@@ -185,6 +486,36 @@ impl UserExt for DBClient {
         Ok(user)
     }
 
+    async fn update_user_profile(
+        &self,
+        user_id: Uuid,
+        name: Option<&str>,
+        display_name: Option<&str>,
+        locale: Option<&str>,
+        avatar_url: Option<&str>,
+    ) -> Result<User, sqlx::Error> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET name = COALESCE($1, name),
+                display_name = COALESCE($2, display_name),
+                locale = COALESCE($3, locale),
+                avatar_url = COALESCE($4, avatar_url),
+                updated_at = Now()
+            WHERE id = $5
+            RETURNING id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role AS "role: UserRole", status AS "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason
+            "#,
+            name,
+            display_name,
+            locale,
+            avatar_url,
+            user_id
+        ).fetch_one(&self.pool).await?;
+
+        Ok(user)
+    }
+
     async fn update_user_role(
         &self,
         user_id: Uuid,
@@ -196,7 +527,7 @@ impl UserExt for DBClient {
             UPDATE users
             SET role = $1, updated_at = Now()
             WHERE id = $2
-            RETURNING id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, role AS "role: UserRole"
+            RETURNING id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role AS "role: UserRole", status AS "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason
             "#,
             new_role as UserRole,
             user_id
@@ -205,60 +536,2588 @@ impl UserExt for DBClient {
         Ok(user)
     }
 
-    async fn update_user_password(
+    async fn update_user_status(
         &self,
         user_id: Uuid,
-        new_password: String
+        status: AccountStatus,
+        reason: Option<&str>
     ) -> Result<User, sqlx::Error> {
         let user = sqlx::query_as!(
             User,
             r#"
             UPDATE users
-            SET password = $1, updated_at = Now()
-            WHERE id = $2
-            RETURNING id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, role AS "role: UserRole"
+            SET status = $1,
+                status_reason = $2,
+                updated_at = Now()
+            WHERE id = $3 AND deleted_at IS NULL
+            RETURNING id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role AS "role: UserRole", status AS "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason
             "#,
-            new_password.into(),
+            status as AccountStatus,
+            reason,
             user_id
         ).fetch_one(&self.pool).await?;
 
         Ok(user)
     }
 
-    async fn verified_token(
+    async fn update_user_frozen(
         &self,
-        token: &str
-    ) -> Result<(), sqlx::Error> {
-        let _ = sqlx::query!(
+        user_id: Uuid,
+        frozen: bool,
+        reason: Option<&str>
+    ) -> Result<User, sqlx::Error> {
+        let user = sqlx::query_as!(
+            User,
             r#"
             UPDATE users
-            SET verified = true, updated_at = Now(), verification_token = NULL, token_expires_at = NULL
-            WHERE verification_token = $1
+            SET frozen = $1,
+                frozen_reason = $2,
+                updated_at = Now()
+            WHERE id = $3 AND deleted_at IS NULL
+            RETURNING id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role AS "role: UserRole", status AS "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason
             "#,
-            token
-        ).execute(&self.pool).await?;
+            frozen,
+            reason,
+            user_id
+        ).fetch_one(&self.pool).await?;
 
-        Ok(())
+        Ok(user)
     }
-    
-    async fn add_verified_token(
+
+    async fn reactivate_user(
+        &self,
+        user_id: Uuid
+    ) -> Result<Option<User>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET status = 'active',
+                status_reason = NULL,
+                tokens_valid_after = date_trunc('second', Now()) + interval '1 second',
+                updated_at = Now()
+            WHERE id = $1 AND status = 'deactivated' AND deleted_at IS NULL
+            RETURNING id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role AS "role: UserRole", status AS "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason
+            "#,
+            user_id
+        ).fetch_optional(&mut *tx).await?;
+
+        if user.is_some() {
+            sqlx::query!(
+                r#"
+                UPDATE sessions
+                SET revoked_at = Now()
+                WHERE user_id = $1 AND revoked_at IS NULL
+                "#,
+                user_id
+            ).execute(&mut *tx).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    async fn update_user_session_limit(
         &self,
         user_id: Uuid,
-        token: &str,
-        token_expires_at: DateTime<Utc>
-    ) -> Result<(), sqlx::Error> {
-        let _ = sqlx::query!(
+        max_sessions: Option<i32>
+    ) -> Result<User, sqlx::Error> {
+        let user = sqlx::query_as!(
+            User,
             r#"
             UPDATE users
-            SET verification_token = $1, token_expires_at = $2, updated_at = Now()
-            where id = $3
+            SET max_sessions = $1,
+                updated_at = Now()
+            WHERE id = $2 AND deleted_at IS NULL
+            RETURNING id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role AS "role: UserRole", status AS "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason
             "#,
-            token,
-            token_expires_at,
+            max_sessions,
             user_id
-        ).execute(&self.pool).await?;
+        ).fetch_one(&self.pool).await?;
 
-        Ok(())
+        Ok(user)
+    }
+
+    async fn update_notification_preferences(
+        &self,
+        user_id: Uuid,
+        preferences: NotificationPreferences
+    ) -> Result<User, sqlx::Error> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET notification_preferences = $1,
+                updated_at = Now()
+            WHERE id = $2 AND deleted_at IS NULL
+            RETURNING id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role AS "role: UserRole", status AS "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason
+            "#,
+            sqlx::types::Json(preferences) as _,
+            user_id
+        ).fetch_one(&self.pool).await?;
+
+        Ok(user)
+    }
+
+    async fn accept_terms(
+        &self,
+        user_id: Uuid,
+        version: &str
+    ) -> Result<User, sqlx::Error> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET accepted_terms_version = $1,
+                accepted_terms_at = Now(),
+                updated_at = Now()
+            WHERE id = $2 AND deleted_at IS NULL
+            RETURNING id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role AS "role: UserRole", status AS "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason
+            "#,
+            version,
+            user_id
+        ).fetch_one(&self.pool).await?;
+
+        Ok(user)
+    }
+
+    async fn bulk_update_user_role(
+        &self,
+        user_ids: &[Uuid],
+        new_role: UserRole
+    ) -> Result<Vec<User>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        self.extend_statement_timeout(&mut tx).await?;
+
+        let users = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET role = $1, updated_at = Now()
+            WHERE id = ANY($2) AND deleted_at IS NULL
+            RETURNING id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role AS "role: UserRole", status AS "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason
+            "#,
+            new_role as UserRole,
+            user_ids
+        ).fetch_all(&mut *tx).await?;
+
+        tx.commit().await?;
+
+        Ok(users)
+    }
+
+    async fn get_admin_count(&self, org_id: Uuid) -> Result<i64, sqlx::Error> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) FROM users WHERE role = 'admin' AND org_id = $1 AND deleted_at IS NULL"#,
+            org_id
+        ).fetch_one(&self.pool).await?;
+
+        Ok(count.unwrap_or(0))
+    }
+
+    async fn get_admin_emails(&self, org_id: Uuid) -> Result<Vec<String>, sqlx::Error> {
+        let emails = sqlx::query_scalar!(
+            r#"SELECT email FROM users WHERE role = 'admin' AND status = 'active' AND org_id = $1 AND deleted_at IS NULL"#,
+            org_id
+        ).fetch_all(&self.pool).await?;
+
+        Ok(emails)
+    }
+
+    async fn reject_pending_user(
+        &self,
+        user_id: Uuid,
+        reason: Option<&str>
+    ) -> Result<Option<User>, sqlx::Error> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET deleted_at = Now(),
+                status_reason = $1,
+                verification_token = NULL,
+                token_expires_at = NULL,
+                updated_at = Now()
+            WHERE id = $2 AND status = 'pending_approval' AND deleted_at IS NULL
+            RETURNING id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role AS "role: UserRole", status AS "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason
+            "#,
+            reason,
+            user_id
+        ).fetch_optional(&self.pool).await?;
+
+        Ok(user)
+    }
+
+    async fn update_user_password(
+        &self,
+        user_id: Uuid,
+        new_password: String,
+        password_pepper_id: Option<&str>
+    ) -> Result<User, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET password = $1, password_pepper_id = $3, password_changed_at = Now(), updated_at = Now()
+            WHERE id = $2
+            RETURNING id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role AS "role: UserRole", status AS "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason
+            "#,
+            new_password,
+            user_id,
+            password_pepper_id
+        ).fetch_one(&mut *tx).await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE trusted_devices
+            SET revoked_at = Now()
+            WHERE user_id = $1 AND revoked_at IS NULL
+            "#,
+            user_id
+        ).execute(&mut *tx).await?;
+
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    async fn rehash_user_password(
+        &self,
+        user_id: Uuid,
+        new_password: String,
+        password_pepper_id: Option<&str>
+    ) -> Result<(), sqlx::Error> {
+        let _ = sqlx::query!(
+            r#"
+            UPDATE users
+            SET password = $1, password_pepper_id = $2
+            WHERE id = $3
+            "#,
+            new_password,
+            password_pepper_id,
+            user_id
+        ).execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    // Only the request whose UPDATE matches consumes the token, a concurrent
+    // or later request with the same token gets false back.
+    async fn verified_token(
+        &self,
+        token: &str
+    ) -> Result<bool, sqlx::Error> {
+        let consumed = sqlx::query_scalar!(
+            r#"
+            WITH verified_user AS (
+                UPDATE users
+                SET verified = true, updated_at = Now(), verification_token = NULL, token_expires_at = NULL,
+                    consumed_verification_token = CASE WHEN verified THEN consumed_verification_token ELSE verification_token END,
+                    consumed_verification_at = CASE WHEN verified THEN consumed_verification_at ELSE Now() END
+                WHERE verification_token = $1 AND token_expires_at > Now()
+                RETURNING id
+            ), verified_email AS (
+                UPDATE user_emails
+                SET verified = true, updated_at = Now()
+                WHERE is_primary AND user_id IN (SELECT id FROM verified_user)
+            )
+            SELECT EXISTS(SELECT 1 FROM verified_user) AS "consumed!"
+            "#,
+            token
+        ).fetch_one(&self.pool).await?;
+
+        Ok(consumed)
+    }
+
+    async fn is_verification_token_consumed(
+        &self,
+        token: &str,
+        consumed_since: DateTime<Utc>
+    ) -> Result<bool, sqlx::Error> {
+        let consumed = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM users
+                WHERE consumed_verification_token = $1 AND consumed_verification_at > $2 AND verified AND deleted_at IS NULL
+            ) AS "consumed!"
+            "#,
+            token,
+            consumed_since
+        ).fetch_one(&self.pool).await?;
+
+        Ok(consumed)
+    }
+    
+    async fn merge_users(
+        &self,
+        source_id: Uuid,
+        target_id: Uuid
+    ) -> Result<User, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        self.extend_statement_timeout(&mut tx).await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE user_emails
+            SET user_id = $1, is_primary = false, updated_at = Now()
+            WHERE user_id = $2
+            "#,
+            target_id,
+            source_id
+        ).execute(&mut *tx).await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE audit_logs
+            SET user_id = $1
+            WHERE user_id = $2
+            "#,
+            target_id,
+            source_id
+        ).execute(&mut *tx).await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET user_id = $1
+            WHERE user_id = $2
+            "#,
+            target_id,
+            source_id
+        ).execute(&mut *tx).await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE api_keys
+            SET user_id = $1
+            WHERE user_id = $2
+            "#,
+            target_id,
+            source_id
+        ).execute(&mut *tx).await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE security_questions
+            SET user_id = $1
+            WHERE user_id = $2
+            "#,
+            target_id,
+            source_id
+        ).execute(&mut *tx).await?;
+
+        // A trusted device only ever passed the source's second factor, carrying
+        // it over would let it skip the target's.
+        sqlx::query!(
+            r#"
+            UPDATE trusted_devices
+            SET revoked_at = Now()
+            WHERE user_id = $1 AND revoked_at IS NULL
+            "#,
+            source_id
+        ).execute(&mut *tx).await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET deleted_at = Now(), verification_token = NULL, token_expires_at = NULL, updated_at = Now()
+            WHERE id = $1 AND deleted_at IS NULL
+            "#,
+            source_id
+        ).execute(&mut *tx).await?;
+
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET updated_at = Now()
+            WHERE id = $1 AND deleted_at IS NULL
+            RETURNING id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role AS "role: UserRole", status AS "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason
+            "#,
+            target_id
+        ).fetch_one(&mut *tx).await?;
+
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    async fn add_verified_token(
+        &self,
+        user_id: Uuid,
+        token: &str,
+        token_expires_at: DateTime<Utc>
+    ) -> Result<(), sqlx::Error> {
+        let _ = sqlx::query!(
+            r#"
+            UPDATE users
+            SET verification_token = $1, token_expires_at = $2, updated_at = Now(),
+                consumed_verification_token = NULL, consumed_verification_at = NULL
+            where id = $3
+            "#,
+            token,
+            token_expires_at,
+            user_id
+        ).execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    async fn get_user_stats(&self, org_id: Uuid, active_since: DateTime<Utc>) -> Result<UserStats, sqlx::Error> {
+        let stats = sqlx::query_as!(
+            UserStats,
+            r#"
+            SELECT
+                COUNT(*) AS "total!",
+                COUNT(*) FILTER (WHERE verified) AS "verified!",
+                COUNT(*) FILTER (WHERE role = 'admin') AS "admins!",
+                COUNT(*) FILTER (WHERE role = 'user') AS "users!",
+                COUNT(*) FILTER (WHERE created_at > Now() - INTERVAL '24 hours') AS "signups_24h!",
+                COUNT(*) FILTER (WHERE created_at > Now() - INTERVAL '7 days') AS "signups_7d!",
+                COUNT(*) FILTER (WHERE created_at > Now() - INTERVAL '30 days') AS "signups_30d!",
+                COUNT(*) FILTER (WHERE COALESCE(last_login_at, created_at) > $2) AS "active!"
+            FROM users
+            WHERE deleted_at IS NULL AND org_id = $1
+            "#,
+            org_id,
+            active_since
+        ).fetch_one(&self.pool).await?;
+
+        Ok(stats)
+    }
+
+    async fn anonymize_deleted_users(
+        &self,
+        deleted_before: DateTime<Utc>,
+        fields: &[ScrubField]
+    ) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let mut builder = QueryBuilder::new("UPDATE users SET anonymized_at = Now()");
+        for field in fields {
+            builder.push(", ");
+            builder.push(scrub_assignment(*field));
+        }
+        builder.push(" WHERE deleted_at IS NOT NULL AND anonymized_at IS NULL AND deleted_at < ");
+        builder.push_bind(deleted_before);
+        builder.push(" RETURNING id");
+
+        let user_ids: Vec<Uuid> = builder
+            .build_query_scalar()
+            .fetch_all(&mut *tx)
+            .await?;
+
+        // Secondary addresses are as personal as the primary one and carry no history worth keeping.
+        if fields.contains(&ScrubField::Email) {
+            sqlx::query!(
+                r#"
+                DELETE FROM user_emails
+                WHERE user_id = ANY($1)
+                "#,
+                &user_ids
+            ).execute(&mut *tx).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(user_ids.len() as u64)
+    }
+
+    // Claims up to one user per token hash and hands each a fresh verification
+    // token, position is the 1-based index of the hash that user received.
+    async fn claim_verification_reminders(
+        &self,
+        created_after: DateTime<Utc>,
+        created_before: DateTime<Utc>,
+        reminded_before: DateTime<Utc>,
+        max_reminders: i32,
+        token_hashes: &[String],
+        token_expires_at: DateTime<Utc>
+    ) -> Result<Vec<VerificationReminder>, sqlx::Error> {
+        let reminders = sqlx::query_as!(
+            VerificationReminder,
+            r#"
+            WITH due AS (
+                SELECT id, ROW_NUMBER() OVER (ORDER BY created_at, id) AS position
+                FROM (
+                    SELECT id, created_at FROM users
+                    WHERE NOT verified AND deleted_at IS NULL
+                    AND created_at > $1 AND created_at <= $2
+                    AND verification_reminders_sent < $4
+                    AND (last_verification_reminder_at IS NULL OR last_verification_reminder_at <= $3)
+                    ORDER BY created_at, id
+                    LIMIT cardinality($5::varchar[])
+                    FOR UPDATE SKIP LOCKED
+                ) candidates
+            ),
+            tokens AS (
+                SELECT token_hash, position
+                FROM UNNEST($5::varchar[]) WITH ORDINALITY AS t(token_hash, position)
+            )
+            UPDATE users
+            SET verification_token = tokens.token_hash,
+                token_expires_at = $6,
+                verification_reminders_sent = verification_reminders_sent + 1,
+                last_verification_reminder_at = Now()
+            FROM due
+            JOIN tokens ON tokens.position = due.position
+            WHERE users.id = due.id
+            RETURNING users.org_id, users.name, users.email, due.position AS "position!"
+            "#,
+            created_after,
+            created_before,
+            reminded_before,
+            max_reminders,
+            token_hashes,
+            token_expires_at
+        ).fetch_all(&self.pool).await?;
+
+        Ok(reminders)
+    }
+}
+
+fn scrub_assignment(field: ScrubField) -> &'static str {
+    match field {
+        ScrubField::Name => "name = 'Deleted user'",
+        ScrubField::Email => "email = 'deleted-' || id || '@anonymized.invalid'",
+        ScrubField::DisplayName => "display_name = NULL",
+        ScrubField::AvatarUrl => "avatar_url = NULL",
+        ScrubField::Locale => "locale = NULL",
+    }
+}
+
+#[async_trait]
+pub trait UserEmailExt {
+    async fn get_user_by_login_email(
+        &self,
+        org_id: Uuid,
+        email: &str
+    ) -> Result<Option<User>, sqlx::Error>;
+
+    async fn get_users_by_login_email_ignore_case(
+        &self,
+        org_id: Uuid,
+        email: &str
+    ) -> Result<Vec<User>, sqlx::Error>;
+
+    async fn is_email_taken(
+        &self,
+        org_id: Uuid,
+        email: &str
+    ) -> Result<bool, sqlx::Error>;
+
+    async fn is_email_taken_ignore_case(
+        &self,
+        org_id: Uuid,
+        email: &str
+    ) -> Result<bool, sqlx::Error>;
+
+    async fn get_user_emails(
+        &self,
+        user_id: Uuid
+    ) -> Result<Vec<UserEmail>, sqlx::Error>;
+
+    async fn get_user_email(
+        &self,
+        user_id: Uuid,
+        email_id: Uuid
+    ) -> Result<Option<UserEmail>, sqlx::Error>;
+
+    async fn get_user_email_by_token(
+        &self,
+        token: &str
+    ) -> Result<Option<UserEmail>, sqlx::Error>;
+
+    async fn add_user_email(
+        &self,
+        user_id: Uuid,
+        email: &str,
+        token: &str,
+        token_expires_at: DateTime<Utc>
+    ) -> Result<UserEmail, sqlx::Error>;
+
+    async fn verify_user_email(
+        &self,
+        email_id: Uuid
+    ) -> Result<(), sqlx::Error>;
+
+    async fn set_primary_email(
+        &self,
+        user_id: Uuid,
+        email_id: Uuid
+    ) -> Result<User, sqlx::Error>;
+
+    async fn delete_user_email(
+        &self,
+        user_id: Uuid,
+        email_id: Uuid
+    ) -> Result<(), sqlx::Error>;
+
+    async fn save_email_change_revert(
+        &self,
+        user_id: Uuid,
+        old_email: &str,
+        new_email: &str,
+        token_hash: &str,
+        expires_at: DateTime<Utc>
+    ) -> Result<(), sqlx::Error>;
+
+    async fn revert_email_change(
+        &self,
+        token_hash: &str
+    ) -> Result<Option<User>, sqlx::Error>;
+}
+
+#[async_trait]
+impl UserEmailExt for DBClient {
+    async fn get_user_by_login_email(
+        &self,
+        org_id: Uuid,
+        email: &str
+    ) -> Result<Option<User>, sqlx::Error> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            SELECT id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role as "role: UserRole", status as "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason FROM users
+            WHERE deleted_at IS NULL AND org_id = $2
+            AND (email = $1 OR id = (SELECT user_id FROM user_emails WHERE email = $1 AND org_id = $2 AND verified))
+            "#,
+            email,
+            org_id
+        ).fetch_optional(&self.pool).await?;
+
+        Ok(user)
+    }
+
+    async fn get_users_by_login_email_ignore_case(
+        &self,
+        org_id: Uuid,
+        email: &str
+    ) -> Result<Vec<User>, sqlx::Error> {
+        let users = sqlx::query_as!(
+            User,
+            r#"
+            SELECT id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role as "role: UserRole", status as "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason FROM users
+            WHERE deleted_at IS NULL AND org_id = $2
+            AND (lower(email) = lower($1) OR id IN (SELECT user_id FROM user_emails WHERE lower(email) = lower($1) AND org_id = $2 AND verified))
+            LIMIT 2
+            "#,
+            email,
+            org_id
+        ).fetch_all(&self.pool).await?;
+
+        Ok(users)
+    }
+
+    async fn is_email_taken(
+        &self,
+        org_id: Uuid,
+        email: &str
+    ) -> Result<bool, sqlx::Error> {
+        let taken = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(SELECT 1 FROM users WHERE email = $1 AND org_id = $2)
+                OR EXISTS(SELECT 1 FROM user_emails WHERE email = $1 AND org_id = $2) AS "taken!"
+            "#,
+            email,
+            org_id
+        ).fetch_one(&self.pool).await?;
+
+        Ok(taken)
+    }
+
+    async fn is_email_taken_ignore_case(
+        &self,
+        org_id: Uuid,
+        email: &str
+    ) -> Result<bool, sqlx::Error> {
+        let taken = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(SELECT 1 FROM users WHERE lower(email) = lower($1) AND org_id = $2)
+                OR EXISTS(SELECT 1 FROM user_emails WHERE lower(email) = lower($1) AND org_id = $2) AS "taken!"
+            "#,
+            email,
+            org_id
+        ).fetch_one(&self.pool).await?;
+
+        Ok(taken)
+    }
+
+    async fn get_user_emails(
+        &self,
+        user_id: Uuid
+    ) -> Result<Vec<UserEmail>, sqlx::Error> {
+        let emails = sqlx::query_as!(
+            UserEmail,
+            r#"
+            SELECT id, user_id, email, verified, is_primary, verification_token, token_expires_at, created_at, updated_at FROM user_emails
+            WHERE user_id = $1
+            ORDER BY is_primary DESC, created_at ASC
+            "#,
+            user_id
+        ).fetch_all(&self.pool).await?;
+
+        Ok(emails)
+    }
+
+    async fn get_user_email(
+        &self,
+        user_id: Uuid,
+        email_id: Uuid
+    ) -> Result<Option<UserEmail>, sqlx::Error> {
+        let email = sqlx::query_as!(
+            UserEmail,
+            r#"
+            SELECT id, user_id, email, verified, is_primary, verification_token, token_expires_at, created_at, updated_at FROM user_emails
+            WHERE id = $1 AND user_id = $2
+            "#,
+            email_id,
+            user_id
+        ).fetch_optional(&self.pool).await?;
+
+        Ok(email)
+    }
+
+    async fn get_user_email_by_token(
+        &self,
+        token: &str
+    ) -> Result<Option<UserEmail>, sqlx::Error> {
+        let email = sqlx::query_as!(
+            UserEmail,
+            r#"
+            SELECT id, user_id, email, verified, is_primary, verification_token, token_expires_at, created_at, updated_at FROM user_emails
+            WHERE verification_token = $1
+            "#,
+            token
+        ).fetch_optional(&self.pool).await?;
+
+        Ok(email)
+    }
+
+    async fn add_user_email(
+        &self,
+        user_id: Uuid,
+        email: &str,
+        token: &str,
+        token_expires_at: DateTime<Utc>
+    ) -> Result<UserEmail, sqlx::Error> {
+        let email = sqlx::query_as!(
+            UserEmail,
+            r#"
+            INSERT INTO user_emails (user_id, email, verification_token, token_expires_at, org_id)
+            VALUES ($1, $2, $3, $4, (SELECT org_id FROM users WHERE id = $1))
+            RETURNING id, user_id, email, verified, is_primary, verification_token, token_expires_at, created_at, updated_at
+            "#,
+            user_id,
+            email,
+            token,
+            token_expires_at
+        ).fetch_one(&self.pool).await?;
+
+        Ok(email)
+    }
+
+    async fn verify_user_email(
+        &self,
+        email_id: Uuid
+    ) -> Result<(), sqlx::Error> {
+        let _ = sqlx::query!(
+            r#"
+            UPDATE user_emails
+            SET verified = true, verification_token = NULL, token_expires_at = NULL, updated_at = Now()
+            WHERE id = $1
+            "#,
+            email_id
+        ).execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    async fn set_primary_email(
+        &self,
+        user_id: Uuid,
+        email_id: Uuid
+    ) -> Result<User, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE user_emails
+            SET is_primary = false, updated_at = Now()
+            WHERE user_id = $1 AND is_primary
+            "#,
+            user_id
+        ).execute(&mut *tx).await?;
+
+        let email = sqlx::query_scalar!(
+            r#"
+            UPDATE user_emails
+            SET is_primary = true, updated_at = Now()
+            WHERE id = $1 AND user_id = $2 AND verified
+            RETURNING email
+            "#,
+            email_id,
+            user_id
+        ).fetch_one(&mut *tx).await?;
+
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET email = $1, verified = true, updated_at = Now()
+            WHERE id = $2
+            RETURNING id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role AS "role: UserRole", status AS "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason
+            "#,
+            email,
+            user_id
+        ).fetch_one(&mut *tx).await?;
+
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    async fn delete_user_email(
+        &self,
+        user_id: Uuid,
+        email_id: Uuid
+    ) -> Result<(), sqlx::Error> {
+        let _ = sqlx::query!(
+            r#"
+            DELETE FROM user_emails
+            WHERE id = $1 AND user_id = $2 AND NOT is_primary
+            "#,
+            email_id,
+            user_id
+        ).execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    async fn save_email_change_revert(
+        &self,
+        user_id: Uuid,
+        old_email: &str,
+        new_email: &str,
+        token_hash: &str,
+        expires_at: DateTime<Utc>
+    ) -> Result<(), sqlx::Error> {
+        let _ = sqlx::query!(
+            r#"
+            INSERT INTO email_change_reverts (user_id, old_email, new_email, token_hash, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            user_id,
+            old_email,
+            new_email,
+            token_hash,
+            expires_at
+        ).execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    async fn revert_email_change(
+        &self,
+        token_hash: &str
+    ) -> Result<Option<User>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let revert = sqlx::query!(
+            r#"
+            UPDATE email_change_reverts
+            SET used_at = Now()
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > Now()
+            RETURNING user_id, old_email, new_email
+            "#,
+            token_hash
+        ).fetch_optional(&mut *tx).await?;
+
+        let Some(revert) = revert else {
+            return Ok(None);
+        };
+
+        sqlx::query!(
+            r#"
+            UPDATE email_change_reverts
+            SET used_at = Now()
+            WHERE user_id = $1 AND used_at IS NULL
+            "#,
+            revert.user_id
+        ).execute(&mut *tx).await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM user_emails
+            WHERE user_id = $1 AND email = $2
+            "#,
+            revert.user_id,
+            revert.new_email
+        ).execute(&mut *tx).await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE user_emails
+            SET is_primary = false, updated_at = Now()
+            WHERE user_id = $1 AND is_primary
+            "#,
+            revert.user_id
+        ).execute(&mut *tx).await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO user_emails (user_id, email, verified, is_primary, org_id)
+            VALUES ($1, $2, true, true, (SELECT org_id FROM users WHERE id = $1))
+            ON CONFLICT (org_id, email)
+            DO UPDATE SET verified = true, is_primary = true, verification_token = NULL, token_expires_at = NULL, updated_at = Now()
+            WHERE user_emails.user_id = EXCLUDED.user_id
+            RETURNING id
+            "#,
+            revert.user_id,
+            revert.old_email
+        ).fetch_one(&mut *tx).await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET revoked_at = Now()
+            WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > Now()
+            "#,
+            revert.user_id
+        ).execute(&mut *tx).await?;
+
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET email = $1, verified = true, tokens_valid_after = date_trunc('second', Now()) + interval '1 second', updated_at = Now()
+            WHERE id = $2
+            RETURNING id, name, email, password, verified, created_at, updated_at, verification_token, token_expires_at, password_changed_at, locale, avatar_url, display_name, deleted_at, tokens_valid_after, password_pepper_id, totp_secret, totp_enabled, role AS "role: UserRole", status AS "status: AccountStatus", status_reason, max_sessions, org_id, notification_preferences AS "notification_preferences: NotificationPrefsJson", last_login_at, accepted_terms_version, accepted_terms_at, frozen, frozen_reason
+            "#,
+            revert.old_email,
+            revert.user_id
+        ).fetch_one(&mut *tx).await?;
+
+        tx.commit().await?;
+
+        Ok(Some(user))
+    }
+}
+
+#[async_trait]
+pub trait TrustedDeviceExt {
+    async fn create_trusted_device(
+        &self,
+        user_id: Uuid,
+        ip_address: Option<&str>,
+        user_agent: Option<&str>,
+        expires_at: DateTime<Utc>
+    ) -> Result<TrustedDevice, sqlx::Error>;
+
+    async fn use_trusted_device(
+        &self,
+        device_id: Uuid,
+        user_id: Uuid
+    ) -> Result<bool, sqlx::Error>;
+
+    async fn get_trusted_devices(
+        &self,
+        user_id: Uuid
+    ) -> Result<Vec<TrustedDevice>, sqlx::Error>;
+
+    async fn revoke_trusted_device(
+        &self,
+        user_id: Uuid,
+        device_id: Uuid
+    ) -> Result<bool, sqlx::Error>;
+
+    async fn revoke_trusted_devices(
+        &self,
+        user_id: Uuid
+    ) -> Result<u64, sqlx::Error>;
+}
+
+#[async_trait]
+impl TrustedDeviceExt for DBClient {
+    async fn create_trusted_device(
+        &self,
+        user_id: Uuid,
+        ip_address: Option<&str>,
+        user_agent: Option<&str>,
+        expires_at: DateTime<Utc>
+    ) -> Result<TrustedDevice, sqlx::Error> {
+        let device = sqlx::query_as!(
+            TrustedDevice,
+            r#"
+            INSERT INTO trusted_devices (user_id, ip_address, user_agent, expires_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, user_id, ip_address, user_agent, created_at, last_used_at, expires_at, revoked_at
+            "#,
+            user_id,
+            ip_address,
+            user_agent,
+            expires_at
+        ).fetch_one(&self.pool).await?;
+
+        Ok(device)
+    }
+
+    async fn use_trusted_device(
+        &self,
+        device_id: Uuid,
+        user_id: Uuid
+    ) -> Result<bool, sqlx::Error> {
+        let device = sqlx::query_scalar!(
+            r#"
+            UPDATE trusted_devices
+            SET last_used_at = Now()
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL AND expires_at > Now()
+            RETURNING id
+            "#,
+            device_id,
+            user_id
+        ).fetch_optional(&self.pool).await?;
+
+        Ok(device.is_some())
+    }
+
+    async fn get_trusted_devices(
+        &self,
+        user_id: Uuid
+    ) -> Result<Vec<TrustedDevice>, sqlx::Error> {
+        let devices = sqlx::query_as!(
+            TrustedDevice,
+            r#"
+            SELECT id, user_id, ip_address, user_agent, created_at, last_used_at, expires_at, revoked_at FROM trusted_devices
+            WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > Now()
+            ORDER BY created_at DESC
+            "#,
+            user_id
+        ).fetch_all(&self.pool).await?;
+
+        Ok(devices)
+    }
+
+    async fn revoke_trusted_device(
+        &self,
+        user_id: Uuid,
+        device_id: Uuid
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE trusted_devices
+            SET revoked_at = Now()
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL AND expires_at > Now()
+            "#,
+            device_id,
+            user_id
+        ).execute(&self.pool).await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn revoke_trusted_devices(
+        &self,
+        user_id: Uuid
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE trusted_devices
+            SET revoked_at = Now()
+            WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > Now()
+            "#,
+            user_id
+        ).execute(&self.pool).await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait]
+pub trait AuditExt {
+    async fn save_audit_log(
+        &self,
+        user_id: Option<Uuid>,
+        event_type: AuditEventType,
+        ip_address: Option<&str>,
+        user_agent: Option<&str>,
+        success: bool,
+        details: Option<&str>,
+    ) -> Result<(), sqlx::Error>;
+
+    async fn get_audit_logs(
+        &self,
+        org_id: Uuid,
+        query: &ListQuery,
+    ) -> Result<Vec<AuditLog>, sqlx::Error>;
+
+    async fn get_audit_log_count(
+        &self,
+        org_id: Uuid,
+        query: &ListQuery,
+    ) -> Result<i64, sqlx::Error>;
+}
+
+#[async_trait]
+impl AuditExt for DBClient {
+    async fn save_audit_log(
+        &self,
+        user_id: Option<Uuid>,
+        event_type: AuditEventType,
+        ip_address: Option<&str>,
+        user_agent: Option<&str>,
+        success: bool,
+        details: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let _ = sqlx::query!(
+            r#"
+            INSERT INTO audit_logs (user_id, event_type, ip_address, user_agent, success, details)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            user_id,
+            event_type.to_str(),
+            ip_address,
+            user_agent,
+            success,
+            details
+        ).execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    async fn get_audit_logs(
+        &self,
+        org_id: Uuid,
+        query: &ListQuery,
+    ) -> Result<Vec<AuditLog>, sqlx::Error> {
+        let mut builder = QueryBuilder::new("SELECT id, user_id, event_type, ip_address, user_agent, success, details, created_at FROM audit_logs WHERE TRUE");
+        push_audit_org_scope(&mut builder, org_id);
+        query.push_filters(&mut builder);
+        query.push_sort_and_page(&mut builder);
+
+        let logs = builder.build_query_as::<AuditLog>().fetch_all(&self.pool).await?;
+        Ok(logs)
+    }
+
+    async fn get_audit_log_count(
+        &self,
+        org_id: Uuid,
+        query: &ListQuery,
+    ) -> Result<i64, sqlx::Error> {
+        let mut builder = QueryBuilder::new("SELECT COUNT(*) FROM audit_logs WHERE TRUE");
+        push_audit_org_scope(&mut builder, org_id);
+        query.push_filters(&mut builder);
+
+        let count = builder.build_query_scalar::<i64>().fetch_one(&self.pool).await?;
+        Ok(count)
+    }
+}
+
+// Events without a user, like logins for unknown emails, cannot be traced to an
+// organization and are only shown to the default one.
+fn push_audit_org_scope(builder: &mut QueryBuilder<'_, Postgres>, org_id: Uuid) {
+    builder.push(" AND (user_id IN (SELECT id FROM users WHERE org_id = ");
+    builder.push_bind(org_id);
+    builder.push(") OR (user_id IS NULL AND ");
+    builder.push_bind(org_id == Organization::DEFAULT_ID);
+    builder.push("))");
+}
+
+#[async_trait]
+pub trait TwoFactorExt {
+    async fn set_totp_secret(
+        &self,
+        user_id: Uuid,
+        secret: &str
+    ) -> Result<(), sqlx::Error>;
+
+    async fn enable_two_factor(
+        &self,
+        user_id: Uuid,
+        code_hashes: &[String]
+    ) -> Result<(), sqlx::Error>;
+
+    async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        code_hashes: &[String]
+    ) -> Result<(), sqlx::Error>;
+
+    async fn consume_recovery_code(
+        &self,
+        user_id: Uuid,
+        code_hash: &str
+    ) -> Result<bool, sqlx::Error>;
+
+    async fn get_recovery_code_count(
+        &self,
+        user_id: Uuid
+    ) -> Result<i64, sqlx::Error>;
+}
+
+#[async_trait]
+impl TwoFactorExt for DBClient {
+    async fn set_totp_secret(
+        &self,
+        user_id: Uuid,
+        secret: &str
+    ) -> Result<(), sqlx::Error> {
+        let _ = sqlx::query!(
+            r#"
+            UPDATE users
+            SET totp_secret = $1, updated_at = Now()
+            WHERE id = $2 AND NOT totp_enabled
+            "#,
+            secret,
+            user_id
+        ).execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    async fn enable_two_factor(
+        &self,
+        user_id: Uuid,
+        code_hashes: &[String]
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET totp_enabled = true, updated_at = Now()
+            WHERE id = $1
+            "#,
+            user_id
+        ).execute(&mut *tx).await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM recovery_codes
+            WHERE user_id = $1
+            "#,
+            user_id
+        ).execute(&mut *tx).await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO recovery_codes (user_id, code_hash)
+            SELECT $1, UNNEST($2::varchar[])
+            "#,
+            user_id,
+            code_hashes
+        ).execute(&mut *tx).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        code_hashes: &[String]
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM recovery_codes
+            WHERE user_id = $1
+            "#,
+            user_id
+        ).execute(&mut *tx).await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO recovery_codes (user_id, code_hash)
+            SELECT $1, UNNEST($2::varchar[])
+            "#,
+            user_id,
+            code_hashes
+        ).execute(&mut *tx).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn consume_recovery_code(
+        &self,
+        user_id: Uuid,
+        code_hash: &str
+    ) -> Result<bool, sqlx::Error> {
+        let consumed = sqlx::query_scalar!(
+            r#"
+            UPDATE recovery_codes
+            SET used_at = Now()
+            WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+            RETURNING id
+            "#,
+            user_id,
+            code_hash
+        ).fetch_optional(&self.pool).await?;
+
+        Ok(consumed.is_some())
+    }
+
+    async fn get_recovery_code_count(
+        &self,
+        user_id: Uuid
+    ) -> Result<i64, sqlx::Error> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) FROM recovery_codes
+            WHERE user_id = $1 AND used_at IS NULL
+            "#,
+            user_id
+        ).fetch_one(&self.pool).await?;
+
+        Ok(count.unwrap_or(0))
+    }
+}
+
+#[async_trait]
+pub trait SessionExt {
+    async fn create_session(
+        &self,
+        user_id: Uuid,
+        ip_address: Option<&str>,
+        user_agent: Option<&str>,
+        binding: &SessionBinding,
+        expires_at: DateTime<Utc>,
+        record_login: bool
+    ) -> Result<Session, sqlx::Error>;
+
+    async fn touch_session(
+        &self,
+        session_id: Uuid,
+        user_id: Uuid
+    ) -> Result<Option<(DateTime<Utc>, SessionBinding)>, sqlx::Error>;
+
+    async fn get_user_sessions(
+        &self,
+        user_id: Uuid,
+        active_only: bool,
+        page: u32,
+        limit: usize
+    ) -> Result<Vec<Session>, sqlx::Error>;
+
+    async fn terminate_user_sessions(
+        &self,
+        user_id: Uuid
+    ) -> Result<u64, sqlx::Error>;
+
+    async fn get_user_session_count(
+        &self,
+        user_id: Uuid,
+        active_only: bool
+    ) -> Result<i64, sqlx::Error>;
+
+    async fn revoke_oldest_sessions(
+        &self,
+        user_id: Uuid,
+        keep: i64
+    ) -> Result<u64, sqlx::Error>;
+
+    async fn revoke_session(
+        &self,
+        session_id: Uuid,
+        user_id: Uuid
+    ) -> Result<bool, sqlx::Error>;
+
+    async fn elevate_session(
+        &self,
+        session_id: Uuid,
+        user_id: Uuid,
+        elevated_until: DateTime<Utc>
+    ) -> Result<bool, sqlx::Error>;
+
+    async fn is_session_elevated(
+        &self,
+        session_id: Uuid,
+        user_id: Uuid
+    ) -> Result<bool, sqlx::Error>;
+
+    async fn get_active_session_expiry(
+        &self,
+        session_id: Uuid,
+        user_id: Uuid
+    ) -> Result<Option<DateTime<Utc>>, sqlx::Error>;
+}
+
+#[async_trait]
+impl SessionExt for DBClient {
+    async fn create_session(
+        &self,
+        user_id: Uuid,
+        ip_address: Option<&str>,
+        user_agent: Option<&str>,
+        binding: &SessionBinding,
+        expires_at: DateTime<Utc>,
+        record_login: bool
+    ) -> Result<Session, sqlx::Error> {
+        let session = sqlx::query_as!(
+            Session,
+            r#"
+            WITH login AS (
+                UPDATE users SET last_login_at = Now()
+                WHERE id = $1 AND $7
+            )
+            INSERT INTO sessions (user_id, ip_address, user_agent, user_agent_hash, network_hash, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, user_id, ip_address, user_agent, created_at, last_used_at, expires_at, revoked_at
+            "#,
+            user_id,
+            ip_address,
+            user_agent,
+            binding.user_agent_hash.as_deref(),
+            binding.network_hash.as_deref(),
+            expires_at,
+            record_login
+        ).fetch_one(&self.pool).await?;
+
+        Ok(session)
+    }
+
+    async fn touch_session(
+        &self,
+        session_id: Uuid,
+        user_id: Uuid
+    ) -> Result<Option<(DateTime<Utc>, SessionBinding)>, sqlx::Error> {
+        let session = sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET last_used_at = Now()
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL AND expires_at > Now()
+            RETURNING expires_at, user_agent_hash, network_hash
+            "#,
+            session_id,
+            user_id
+        ).fetch_optional(&self.pool).await?;
+
+        Ok(session.map(|session| {
+            let binding = SessionBinding {
+                user_agent_hash: session.user_agent_hash,
+                network_hash: session.network_hash,
+            };
+
+            (session.expires_at, binding)
+        }))
+    }
+
+    async fn get_user_sessions(
+        &self,
+        user_id: Uuid,
+        active_only: bool,
+        page: u32,
+        limit: usize
+    ) -> Result<Vec<Session>, sqlx::Error> {
+        let offset: u32 = (page-1)*limit as u32;
+
+        let sessions = sqlx::query_as!(
+            Session,
+            r#"
+            SELECT id, user_id, ip_address, user_agent, created_at, last_used_at, expires_at, revoked_at FROM sessions
+            WHERE user_id = $1
+            AND (NOT $2 OR (revoked_at IS NULL AND expires_at > Now()))
+            ORDER BY last_used_at DESC LIMIT $3 OFFSET $4
+            "#,
+            user_id,
+            active_only,
+            limit as i64,
+            offset as i64,
+        ).fetch_all(&self.pool).await?;
+
+        Ok(sessions)
+    }
+
+    async fn terminate_user_sessions(
+        &self,
+        user_id: Uuid
+    ) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let terminated = sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET revoked_at = Now()
+            WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > Now()
+            "#,
+            user_id
+        ).execute(&mut *tx).await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET tokens_valid_after = date_trunc('second', Now()) + interval '1 second', updated_at = Now()
+            WHERE id = $1
+            "#,
+            user_id
+        ).execute(&mut *tx).await?;
+
+        tx.commit().await?;
+
+        Ok(terminated.rows_affected())
+    }
+
+    async fn get_user_session_count(
+        &self,
+        user_id: Uuid,
+        active_only: bool
+    ) -> Result<i64, sqlx::Error> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) FROM sessions
+            WHERE user_id = $1
+            AND (NOT $2 OR (revoked_at IS NULL AND expires_at > Now()))
+            "#,
+            user_id,
+            active_only
+        ).fetch_one(&self.pool).await?;
+
+        Ok(count.unwrap_or(0))
+    }
+
+    async fn revoke_oldest_sessions(
+        &self,
+        user_id: Uuid,
+        keep: i64
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET revoked_at = Now()
+            WHERE id IN (
+                SELECT id FROM sessions
+                WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > Now()
+                ORDER BY created_at DESC
+                OFFSET $2
+            )
+            "#,
+            user_id,
+            keep
+        ).execute(&self.pool).await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn revoke_session(
+        &self,
+        session_id: Uuid,
+        user_id: Uuid
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET revoked_at = Now()
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+            "#,
+            session_id,
+            user_id
+        ).execute(&self.pool).await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn elevate_session(
+        &self,
+        session_id: Uuid,
+        user_id: Uuid,
+        elevated_until: DateTime<Utc>
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET elevated_until = $3
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL AND expires_at > Now()
+            "#,
+            session_id,
+            user_id,
+            elevated_until
+        ).execute(&self.pool).await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn is_session_elevated(
+        &self,
+        session_id: Uuid,
+        user_id: Uuid
+    ) -> Result<bool, sqlx::Error> {
+        let elevated = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM sessions
+                WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL AND elevated_until > Now()
+            ) AS "elevated!"
+            "#,
+            session_id,
+            user_id
+        ).fetch_one(&self.pool).await?;
+
+        Ok(elevated)
+    }
+
+    async fn get_active_session_expiry(
+        &self,
+        session_id: Uuid,
+        user_id: Uuid
+    ) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        let expires_at = sqlx::query_scalar!(
+            r#"
+            SELECT expires_at FROM sessions
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL AND expires_at > Now()
+            "#,
+            session_id,
+            user_id
+        ).fetch_optional(&self.pool).await?;
+
+        Ok(expires_at)
+    }
+}
+
+#[async_trait]
+pub trait ApiKeyExt {
+    async fn create_api_key(
+        &self,
+        user_id: Uuid,
+        name: &str,
+        prefix: &str,
+        key_hash: &str
+    ) -> Result<ApiKey, sqlx::Error>;
+
+    async fn get_user_api_keys(
+        &self,
+        user_id: Uuid
+    ) -> Result<Vec<ApiKey>, sqlx::Error>;
+
+    async fn get_user_api_key(
+        &self,
+        user_id: Uuid,
+        key_id: Uuid
+    ) -> Result<Option<ApiKey>, sqlx::Error>;
+
+    async fn revoke_api_key(
+        &self,
+        user_id: Uuid,
+        key_id: Uuid
+    ) -> Result<bool, sqlx::Error>;
+
+    async fn rotate_api_key(
+        &self,
+        user_id: Uuid,
+        key_id: Uuid,
+        prefix: &str,
+        key_hash: &str,
+        grace_until: DateTime<Utc>
+    ) -> Result<Option<(ApiKey, ApiKey)>, sqlx::Error>;
+
+    async fn use_api_key(
+        &self,
+        key_hash: &str
+    ) -> Result<Option<Uuid>, sqlx::Error>;
+}
+
+#[async_trait]
+impl ApiKeyExt for DBClient {
+    async fn create_api_key(
+        &self,
+        user_id: Uuid,
+        name: &str,
+        prefix: &str,
+        key_hash: &str
+    ) -> Result<ApiKey, sqlx::Error> {
+        let api_key = sqlx::query_as!(
+            ApiKey,
+            r#"
+            INSERT INTO api_keys (user_id, name, prefix, key_hash)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, user_id, name, prefix, key_hash, created_at, last_used_at, expires_at, revoked_at, rotated_at, replaced_by
+            "#,
+            user_id,
+            name,
+            prefix,
+            key_hash
+        ).fetch_one(&self.pool).await?;
+
+        Ok(api_key)
+    }
+
+    async fn get_user_api_keys(
+        &self,
+        user_id: Uuid
+    ) -> Result<Vec<ApiKey>, sqlx::Error> {
+        let api_keys = sqlx::query_as!(
+            ApiKey,
+            r#"
+            SELECT id, user_id, name, prefix, key_hash, created_at, last_used_at, expires_at, revoked_at, rotated_at, replaced_by FROM api_keys
+            WHERE user_id = $1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > Now())
+            ORDER BY created_at DESC
+            "#,
+            user_id
+        ).fetch_all(&self.pool).await?;
+
+        Ok(api_keys)
+    }
+
+    async fn get_user_api_key(
+        &self,
+        user_id: Uuid,
+        key_id: Uuid
+    ) -> Result<Option<ApiKey>, sqlx::Error> {
+        let api_key = sqlx::query_as!(
+            ApiKey,
+            r#"
+            SELECT id, user_id, name, prefix, key_hash, created_at, last_used_at, expires_at, revoked_at, rotated_at, replaced_by FROM api_keys
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > Now())
+            "#,
+            key_id,
+            user_id
+        ).fetch_optional(&self.pool).await?;
+
+        Ok(api_key)
+    }
+
+    async fn revoke_api_key(
+        &self,
+        user_id: Uuid,
+        key_id: Uuid
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE api_keys
+            SET revoked_at = Now()
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+            "#,
+            key_id,
+            user_id
+        ).execute(&self.pool).await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn rotate_api_key(
+        &self,
+        user_id: Uuid,
+        key_id: Uuid,
+        prefix: &str,
+        key_hash: &str,
+        grace_until: DateTime<Utc>
+    ) -> Result<Option<(ApiKey, ApiKey)>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let current = sqlx::query_as!(
+            ApiKey,
+            r#"
+            SELECT id, user_id, name, prefix, key_hash, created_at, last_used_at, expires_at, revoked_at, rotated_at, replaced_by FROM api_keys
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL AND rotated_at IS NULL
+            AND (expires_at IS NULL OR expires_at > Now())
+            FOR UPDATE
+            "#,
+            key_id,
+            user_id
+        ).fetch_optional(&mut *tx).await?;
+
+        let Some(current) = current else {
+            return Ok(None);
+        };
+
+        let replacement = sqlx::query_as!(
+            ApiKey,
+            r#"
+            INSERT INTO api_keys (user_id, name, prefix, key_hash)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, user_id, name, prefix, key_hash, created_at, last_used_at, expires_at, revoked_at, rotated_at, replaced_by
+            "#,
+            user_id,
+            current.name,
+            prefix,
+            key_hash
+        ).fetch_one(&mut *tx).await?;
+
+        let rotated = sqlx::query_as!(
+            ApiKey,
+            r#"
+            UPDATE api_keys
+            SET rotated_at = Now(), replaced_by = $2, expires_at = LEAST(COALESCE(expires_at, $3), $3)
+            WHERE id = $1
+            RETURNING id, user_id, name, prefix, key_hash, created_at, last_used_at, expires_at, revoked_at, rotated_at, replaced_by
+            "#,
+            current.id,
+            replacement.id,
+            grace_until
+        ).fetch_one(&mut *tx).await?;
+
+        tx.commit().await?;
+
+        Ok(Some((rotated, replacement)))
+    }
+
+    async fn use_api_key(
+        &self,
+        key_hash: &str
+    ) -> Result<Option<Uuid>, sqlx::Error> {
+        let user_id = sqlx::query_scalar!(
+            r#"
+            UPDATE api_keys
+            SET last_used_at = Now()
+            WHERE key_hash = $1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > Now())
+            RETURNING user_id
+            "#,
+            key_hash
+        ).fetch_optional(&self.pool).await?;
+
+        Ok(user_id)
+    }
+}
+
+#[async_trait]
+pub trait EmailJobExt {
+    async fn enqueue_email_job(
+        &self,
+        org_id: Uuid,
+        to_email: &str,
+        subject: &str,
+        template_path: &str,
+        placeholders: &str,
+        max_attempts: i32
+    ) -> Result<EmailJob, sqlx::Error>;
+
+    async fn claim_email_job(
+        &self,
+        lease_until: DateTime<Utc>
+    ) -> Result<Option<EmailJob>, sqlx::Error>;
+
+    async fn mark_email_job_sent(
+        &self,
+        job_id: Uuid
+    ) -> Result<(), sqlx::Error>;
+
+    async fn reschedule_email_job(
+        &self,
+        job_id: Uuid,
+        next_attempt_at: DateTime<Utc>,
+        error: &str
+    ) -> Result<(), sqlx::Error>;
+
+    async fn dead_letter_email_job(
+        &self,
+        job_id: Uuid,
+        error: &str
+    ) -> Result<(), sqlx::Error>;
+
+    async fn get_dead_email_jobs(
+        &self,
+        org_id: Uuid,
+        page: u32,
+        limit: usize
+    ) -> Result<Vec<EmailJob>, sqlx::Error>;
+
+    async fn get_dead_email_job_count(&self, org_id: Uuid) -> Result<i64, sqlx::Error>;
+
+    async fn retry_email_job(
+        &self,
+        org_id: Uuid,
+        job_id: Uuid
+    ) -> Result<Option<EmailJob>, sqlx::Error>;
+}
+
+#[async_trait]
+impl EmailJobExt for DBClient {
+    async fn enqueue_email_job(
+        &self,
+        org_id: Uuid,
+        to_email: &str,
+        subject: &str,
+        template_path: &str,
+        placeholders: &str,
+        max_attempts: i32
+    ) -> Result<EmailJob, sqlx::Error> {
+        let job = sqlx::query_as!(
+            EmailJob,
+            r#"
+            INSERT INTO email_jobs (org_id, to_email, subject, template_path, placeholders, max_attempts)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, to_email, subject, template_path, placeholders, status, attempts, max_attempts, next_attempt_at, last_error, created_at, updated_at
+            "#,
+            org_id,
+            to_email,
+            subject,
+            template_path,
+            placeholders,
+            max_attempts
+        ).fetch_one(&self.pool).await?;
+
+        Ok(job)
+    }
+
+    async fn claim_email_job(
+        &self,
+        lease_until: DateTime<Utc>
+    ) -> Result<Option<EmailJob>, sqlx::Error> {
+        let job = sqlx::query_as!(
+            EmailJob,
+            r#"
+            UPDATE email_jobs
+            SET attempts = attempts + 1, next_attempt_at = $1, updated_at = Now()
+            WHERE id = (
+                SELECT id FROM email_jobs
+                WHERE status = 'pending' AND next_attempt_at <= Now()
+                ORDER BY next_attempt_at
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, to_email, subject, template_path, placeholders, status, attempts, max_attempts, next_attempt_at, last_error, created_at, updated_at
+            "#,
+            lease_until
+        ).fetch_optional(&self.pool).await?;
+
+        Ok(job)
+    }
+
+    async fn mark_email_job_sent(
+        &self,
+        job_id: Uuid
+    ) -> Result<(), sqlx::Error> {
+        let _ = sqlx::query!(
+            r#"
+            UPDATE email_jobs
+            SET status = 'sent', last_error = NULL, updated_at = Now()
+            WHERE id = $1
+            "#,
+            job_id
+        ).execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    async fn reschedule_email_job(
+        &self,
+        job_id: Uuid,
+        next_attempt_at: DateTime<Utc>,
+        error: &str
+    ) -> Result<(), sqlx::Error> {
+        let _ = sqlx::query!(
+            r#"
+            UPDATE email_jobs
+            SET next_attempt_at = $2, last_error = $3, updated_at = Now()
+            WHERE id = $1
+            "#,
+            job_id,
+            next_attempt_at,
+            error
+        ).execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    async fn dead_letter_email_job(
+        &self,
+        job_id: Uuid,
+        error: &str
+    ) -> Result<(), sqlx::Error> {
+        let _ = sqlx::query!(
+            r#"
+            UPDATE email_jobs
+            SET status = 'dead', last_error = $2, updated_at = Now()
+            WHERE id = $1
+            "#,
+            job_id,
+            error
+        ).execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    async fn get_dead_email_jobs(
+        &self,
+        org_id: Uuid,
+        page: u32,
+        limit: usize
+    ) -> Result<Vec<EmailJob>, sqlx::Error> {
+        let offset: u32 = (page-1)*limit as u32;
+
+        let jobs = sqlx::query_as!(
+            EmailJob,
+            r#"
+            SELECT id, to_email, subject, template_path, placeholders, status, attempts, max_attempts, next_attempt_at, last_error, created_at, updated_at FROM email_jobs
+            WHERE org_id = $1 AND status = 'dead'
+            ORDER BY updated_at DESC LIMIT $2 OFFSET $3
+            "#,
+            org_id,
+            limit as i64,
+            offset as i64,
+        ).fetch_all(&self.pool).await?;
+
+        Ok(jobs)
+    }
+
+    async fn get_dead_email_job_count(&self, org_id: Uuid) -> Result<i64, sqlx::Error> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) FROM email_jobs WHERE org_id = $1 AND status = 'dead'"#,
+            org_id
+        ).fetch_one(&self.pool).await?;
+
+        Ok(count.unwrap_or(0))
+    }
+
+    async fn retry_email_job(
+        &self,
+        org_id: Uuid,
+        job_id: Uuid
+    ) -> Result<Option<EmailJob>, sqlx::Error> {
+        let job = sqlx::query_as!(
+            EmailJob,
+            r#"
+            UPDATE email_jobs
+            SET status = 'pending', attempts = 0, next_attempt_at = Now(), updated_at = Now()
+            WHERE id = $1 AND org_id = $2 AND status = 'dead'
+            RETURNING id, to_email, subject, template_path, placeholders, status, attempts, max_attempts, next_attempt_at, last_error, created_at, updated_at
+            "#,
+            job_id,
+            org_id
+        ).fetch_optional(&self.pool).await?;
+
+        Ok(job)
+    }
+}
+
+#[async_trait]
+pub trait VerificationCodeExt {
+    async fn save_verification_code(
+        &self,
+        user_id: Uuid,
+        code_hash: &str,
+        expires_at: DateTime<Utc>
+    ) -> Result<(), sqlx::Error>;
+
+    async fn get_verification_code(
+        &self,
+        user_id: Uuid
+    ) -> Result<Option<EmailVerificationCode>, sqlx::Error>;
+
+    async fn record_verification_code_attempt(
+        &self,
+        user_id: Uuid
+    ) -> Result<(), sqlx::Error>;
+
+    async fn consume_verification_code(
+        &self,
+        user_id: Uuid,
+        code_hash: &str,
+        max_attempts: i32
+    ) -> Result<bool, sqlx::Error>;
+}
+
+#[async_trait]
+impl VerificationCodeExt for DBClient {
+    async fn save_verification_code(
+        &self,
+        user_id: Uuid,
+        code_hash: &str,
+        expires_at: DateTime<Utc>
+    ) -> Result<(), sqlx::Error> {
+        let _ = sqlx::query!(
+            r#"
+            INSERT INTO email_verification_codes (user_id, code_hash, expires_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_id)
+            DO UPDATE SET code_hash = EXCLUDED.code_hash, expires_at = EXCLUDED.expires_at, attempts = 0, created_at = Now()
+            "#,
+            user_id,
+            code_hash,
+            expires_at
+        ).execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    async fn get_verification_code(
+        &self,
+        user_id: Uuid
+    ) -> Result<Option<EmailVerificationCode>, sqlx::Error> {
+        let code = sqlx::query_as!(
+            EmailVerificationCode,
+            r#"
+            SELECT user_id, code_hash, attempts, expires_at, created_at FROM email_verification_codes
+            WHERE user_id = $1
+            "#,
+            user_id
+        ).fetch_optional(&self.pool).await?;
+
+        Ok(code)
+    }
+
+    async fn record_verification_code_attempt(
+        &self,
+        user_id: Uuid
+    ) -> Result<(), sqlx::Error> {
+        let _ = sqlx::query!(
+            r#"
+            UPDATE email_verification_codes
+            SET attempts = attempts + 1
+            WHERE user_id = $1
+            "#,
+            user_id
+        ).execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    async fn consume_verification_code(
+        &self,
+        user_id: Uuid,
+        code_hash: &str,
+        max_attempts: i32
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let consumed = sqlx::query_scalar!(
+            r#"
+            DELETE FROM email_verification_codes
+            WHERE user_id = $1 AND code_hash = $2 AND attempts < $3 AND expires_at > Now()
+            RETURNING user_id
+            "#,
+            user_id,
+            code_hash,
+            max_attempts
+        ).fetch_optional(&mut *tx).await?;
+
+        if consumed.is_none() {
+            return Ok(false);
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET verified = true, updated_at = Now(), verification_token = NULL, token_expires_at = NULL
+            WHERE id = $1
+            "#,
+            user_id
+        ).execute(&mut *tx).await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE user_emails
+            SET verified = true, updated_at = Now()
+            WHERE is_primary AND user_id = $1
+            "#,
+            user_id
+        ).execute(&mut *tx).await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+}
+
+#[async_trait]
+pub trait PasswordResetCodeExt {
+    async fn save_password_reset_code(
+        &self,
+        user_id: Uuid,
+        code_hash: &str,
+        expires_at: DateTime<Utc>
+    ) -> Result<(), sqlx::Error>;
+
+    async fn get_password_reset_code(
+        &self,
+        user_id: Uuid
+    ) -> Result<Option<PasswordResetCode>, sqlx::Error>;
+
+    async fn record_password_reset_code_attempt(
+        &self,
+        user_id: Uuid
+    ) -> Result<(), sqlx::Error>;
+
+    async fn consume_password_reset_code(
+        &self,
+        user_id: Uuid,
+        code_hash: &str,
+        max_attempts: i32
+    ) -> Result<bool, sqlx::Error>;
+}
+
+#[async_trait]
+impl PasswordResetCodeExt for DBClient {
+    async fn save_password_reset_code(
+        &self,
+        user_id: Uuid,
+        code_hash: &str,
+        expires_at: DateTime<Utc>
+    ) -> Result<(), sqlx::Error> {
+        let _ = sqlx::query!(
+            r#"
+            INSERT INTO password_reset_codes (user_id, code_hash, expires_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_id)
+            DO UPDATE SET code_hash = EXCLUDED.code_hash, expires_at = EXCLUDED.expires_at, attempts = 0, created_at = Now()
+            "#,
+            user_id,
+            code_hash,
+            expires_at
+        ).execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    async fn get_password_reset_code(
+        &self,
+        user_id: Uuid
+    ) -> Result<Option<PasswordResetCode>, sqlx::Error> {
+        let code = sqlx::query_as!(
+            PasswordResetCode,
+            r#"
+            SELECT user_id, code_hash, attempts, expires_at, created_at FROM password_reset_codes
+            WHERE user_id = $1
+            "#,
+            user_id
+        ).fetch_optional(&self.pool).await?;
+
+        Ok(code)
+    }
+
+    async fn record_password_reset_code_attempt(
+        &self,
+        user_id: Uuid
+    ) -> Result<(), sqlx::Error> {
+        let _ = sqlx::query!(
+            r#"
+            UPDATE password_reset_codes
+            SET attempts = attempts + 1
+            WHERE user_id = $1
+            "#,
+            user_id
+        ).execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    async fn consume_password_reset_code(
+        &self,
+        user_id: Uuid,
+        code_hash: &str,
+        max_attempts: i32
+    ) -> Result<bool, sqlx::Error> {
+        let consumed = sqlx::query_scalar!(
+            r#"
+            DELETE FROM password_reset_codes
+            WHERE user_id = $1 AND code_hash = $2 AND attempts < $3 AND expires_at > Now()
+            RETURNING user_id
+            "#,
+            user_id,
+            code_hash,
+            max_attempts
+        ).fetch_optional(&self.pool).await?;
+
+        Ok(consumed.is_some())
+    }
+}
+
+#[async_trait]
+pub trait OrganizationExt {
+    async fn get_organization_by_slug(
+        &self,
+        slug: &str
+    ) -> Result<Option<Organization>, sqlx::Error>;
+}
+
+#[async_trait]
+impl OrganizationExt for DBClient {
+    async fn get_organization_by_slug(
+        &self,
+        slug: &str
+    ) -> Result<Option<Organization>, sqlx::Error> {
+        let organization = sqlx::query_as!(
+            Organization,
+            r#"
+            SELECT id, slug, name, created_at FROM organizations
+            WHERE slug = $1
+            "#,
+            slug
+        ).fetch_optional(&self.pool).await?;
+
+        Ok(organization)
+    }
+}
+
+#[async_trait]
+pub trait InviteCodeExt {
+    async fn create_invite_code(
+        &self,
+        org_id: Uuid,
+        code: &str,
+        max_uses: Option<i32>,
+        expires_at: Option<DateTime<Utc>>,
+        created_by: Uuid
+    ) -> Result<InviteCode, sqlx::Error>;
+
+    async fn get_invite_code(
+        &self,
+        org_id: Uuid,
+        code: &str
+    ) -> Result<Option<InviteCode>, sqlx::Error>;
+
+    async fn revoke_invite_code(
+        &self,
+        org_id: Uuid,
+        code_id: Uuid
+    ) -> Result<bool, sqlx::Error>;
+
+    async fn redeem_invite_code(
+        &self,
+        org_id: Uuid,
+        code: &str
+    ) -> Result<Option<Uuid>, sqlx::Error>;
+
+    async fn release_invite_code(
+        &self,
+        code_id: Uuid
+    ) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+impl InviteCodeExt for DBClient {
+    async fn create_invite_code(
+        &self,
+        org_id: Uuid,
+        code: &str,
+        max_uses: Option<i32>,
+        expires_at: Option<DateTime<Utc>>,
+        created_by: Uuid
+    ) -> Result<InviteCode, sqlx::Error> {
+        let invite_code = sqlx::query_as!(
+            InviteCode,
+            r#"
+            INSERT INTO invite_codes (org_id, code, max_uses, expires_at, created_by)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, org_id, code, max_uses, used_count, expires_at, revoked_at, created_by, created_at
+            "#,
+            org_id,
+            code,
+            max_uses,
+            expires_at,
+            created_by
+        ).fetch_one(&self.pool).await?;
+
+        Ok(invite_code)
+    }
+
+    async fn get_invite_code(
+        &self,
+        org_id: Uuid,
+        code: &str
+    ) -> Result<Option<InviteCode>, sqlx::Error> {
+        let invite_code = sqlx::query_as!(
+            InviteCode,
+            r#"
+            SELECT id, org_id, code, max_uses, used_count, expires_at, revoked_at, created_by, created_at FROM invite_codes
+            WHERE org_id = $1 AND code = $2
+            "#,
+            org_id,
+            code
+        ).fetch_optional(&self.pool).await?;
+
+        Ok(invite_code)
+    }
+
+    async fn revoke_invite_code(
+        &self,
+        org_id: Uuid,
+        code_id: Uuid
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE invite_codes
+            SET revoked_at = Now()
+            WHERE id = $1 AND org_id = $2 AND revoked_at IS NULL
+            "#,
+            code_id,
+            org_id
+        ).execute(&self.pool).await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn redeem_invite_code(
+        &self,
+        org_id: Uuid,
+        code: &str
+    ) -> Result<Option<Uuid>, sqlx::Error> {
+        let redeemed = sqlx::query_scalar!(
+            r#"
+            UPDATE invite_codes
+            SET used_count = used_count + 1
+            WHERE org_id = $1 AND code = $2
+            AND revoked_at IS NULL
+            AND (expires_at IS NULL OR expires_at > Now())
+            AND (max_uses IS NULL OR used_count < max_uses)
+            RETURNING id
+            "#,
+            org_id,
+            code
+        ).fetch_optional(&self.pool).await?;
+
+        Ok(redeemed)
+    }
+
+    async fn release_invite_code(
+        &self,
+        code_id: Uuid
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE invite_codes
+            SET used_count = GREATEST(used_count - 1, 0)
+            WHERE id = $1
+            "#,
+            code_id
+        ).execute(&self.pool).await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+pub trait SecurityQuestionExt {
+    async fn get_security_questions(
+        &self,
+        user_id: Uuid
+    ) -> Result<Vec<SecurityQuestion>, sqlx::Error>;
+
+    async fn replace_security_questions(
+        &self,
+        user_id: Uuid,
+        questions: &[String],
+        answer_hashes: &[String],
+        answer_pepper_id: Option<&str>
+    ) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+impl SecurityQuestionExt for DBClient {
+    async fn get_security_questions(
+        &self,
+        user_id: Uuid
+    ) -> Result<Vec<SecurityQuestion>, sqlx::Error> {
+        let questions = sqlx::query_as!(
+            SecurityQuestion,
+            r#"
+            SELECT id, user_id, question, answer_hash, answer_pepper_id, created_at FROM security_questions
+            WHERE user_id = $1
+            ORDER BY created_at, id
+            "#,
+            user_id
+        ).fetch_all(&self.pool).await?;
+
+        Ok(questions)
+    }
+
+    async fn replace_security_questions(
+        &self,
+        user_id: Uuid,
+        questions: &[String],
+        answer_hashes: &[String],
+        answer_pepper_id: Option<&str>
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM security_questions
+            WHERE user_id = $1
+            "#,
+            user_id
+        ).execute(&mut *tx).await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO security_questions (user_id, question, answer_hash, answer_pepper_id)
+            SELECT $1, question, answer_hash, $4
+            FROM UNNEST($2::varchar[], $3::varchar[]) AS t(question, answer_hash)
+            "#,
+            user_id,
+            questions,
+            answer_hashes,
+            answer_pepper_id
+        ).execute(&mut *tx).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+
+    use super::*;
+    use crate::error::HttpError;
+
+    #[sqlx::test(migrations = false)]
+    async fn extended_statement_timeout_cancels_slow_queries(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool, 1);
+        let mut tx = db_client.pool.begin().await.unwrap();
+        db_client.extend_statement_timeout(&mut tx).await.unwrap();
+
+        let error = sqlx::query("SELECT pg_sleep(3)")
+            .execute(&mut *tx)
+            .await
+            .unwrap_err();
+
+        assert_eq!(HttpError::database(error).status, StatusCode::GATEWAY_TIMEOUT);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn extended_statement_timeout_ends_with_the_transaction(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool, 1);
+        let mut conn = db_client.pool.acquire().await.unwrap();
+        let default: String = sqlx::query_scalar("SHOW statement_timeout")
+            .fetch_one(&mut *conn)
+            .await
+            .unwrap();
+
+        let mut tx = sqlx::Connection::begin(&mut *conn).await.unwrap();
+        db_client.extend_statement_timeout(&mut tx).await.unwrap();
+        tx.commit().await.unwrap();
+
+        let after: String = sqlx::query_scalar("SHOW statement_timeout")
+            .fetch_one(&mut *conn)
+            .await
+            .unwrap();
+
+        assert_eq!(after, default);
+    }
+
+    #[sqlx::test]
+    async fn bootstrap_admin_ignores_deleted_and_other_orgs_admins(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool, 1);
+        let other_org: Uuid = sqlx::query_scalar("INSERT INTO organizations (slug, name) VALUES ('other', 'Other') RETURNING id")
+            .fetch_one(&db_client.pool)
+            .await
+            .unwrap();
+
+        sqlx::query("INSERT INTO users (name, email, password, role, org_id) VALUES ('Other', 'admin@other.example', 'x', 'admin', $1)")
+            .bind(other_org)
+            .execute(&db_client.pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO users (name, email, password, role, deleted_at) VALUES ('Gone', 'gone@example.com', 'x', 'admin', Now())")
+            .execute(&db_client.pool)
+            .await
+            .unwrap();
+
+        let admin = db_client.create_bootstrap_admin("Admin", "admin@example.com", "x", None).await.unwrap();
+        assert_eq!(admin.map(|admin| admin.org_id), Some(Organization::DEFAULT_ID));
+
+        let again = db_client.create_bootstrap_admin("Admin", "second@example.com", "x", None).await.unwrap();
+        assert!(again.is_none());
     }
-    
 }
@@ -1,13 +1,42 @@
 use core::str;
+use std::{collections::BTreeMap, sync::OnceLock};
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
-use validator::Validate;
+use validator::{Validate, ValidationErrors};
 
-use crate::models::{UserRole, User};
+use uuid::Uuid;
+
+use crate::config::NameLength;
+use crate::error::field_errors;
+use crate::utils::{csv, device, ip::IpMasking, names::NameBlocklist};
+use crate::models::{AccountStatus, ApiKey, AuditEventType, AuditLog, EmailJob, InviteCode, NotificationPreferences, SecurityQuestion, Session, TrustedDevice, UserRole, User, UserEmail, UserStats};
+
+static NAME_LENGTH: OnceLock<NameLength> = OnceLock::new();
+static NAME_BLOCKLIST: OnceLock<NameBlocklist> = OnceLock::new();
+
+pub fn set_name_length(name_length: NameLength) {
+    let _ = NAME_LENGTH.set(name_length);
+}
+
+fn name_length() -> NameLength {
+    NAME_LENGTH.get().copied().unwrap_or_default()
+}
+
+pub fn set_name_blocklist(name_blocklist: NameBlocklist) {
+    let _ = NAME_BLOCKLIST.set(name_blocklist);
+}
+
+fn check_name_blocklist(name: &str) -> Result<(), validator::ValidationError> {
+    match NAME_BLOCKLIST.get().map(|blocklist| blocklist.check(name)) {
+        Some(Err(message)) => Err(validator::ValidationError::new("blocked_name").with_message(message.into())),
+        _ => Ok(()),
+    }
+}
 
 #[derive(Debug, Validate, Default, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct RegisterUserDto {
-    #[validate(length(min=1, message="Name is Required"))]
+    #[validate(custom(function = "validate_name"))]
     pub name: String,
 
     #[validate(
@@ -25,9 +54,21 @@ pub struct RegisterUserDto {
     )]
     #[serde(rename="passwordConfirm")]
     pub password_confirm: String,
+
+    #[serde(rename="captchaToken", default)]
+    pub captcha_token: Option<String>,
+
+    #[validate(length(max=64, message="Invite code must be at most 64 characters"))]
+    #[serde(rename="inviteCode", default)]
+    pub invite_code: Option<String>,
+
+    #[validate(length(max=64, message="Terms version must be at most 64 characters"))]
+    #[serde(rename="termsVersion", default)]
+    pub terms_version: Option<String>,
 }
 
 #[derive(Debug, Default, Validate, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct LoginUserDto {
     #[validate(
         length(min=1, message="Email is required"),
@@ -37,8 +78,14 @@ pub struct LoginUserDto {
 
     #[validate(length(min=8, message="Password must be at least 8 characters"))]
     pub password: String,
+
+    #[serde(rename="captchaToken", default)]
+    pub captcha_token: Option<String>,
 }
 
+const DEFAULT_PAGE_LIMIT: usize = 10;
+const MAX_PAGE_LIMIT: usize = 50;
+
 #[derive(Debug, Serialize, Deserialize, Validate)]
 pub struct RequestQueryDto {
     #[validate(range(min=1))]
@@ -48,13 +95,106 @@ pub struct RequestQueryDto {
     pub limit: Option<usize>,
 }
 
+impl RequestQueryDto {
+    pub fn page(&self) -> usize {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Paginated<T> {
+    pub status: String,
+    pub data: Vec<T>,
+    pub page: usize,
+    pub limit: usize,
+    pub total: i64,
+    #[serde(rename="totalPages")]
+    pub total_pages: i64,
+}
+
+impl<T> Paginated<T> {
+    pub fn new(data: Vec<T>, query: &RequestQueryDto, total: i64) -> Self {
+        let limit = query.limit();
+
+        Paginated {
+            status: "success".to_string(),
+            data,
+            page: query.page(),
+            limit,
+            total,
+            total_pages: (total + limit as i64 - 1) / limit as i64,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct UserListQueryDto {
+    #[validate(length(max=100, message="Search must be at most 100 characters"))]
+    pub search: Option<String>,
+    pub role: Option<String>,
+    pub status: Option<String>,
+    pub verified: Option<bool>,
+    pub inactive_since: Option<DateTime<Utc>>,
+    pub sort_by: Option<String>,
+    pub order: Option<String>,
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FieldsQueryDto {
+    pub fields: Option<String>,
+}
+
+impl FieldsQueryDto {
+    pub fn selection(&self) -> Result<Option<Vec<&str>>, String> {
+        let Some(fields) = self.fields.as_deref() else {
+            return Ok(None);
+        };
+
+        let mut selected: Vec<&str> = Vec::new();
+        for field in fields.split(',').map(str::trim).filter(|field| !field.is_empty()) {
+            if !FilterUserDto::FIELDS.contains(&field) {
+                return Err(format!("Unknown field '{}', allowed fields are {}", field, FilterUserDto::FIELDS.join(", ")));
+            }
+            if !selected.contains(&field) {
+                selected.push(field);
+            }
+        }
+
+        if selected.is_empty() {
+            return Err("Fields must list at least one field".to_string());
+        }
+
+        Ok(Some(selected))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FilterUserDto {
     pub id: String,
     pub name: String,
+    #[serde(rename="displayName")]
+    pub display_name: String,
     pub email: String,
     pub role: String,
+    #[serde(rename="roleLevel")]
+    pub role_level: i32,
+    pub status: String,
     pub verified: bool,
+    pub frozen: bool,
+    pub locale: Option<String>,
+    #[serde(rename="avatarUrl")]
+    pub avatar_url: Option<String>,
+    #[serde(rename="lastLoginAt")]
+    pub last_login_at: Option<DateTime<Utc>>,
+    #[serde(rename="acceptedTermsVersion")]
+    pub accepted_terms_version: Option<String>,
+    #[serde(rename="acceptedTermsAt")]
+    pub accepted_terms_at: Option<DateTime<Utc>>,
     #[serde(rename="createdAt")]
     pub created_at: DateTime<Utc>,
     #[serde(rename="updatedAt")]
@@ -62,13 +202,24 @@ pub struct FilterUserDto {
 }
 
 impl FilterUserDto {
+    pub const FIELDS: [&'static str; 16] = ["id", "name", "displayName", "email", "role", "roleLevel", "status", "verified", "frozen", "locale", "avatarUrl", "lastLoginAt", "acceptedTermsVersion", "acceptedTermsAt", "createdAt", "updatedAt"];
+
     pub fn filter_user(user: &User) -> Self {
         FilterUserDto {
             id: user.id.to_string(),
             name: user.name.to_owned(),
+            display_name: user.display_name().to_owned(),
             email: user.email.to_owned(),
             verified: user.verified,
             role: user.role.to_str().to_string(),
+            role_level: user.role.level(),
+            status: user.status.to_str().to_string(),
+            frozen: user.frozen,
+            locale: user.locale.to_owned(),
+            avatar_url: user.avatar_url.to_owned(),
+            last_login_at: user.last_login_at,
+            accepted_terms_version: user.accepted_terms_version.to_owned(),
+            accepted_terms_at: user.accepted_terms_at,
             created_at: user.created_at.unwrap(),
             updated_at: user.updated_at.unwrap(),
         }
@@ -77,32 +228,287 @@ impl FilterUserDto {
     pub fn filter_users(user: &[User]) -> Vec<FilterUserDto> {
         user.iter().map(FilterUserDto::filter_user).collect()
     }
+
+    pub fn project(&self, fields: &[&str]) -> serde_json::Value {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        if let Some(object) = value.as_object_mut() {
+            object.retain(|key, _| fields.contains(&key.as_str()));
+        }
+        value
+    }
+
+    pub fn csv_header() -> String {
+        csv::row(&FilterUserDto::FIELDS)
+    }
+
+    pub fn to_csv_row(&self) -> String {
+        csv::row(&[
+            &self.id,
+            &self.name,
+            &self.display_name,
+            &self.email,
+            &self.role,
+            &self.role_level.to_string(),
+            &self.status,
+            if self.verified { "true" } else { "false" },
+            if self.frozen { "true" } else { "false" },
+            self.locale.as_deref().unwrap_or_default(),
+            self.avatar_url.as_deref().unwrap_or_default(),
+            &self.last_login_at.map(|at| at.to_rfc3339()).unwrap_or_default(),
+            self.accepted_terms_version.as_deref().unwrap_or_default(),
+            &self.accepted_terms_at.map(|at| at.to_rfc3339()).unwrap_or_default(),
+            &self.created_at.to_rfc3339(),
+            &self.updated_at.to_rfc3339(),
+        ])
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct UserData {
+pub struct ProfileDto {
+    #[serde(flatten)]
     pub user: FilterUserDto,
+    #[serde(rename="notificationPreferences")]
+    pub notification_preferences: NotificationPreferences,
+    #[serde(rename="termsAcceptanceRequired")]
+    pub terms_acceptance_required: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Validate, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationPreferencesUpdateDto {
+    pub welcome: Option<bool>,
+    #[serde(rename="lockoutNotices")]
+    pub lockout_notices: Option<bool>,
+}
+
+impl NotificationPreferencesUpdateDto {
+    pub fn apply(&self, current: NotificationPreferences) -> NotificationPreferences {
+        NotificationPreferences {
+            welcome: self.welcome.unwrap_or(current.welcome),
+            lockout_notices: self.lockout_notices.unwrap_or(current.lockout_notices),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NotificationPreferencesResponseDto {
+    pub status: String,
+    #[serde(rename="notificationPreferences")]
+    pub notification_preferences: NotificationPreferences,
+}
+
+#[derive(Debug, Default, Validate, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AcceptTermsDto {
+    #[validate(length(min=1, max=64, message="Terms version must be between 1 and 64 characters"))]
+    pub version: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TermsStatusDto {
+    #[serde(rename="currentVersion")]
+    pub current_version: Option<String>,
+    #[serde(rename="acceptedVersion")]
+    pub accepted_version: Option<String>,
+    #[serde(rename="acceptedAt")]
+    pub accepted_at: Option<DateTime<Utc>>,
+    #[serde(rename="acceptanceRequired")]
+    pub acceptance_required: bool,
+}
+
+impl TermsStatusDto {
+    pub fn from_user(user: &User, current_version: Option<&str>) -> Self {
+        TermsStatusDto {
+            current_version: current_version.map(|version| version.to_string()),
+            accepted_version: user.accepted_terms_version.to_owned(),
+            accepted_at: user.accepted_terms_at,
+            acceptance_required: user.terms_acceptance_required(current_version),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TermsStatusResponseDto {
+    pub status: String,
+    pub data: TermsStatusDto,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PublicUserDto {
+    pub id: String,
+    pub name: String,
+    #[serde(rename="displayName")]
+    pub display_name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum UserViewDto {
+    Full(FilterUserDto),
+    Public(PublicUserDto),
+}
+
+impl UserViewDto {
+    pub fn for_viewer(viewer: &User, user: &User) -> Self {
+        if viewer.id == user.id || viewer.role == UserRole::Admin {
+            UserViewDto::Full(FilterUserDto::filter_user(user))
+        } else {
+            UserViewDto::Public(PublicUserDto {
+                id: user.id.to_string(),
+                name: user.name.to_owned(),
+                display_name: user.display_name().to_owned(),
+            })
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserViewResponseDto {
+    pub status: String,
+    pub user: UserViewDto,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountSummaryDto {
+    pub id: String,
+    pub email: String,
+    pub role: String,
+    #[serde(rename="createdAt")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl AccountSummaryDto {
+    pub fn from_filtered(user: FilterUserDto) -> Self {
+        AccountSummaryDto {
+            id: user.id,
+            email: user.email,
+            role: user.role,
+            created_at: user.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignedSummaryResponseDto {
+    pub status: String,
+    pub summary: String,
+    #[serde(rename="expiresAt")]
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+pub struct VerifySummaryDto {
+    #[validate(length(min=1, message="Summary is required"))]
+    pub summary: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct UserResponseDto {
+pub struct VerifiedSummaryResponseDto {
     pub status: String,
-    pub data: UserData,
+    pub data: AccountSummaryDto,
+    #[serde(rename="issuedAt")]
+    pub issued_at: DateTime<Utc>,
+    #[serde(rename="expiresAt")]
+    pub expires_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct UserListResponseDto {
+pub struct UserData<T = FilterUserDto> {
+    pub user: T,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserResponseDto<T = FilterUserDto> {
     pub status: String,
-    pub users: Vec<FilterUserDto>,
-    pub results: i64,
+    pub data: UserData<T>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserLoginResponseDto {
     pub status: String, 
     pub token: String,
+    #[serde(rename="expiresAt")]
+    pub expires_at: DateTime<Utc>,
+    #[serde(rename="mustChangePassword")]
+    pub must_change_password: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<FilterUserDto>,
+}
+
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorChallengeResponseDto {
+    pub status: String,
+    #[serde(rename="twoFactorRequired")]
+    pub two_factor_required: bool,
+    #[serde(rename="challengeToken")]
+    pub challenge_token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReactivationChallengeResponseDto {
+    pub status: String,
+    #[serde(rename="reactivationRequired")]
+    pub reactivation_required: bool,
+    #[serde(rename="reactivationToken")]
+    pub reactivation_token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserSecurityDto {
+    #[serde(rename="passwordChangedAt")]
+    pub password_changed_at: Option<DateTime<Utc>>,
+    #[serde(rename="passwordExpiresAt")]
+    pub password_expires_at: Option<DateTime<Utc>>,
+    #[serde(rename="mustChangePassword")]
+    pub must_change_password: bool,
+    #[serde(rename="twoFactorEnabled")]
+    pub two_factor_enabled: bool,
+    #[serde(rename="recoveryCodesRemaining")]
+    pub recovery_codes_remaining: i64,
+    pub verified: bool,
+    #[serde(rename="verificationDeadline")]
+    pub verification_deadline: Option<DateTime<Utc>>,
+    #[serde(rename="trustedDevices")]
+    pub trusted_devices: Vec<TrustedDeviceDto>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrustedDeviceDto {
+    pub id: String,
+    #[serde(rename="ipAddress")]
+    pub ip_address: Option<String>,
+    #[serde(rename="userAgent")]
+    pub user_agent: Option<String>,
+    #[serde(rename="createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename="lastUsedAt")]
+    pub last_used_at: Option<DateTime<Utc>>,
+    #[serde(rename="expiresAt")]
+    pub expires_at: DateTime<Utc>,
+}
+
+impl TrustedDeviceDto {
+    pub fn filter_devices(devices: &[TrustedDevice]) -> Vec<TrustedDeviceDto> {
+        devices
+            .iter()
+            .map(|device| TrustedDeviceDto {
+                id: device.id.to_string(),
+                ip_address: device.ip_address.to_owned(),
+                user_agent: device.user_agent.to_owned(),
+                created_at: device.created_at,
+                last_used_at: device.last_used_at,
+                expires_at: device.expires_at,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserSecurityResponseDto {
+    pub status: String,
+    pub data: UserSecurityDto,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Response {
@@ -110,26 +516,176 @@ pub struct Response {
     pub message: String,
 }
 
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DevEmailDto {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailSentResponseDto {
+    pub status: &'static str,
+    pub message: String,
+    #[serde(rename="devEmail", skip_serializing_if = "Option::is_none")]
+    pub dev_email: Option<DevEmailDto>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Validate, Default, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct NameUpdateDto {
-    #[validate(length(min=1, message="Name is required"))]
+    #[validate(custom(function = "validate_name"))]
     pub name: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Validate, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileUpdateDto {
+    #[validate(custom(function = "validate_name"))]
+    pub name: Option<String>,
+
+    #[validate(custom(function = "validate_display_name"))]
+    #[serde(rename="displayName")]
+    pub display_name: Option<String>,
+
+    #[validate(custom(function = "validate_locale", message="Locale is invalid"))]
+    pub locale: Option<String>,
+
+    #[validate(
+        length(max=2048, message="Avatar URL is too long"),
+        url(message="Avatar URL is invalid")
+    )]
+    #[serde(rename="avatarUrl")]
+    pub avatar_url: Option<String>,
+}
+
+impl ProfileUpdateDto {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.display_name.is_none() && self.locale.is_none() && self.avatar_url.is_none()
+    }
+}
+
+fn validate_name(name: &str) -> Result<(), validator::ValidationError> {
+    let NameLength { min, max } = name_length();
+    let length = name.trim().chars().count();
+
+    let message = if length == 0 {
+        "Name is required".to_string()
+    } else if length < min {
+        format!("Name must be at least {} characters", min)
+    } else if length > max {
+        format!("Name must be at most {} characters", max)
+    } else {
+        return check_name_blocklist(name);
+    };
+
+    Err(validator::ValidationError::new("invalid_name").with_message(message.into()))
+}
+
+fn validate_display_name(display_name: &str) -> Result<(), validator::ValidationError> {
+    let length = display_name.trim().chars().count();
+
+    if (1..=50).contains(&length) && !display_name.chars().any(char::is_control) {
+        check_name_blocklist(display_name)
+    } else {
+        Err(validator::ValidationError::new("invalid_display_name")
+            .with_message("Display name must be 1-50 characters without control characters".into()))
+    }
+}
+
+fn validate_locale(locale: &str) -> Result<(), validator::ValidationError> {
+    let mut subtags = locale.split(['-', '_']);
+
+    let language_valid = subtags
+        .next()
+        .map(|language| (2..=3).contains(&language.len()) && language.chars().all(|c| c.is_ascii_alphabetic()))
+        .unwrap_or(false);
+
+    let subtags_valid = subtags
+        .all(|subtag| (2..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric()));
+
+    if locale.len() <= 35 && language_valid && subtags_valid {
+        Ok(())
+    } else {
+        Err(validator::ValidationError::new("invalid_locale"))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Validate, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct RoleUpdateDto {
     #[validate(custom(function = "validate_user_role"))]
     pub role: UserRole,
+
+    #[serde(default)]
+    pub user_id: Option<uuid::Uuid>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct StatusUpdateDto {
+    pub status: AccountStatus,
+
+    #[validate(length(min=1, max=255, message="Reason must be between 1 and 255 characters"))]
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Validate, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FreezeUserDto {
+    #[validate(length(min=1, max=255, message="Reason must be between 1 and 255 characters"))]
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Validate, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RejectUserDto {
+    #[validate(length(min=1, max=255, message="Reason must be between 1 and 255 characters"))]
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct BulkRoleUpdateDto {
+    #[validate(length(min=1, max=100, message="Between 1 and 100 user ids are required"))]
+    pub ids: Vec<String>,
+
+    #[validate(custom(function = "validate_user_role"))]
+    pub role: UserRole,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BulkRoleResultDto {
+    pub id: String,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BulkRoleResponseDto {
+    pub status: String,
+    pub results: Vec<BulkRoleResultDto>,
 }
 
 fn validate_user_role(role: &UserRole) -> Result<(), validator::ValidationError> {
     match role {
         UserRole::Admin | UserRole::User => Ok(()),
-        _ => Err(validator::ValidationError::new("invalid_role")),
     }
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MergeUsersDto {
+    pub source_id: uuid::Uuid,
+    pub target_id: uuid::Uuid,
+}
+
 #[derive(Debug, Default, Clone, Validate, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct UserPasswordUpdateDto {
     #[validate(length(min=8, message="Password must be at least 8 characters"))]
     pub new_password: String,
@@ -150,6 +706,28 @@ pub struct VerifyEmailQueryDto {
     pub token: String,
 }
 
+#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct VerifyEmailCodeDto {
+    #[validate(
+        length(min=1, message="Email is required"),
+        email(message="Email is invalid")
+    )]
+    pub email: String,
+
+    #[validate(length(min=6, max=6, message="Code must be 6 digits"))]
+    pub code: String,
+}
+
+#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+pub struct ResendVerificationCodeDto {
+    #[validate(
+        length(min=1, message="Email is required"),
+        email(message="Email is invalid")
+    )]
+    pub email: String,
+}
+
 #[derive(Debug, Clone, Validate, Deserialize, Serialize)]
 pub struct ForgotPasswordRequestDto {
     #[validate(length(min=1, message= "Email is required"))]
@@ -157,6 +735,7 @@ pub struct ForgotPasswordRequestDto {
 }
 
 #[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct ResetPasswordRequestDto {
     #[validate(length(min=1, message="Token is Required"))]
     pub token: String,
@@ -170,3 +749,693 @@ pub struct ResetPasswordRequestDto {
     )]
         pub new_password_confirm: String,
 }
+
+#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResetPasswordWithCodeDto {
+    #[validate(
+        length(min=1, message="Email is required"),
+        email(message="Email is invalid")
+    )]
+    pub email: String,
+
+    #[validate(length(min=6, max=6, message="Code must be 6 digits"))]
+    pub code: String,
+
+    #[validate(length(min=8, message="New password must be at least 8 characters"))]
+    pub new_password: String,
+
+    #[validate(
+        length(min=8, message="New password confirm must be at least 8 characters"),
+        must_match(other="new_password", message="New passwords do not match")
+    )]
+    pub new_password_confirm: String,
+}
+
+#[derive(Validate, Serialize, Deserialize)]
+pub struct UndoEmailChangeDto {
+    #[validate(length(min=1, message="Token is required"))]
+    pub token: String,
+}
+
+#[derive(Validate, Serialize, Deserialize)]
+pub struct ResetTokenQueryDto {
+    #[validate(length(min=1, message="Token is required"))]
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResetTokenStatusDto {
+    pub valid: bool,
+}
+
+#[derive(Validate, Serialize, Deserialize)]
+pub struct EmailAvailabilityQueryDto {
+    #[validate(
+        length(min=1, message="Email is required"),
+        email(message="Email is invalid")
+    )]
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailAvailabilityDto {
+    pub available: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorSetupDto {
+    pub secret: String,
+    #[serde(rename="otpauthUrl")]
+    pub otpauth_url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorSetupResponseDto {
+    pub status: String,
+    pub data: TwoFactorSetupDto,
+}
+
+#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+pub struct TwoFactorCodeDto {
+    #[validate(length(min=6, max=6, message="Code must be 6 digits"))]
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoveryCodesResponseDto {
+    pub status: String,
+    #[serde(rename="recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+pub struct TwoFactorLoginDto {
+    #[validate(length(min=1, message="Challenge token is required"))]
+    pub challenge_token: String,
+
+    #[validate(length(min=6, max=6, message="Code must be 6 digits"))]
+    pub code: String,
+
+    #[serde(default)]
+    pub trust_device: bool,
+}
+
+#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReauthDto {
+    #[validate(length(min=1, message="Password is required"))]
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthMethodsResponseDto {
+    pub status: String,
+    pub methods: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReauthResponseDto {
+    pub status: String,
+    #[serde(rename="elevatedUntil")]
+    pub elevated_until: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionStatusDto {
+    pub status: String,
+    #[serde(rename="expiresAt")]
+    pub expires_at: DateTime<Utc>,
+    #[serde(rename="secondsRemaining")]
+    pub seconds_remaining: i64,
+    #[serde(rename="refreshAt")]
+    pub refresh_at: DateTime<Utc>,
+    #[serde(rename="refreshExpiresAt")]
+    pub refresh_expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+pub struct ReactivateAccountDto {
+    #[validate(length(min=1, message="Reactivation token is required"))]
+    pub reactivation_token: String,
+}
+
+#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+pub struct RecoveryLoginDto {
+    #[validate(length(min=1, message="Challenge token is required"))]
+    pub challenge_token: String,
+
+    #[validate(length(min=1, message="Recovery code is required"))]
+    pub recovery_code: String,
+}
+
+#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+pub struct SecurityQuestionInputDto {
+    #[validate(length(min=1, max=255, message="Question must be between 1 and 255 characters"))]
+    pub question: String,
+
+    #[validate(length(min=1, max=64, message="Answer must be between 1 and 64 characters"))]
+    pub answer: String,
+}
+
+#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+pub struct SetSecurityQuestionsDto {
+    #[validate(nested)]
+    pub questions: Vec<SecurityQuestionInputDto>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SecurityQuestionDto {
+    pub id: String,
+    pub question: String,
+}
+
+impl SecurityQuestionDto {
+    pub fn filter_question(question: &SecurityQuestion) -> Self {
+        SecurityQuestionDto {
+            id: question.id.to_string(),
+            question: question.question.to_owned(),
+        }
+    }
+
+    pub fn filter_questions(questions: &[SecurityQuestion]) -> Vec<SecurityQuestionDto> {
+        questions.iter().map(SecurityQuestionDto::filter_question).collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SecurityQuestionListResponseDto {
+    pub status: String,
+    pub questions: Vec<SecurityQuestionDto>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SecurityQuestionChallengeResponseDto {
+    pub status: String,
+    #[serde(rename="challengeToken")]
+    pub challenge_token: String,
+    pub questions: Vec<SecurityQuestionDto>,
+}
+
+#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+pub struct SecurityAnswerDto {
+    #[validate(length(min=1, message="Question id is required"))]
+    pub id: String,
+
+    #[validate(length(min=1, max=64, message="Answer must be between 1 and 64 characters"))]
+    pub answer: String,
+}
+
+#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+pub struct SecurityQuestionRecoveryDto {
+    #[serde(rename="challengeToken")]
+    #[validate(length(min=1, message="Challenge token is required"))]
+    pub challenge_token: String,
+
+    #[validate(nested)]
+    pub answers: Vec<SecurityAnswerDto>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SecurityQuestionRecoveryResponseDto {
+    pub status: String,
+    #[serde(rename="resetToken")]
+    pub reset_token: String,
+    #[serde(rename="expiresAt")]
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct IntrospectBatchDto {
+    #[validate(length(min=1, message="At least one token is required"))]
+    pub tokens: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct IntrospectionResultDto {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IntrospectBatchResponseDto {
+    pub status: String,
+    pub results: Vec<IntrospectionResultDto>,
+}
+
+#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+pub struct AddEmailDto {
+    #[validate(
+        length(min=1, message="Email is required"),
+        email(message="Email is invalid")
+    )]
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FilterUserEmailDto {
+    pub id: String,
+    pub email: String,
+    pub verified: bool,
+    #[serde(rename="isPrimary")]
+    pub is_primary: bool,
+    #[serde(rename="createdAt")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl FilterUserEmailDto {
+    pub fn filter_email(email: &UserEmail) -> Self {
+        FilterUserEmailDto {
+            id: email.id.to_string(),
+            email: email.email.to_owned(),
+            verified: email.verified,
+            is_primary: email.is_primary,
+            created_at: email.created_at.unwrap(),
+        }
+    }
+
+    pub fn filter_emails(emails: &[UserEmail]) -> Vec<FilterUserEmailDto> {
+        emails.iter().map(FilterUserEmailDto::filter_email).collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserEmailResponseDto {
+    pub status: String,
+    pub email: FilterUserEmailDto,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserEmailListResponseDto {
+    pub status: String,
+    pub emails: Vec<FilterUserEmailDto>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionFilterQueryDto {
+    pub active_only: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionDto {
+    pub id: String,
+    #[serde(rename="ipAddress")]
+    pub ip_address: Option<String>,
+    #[serde(rename="userAgent")]
+    pub user_agent: Option<String>,
+    #[serde(rename="createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename="lastUsedAt")]
+    pub last_used_at: DateTime<Utc>,
+    #[serde(rename="expiresAt")]
+    pub expires_at: DateTime<Utc>,
+    pub active: bool,
+    pub current: bool,
+}
+
+impl SessionDto {
+    pub fn filter_session(session: &Session, current_session_id: Option<Uuid>) -> Self {
+        SessionDto {
+            id: session.id.to_string(),
+            ip_address: session.ip_address.to_owned(),
+            user_agent: session.user_agent.to_owned(),
+            created_at: session.created_at,
+            last_used_at: session.last_used_at,
+            expires_at: session.expires_at,
+            active: session.is_active(),
+            current: current_session_id == Some(session.id),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionListResponseDto {
+    #[serde(flatten)]
+    pub sessions: Paginated<SessionDto>,
+    #[serde(rename="activeSessions")]
+    pub active_sessions: i64,
+    #[serde(rename="sessionLimit")]
+    pub session_limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct SessionLimitUpdateDto {
+    #[validate(range(min=1, max=100, message="Session limit must be between 1 and 100"))]
+    #[serde(rename="maxSessions")]
+    pub max_sessions: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct CreateApiKeyDto {
+    #[validate(length(min=1, max=100, message="Name must be between 1 and 100 characters"))]
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiKeyDto {
+    pub id: String,
+    pub name: String,
+    pub prefix: String,
+    #[serde(rename="createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename="lastUsedAt")]
+    pub last_used_at: Option<DateTime<Utc>>,
+    #[serde(rename="expiresAt")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(rename="inRotation")]
+    pub in_rotation: bool,
+    #[serde(rename="replacedBy")]
+    pub replaced_by: Option<String>,
+}
+
+impl ApiKeyDto {
+    pub fn filter_api_key(api_key: &ApiKey) -> Self {
+        ApiKeyDto {
+            id: api_key.id.to_string(),
+            name: api_key.name.to_owned(),
+            prefix: api_key.prefix.to_owned(),
+            created_at: api_key.created_at,
+            last_used_at: api_key.last_used_at,
+            expires_at: api_key.expires_at,
+            in_rotation: api_key.rotated_at.is_some(),
+            replaced_by: api_key.replaced_by.map(|id| id.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiKeyListResponseDto {
+    pub status: String,
+    #[serde(rename="apiKeys")]
+    pub api_keys: Vec<ApiKeyDto>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiKeyCreatedResponseDto {
+    pub status: String,
+    pub key: String,
+    #[serde(rename="apiKey")]
+    pub api_key: ApiKeyDto,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiKeyRotatedResponseDto {
+    pub status: String,
+    pub key: String,
+    #[serde(rename="apiKey")]
+    pub api_key: ApiKeyDto,
+    #[serde(rename="previousKey")]
+    pub previous_key: ApiKeyDto,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImpersonationResponseDto {
+    pub status: String,
+    pub token: String,
+    #[serde(rename="userId")]
+    pub user_id: String,
+    #[serde(rename="expiresAt")]
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TerminateSessionsResponseDto {
+    pub status: String,
+    pub terminated: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct AuditQueryDto {
+    pub user_id: Option<uuid::Uuid>,
+    pub event_type: Option<AuditEventType>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub sort_by: Option<String>,
+    pub order: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditEntryDto {
+    pub id: String,
+    #[serde(rename="userId")]
+    pub user_id: Option<String>,
+    #[serde(rename="eventType")]
+    pub event_type: String,
+    #[serde(rename="ipAddress")]
+    pub ip_address: Option<String>,
+    #[serde(rename="userAgent")]
+    pub user_agent: Option<String>,
+    pub success: bool,
+    pub details: Option<String>,
+    #[serde(rename="createdAt")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl AuditEntryDto {
+    pub fn filter_entry(log: &AuditLog) -> Self {
+        AuditEntryDto {
+            id: log.id.to_string(),
+            user_id: log.user_id.map(|user_id| user_id.to_string()),
+            event_type: log.event_type.to_owned(),
+            ip_address: log.ip_address.to_owned(),
+            user_agent: log.user_agent.to_owned(),
+            success: log.success,
+            details: log.details.to_owned(),
+            created_at: log.created_at,
+        }
+    }
+
+    pub fn filter_entries(logs: &[AuditLog]) -> Vec<AuditEntryDto> {
+        logs.iter().map(AuditEntryDto::filter_entry).collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginHistoryEntryDto {
+    pub id: String,
+    #[serde(rename="eventType")]
+    pub event_type: String,
+    pub success: bool,
+    #[serde(rename="ipAddress")]
+    pub ip_address: Option<String>,
+    pub device: Option<String>,
+    #[serde(rename="createdAt")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl LoginHistoryEntryDto {
+    pub fn filter_entry(log: &AuditLog, masking: IpMasking) -> Self {
+        LoginHistoryEntryDto {
+            id: log.id.to_string(),
+            event_type: log.event_type.to_owned(),
+            success: log.success,
+            ip_address: log.ip_address.as_deref().and_then(|ip| masking.apply(ip)),
+            device: log.user_agent.as_deref().and_then(device::describe),
+            created_at: log.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailJobDto {
+    pub id: String,
+    #[serde(rename="toEmail")]
+    pub to_email: String,
+    pub subject: String,
+    pub status: String,
+    pub attempts: i32,
+    #[serde(rename="maxAttempts")]
+    pub max_attempts: i32,
+    #[serde(rename="nextAttemptAt")]
+    pub next_attempt_at: DateTime<Utc>,
+    #[serde(rename="lastError")]
+    pub last_error: Option<String>,
+    #[serde(rename="createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename="updatedAt")]
+    pub updated_at: DateTime<Utc>,
+}
+
+impl EmailJobDto {
+    pub fn filter_job(job: &EmailJob) -> Self {
+        EmailJobDto {
+            id: job.id.to_string(),
+            to_email: job.to_email.to_owned(),
+            subject: job.subject.to_owned(),
+            status: job.status.to_owned(),
+            attempts: job.attempts,
+            max_attempts: job.max_attempts,
+            next_attempt_at: job.next_attempt_at,
+            last_error: job.last_error.to_owned(),
+            created_at: job.created_at,
+            updated_at: job.updated_at,
+        }
+    }
+
+    pub fn filter_jobs(jobs: &[EmailJob]) -> Vec<EmailJobDto> {
+        jobs.iter().map(EmailJobDto::filter_job).collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailJobResponseDto {
+    pub status: String,
+    pub email: EmailJobDto,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RoleCountsDto {
+    pub admin: i64,
+    pub user: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SignupCountsDto {
+    #[serde(rename="last24h")]
+    pub last_24h: i64,
+    #[serde(rename="last7d")]
+    pub last_7d: i64,
+    #[serde(rename="last30d")]
+    pub last_30d: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ActivityCountsDto {
+    pub active: i64,
+    pub dormant: i64,
+    #[serde(rename="dormantAfterDays")]
+    pub dormant_after_days: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UserStatsDto {
+    pub total: i64,
+    pub verified: i64,
+    pub unverified: i64,
+    pub roles: RoleCountsDto,
+    pub signups: SignupCountsDto,
+    pub activity: ActivityCountsDto,
+    #[serde(rename="generatedAt")]
+    pub generated_at: DateTime<Utc>,
+}
+
+impl UserStatsDto {
+    pub fn from_stats(stats: &UserStats, dormant_after_days: i64) -> Self {
+        UserStatsDto {
+            total: stats.total,
+            verified: stats.verified,
+            unverified: stats.total - stats.verified,
+            roles: RoleCountsDto {
+                admin: stats.admins,
+                user: stats.users,
+            },
+            signups: SignupCountsDto {
+                last_24h: stats.signups_24h,
+                last_7d: stats.signups_7d,
+                last_30d: stats.signups_30d,
+            },
+            activity: ActivityCountsDto {
+                active: stats.active,
+                dormant: stats.total - stats.active,
+                dormant_after_days,
+            },
+            generated_at: Utc::now(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserStatsResponseDto {
+    pub status: String,
+    pub data: UserStatsDto,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ValidationResultDto {
+    pub status: &'static str,
+    pub valid: bool,
+    pub errors: BTreeMap<String, Vec<String>>,
+}
+
+impl ValidationResultDto {
+    pub fn from_result(result: Result<(), ValidationErrors>) -> Self {
+        let errors = match result {
+            Ok(()) => BTreeMap::new(),
+            Err(errors) => field_errors(&errors),
+        };
+
+        ValidationResultDto {
+            status: "success",
+            valid: errors.is_empty(),
+            errors,
+        }
+    }
+}
+
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreateInviteCodeDto {
+    #[validate(
+        length(min=4, max=64, message="Code must be between 4 and 64 characters"),
+        custom(function = "validate_invite_code")
+    )]
+    pub code: Option<String>,
+
+    #[validate(range(min=1, message="Max uses must be at least 1"))]
+    #[serde(rename="maxUses")]
+    pub max_uses: Option<i32>,
+
+    #[serde(rename="expiresAt")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+fn validate_invite_code(code: &str) -> Result<(), validator::ValidationError> {
+    if code.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        Ok(())
+    } else {
+        Err(validator::ValidationError::new("invalid_invite_code")
+            .with_message("Code may only contain letters, digits, dashes and underscores".into()))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InviteCodeDto {
+    pub id: String,
+    pub code: String,
+    #[serde(rename="maxUses")]
+    pub max_uses: Option<i32>,
+    #[serde(rename="usedCount")]
+    pub used_count: i32,
+    #[serde(rename="expiresAt")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(rename="createdAt")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl InviteCodeDto {
+    pub fn filter_invite_code(invite_code: &InviteCode) -> Self {
+        InviteCodeDto {
+            id: invite_code.id.to_string(),
+            code: invite_code.code.to_owned(),
+            max_uses: invite_code.max_uses,
+            used_count: invite_code.used_count,
+            expires_at: invite_code.expires_at,
+            created_at: invite_code.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InviteCodeResponseDto {
+    pub status: String,
+    #[serde(rename="inviteCode")]
+    pub invite_code: InviteCodeDto,
+}
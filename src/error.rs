@@ -1,17 +1,80 @@
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json
 };
-use std::fmt;
+use std::{collections::BTreeMap, fmt, future::Future, sync::OnceLock};
 use serde::{Deserialize, Serialize};
+use validator::ValidationErrors;
+
+use crate::config::{ErrorDetail, ErrorFormat};
+
+static ERROR_DETAIL: OnceLock<ErrorDetail> = OnceLock::new();
+static ERROR_FORMAT: OnceLock<ErrorFormat> = OnceLock::new();
+
+tokio::task_local! {
+    static REQUEST_PATH: String;
+}
+
+pub fn set_error_detail(error_detail: ErrorDetail) {
+    let _ = ERROR_DETAIL.set(error_detail);
+}
+
+fn error_detail() -> ErrorDetail {
+    ERROR_DETAIL.get().copied().unwrap_or(ErrorDetail::Detailed)
+}
+
+pub fn set_error_format(error_format: ErrorFormat) {
+    let _ = ERROR_FORMAT.set(error_format);
+}
+
+fn error_format() -> ErrorFormat {
+    ERROR_FORMAT.get().copied().unwrap_or(ErrorFormat::Json)
+}
+
+pub async fn with_request_path<F: Future>(path: String, f: F) -> F::Output {
+    REQUEST_PATH.scope(path, f).await
+}
+
+pub fn field_errors(errors: &ValidationErrors) -> BTreeMap<String, Vec<String>> {
+    errors
+        .field_errors()
+        .into_iter()
+        .map(|(field, field_errors)| {
+            let messages = field_errors
+                .iter()
+                .map(|error| error.message
+                    .as_ref()
+                    .map(|message| message.to_string())
+                    .unwrap_or_else(|| error.code.to_string()))
+                .collect();
+
+            (field.to_string(), messages)
+        })
+        .collect()
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ErrorResponse {
     pub status: String,
+    pub code: String,
     pub message: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProblemDetails {
+    #[serde(rename="type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub code: String,
+    pub detail: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub instance: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub errors: Option<BTreeMap<String, Vec<String>>>,
+}
+
 impl fmt::Display for ErrorResponse {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", serde_json::to_string(&self).unwrap())
@@ -24,7 +87,6 @@ pub enum ErrorMessage {
     ExceededMaxPasswordLength(usize),
     HashingError,
     InvalidToken,
-    ServerError,
     WrongCredentials,
     EmailExist,
     UserNoLongerExist,
@@ -32,11 +94,68 @@ pub enum ErrorMessage {
     PermissionDenied,
     UserNotAuthenticated,
     InvalidHashFormat,
+    TooManyRequests,
+    InsecureTransport,
+    UnknownPasswordPepper,
+    MethodNotAllowed,
+    InvalidTwoFactorCode,
+    VerificationRequired,
+    UnsupportedQueryField(String),
+    RequestTimeout,
+    CaptchaRequired,
+    CaptchaUnavailable,
+    AccountPendingApproval,
+    AccountSuspended(Option<String>),
+    AccountDeactivated,
+    SessionLimitReached(i64),
+    PasswordTooCommon,
+    PasswordContainsEmail,
+    PasswordContainsName,
+    PasswordContainsAppName,
+    PasswordTooWeak(String),
+    ApiKeyNotFound,
+    ApiKeyAlreadyRotating,
+    EmailDomainUndeliverable,
+    UnknownValidationTarget(String),
+    TrustedDeviceNotFound,
+    ImpersonationNotAllowed,
+    NotImpersonating,
+    AmbiguousLoginEmail,
+    TokenExpired,
+    ReauthenticationRequired,
+    ReauthenticationUnavailable,
+    AuthMethodDisabled(String),
+    OrganizationNotFound,
+    OrganizationMismatch,
+    InviteCodeRequired,
+    InviteCodeInvalid,
+    InviteCodeExpired,
+    InviteCodeExhausted,
+    InviteCodeExists,
+    InviteCodeNotFound,
+    UserNotPendingApproval,
+    SecurityQuestionsDisabled,
+    SecurityQuestionsCount(usize),
+    SecurityQuestionsDuplicate,
+    SecurityQuestionsUnavailable,
+    SecurityQuestionsIncorrect,
+    SessionBindingMismatch,
+    RegistrationLimitReached,
+    IntrospectionDisabled,
+    InvalidServiceCredentials,
+    IntrospectionBatchTooLarge(usize),
+    TermsDisabled,
+    TermsAcceptanceRequired,
+    TermsVersionMismatch(String),
+    AccountFrozen(Option<String>),
+    UserAlreadyFrozen,
+    UserNotFrozen,
+    DatabaseBusy,
 }
 
-impl ToString for ErrorMessage {
-    fn to_string(&self) -> String {
-        self.to_str().to_owned()
+impl fmt::Display for ErrorMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_str())
     }
 }
 
@@ -47,7 +166,6 @@ impl ErrorMessage {
             ErrorMessage::ExceededMaxPasswordLength(length) => format!("Password must be at most {} characters long", length),
             ErrorMessage::HashingError => "Error occured while hashing password".to_string(),
             ErrorMessage::InvalidToken => "Invalid Token".to_string(),
-            ErrorMessage::ServerError => "Internal Server Error".to_string(),
             ErrorMessage::WrongCredentials => "Wrong Credentials".to_string(),
             ErrorMessage::EmailExist => "Email already exists".to_string(),
             ErrorMessage::UserNoLongerExist => "User no longer exists".to_string(),
@@ -55,66 +173,309 @@ impl ErrorMessage {
             ErrorMessage::PermissionDenied => "Permission Denied".to_string(),
             ErrorMessage::UserNotAuthenticated => "User not authenticated".to_string(),
             ErrorMessage::InvalidHashFormat => "Invalid Password Hash Format".to_string(),
+            ErrorMessage::TooManyRequests => "Too many requests, please try again later".to_string(),
+            ErrorMessage::InsecureTransport => "Cookie authentication requires HTTPS".to_string(),
+            ErrorMessage::UnknownPasswordPepper => "Password pepper is not configured".to_string(),
+            ErrorMessage::MethodNotAllowed => "Method not allowed".to_string(),
+            ErrorMessage::InvalidTwoFactorCode => "Invalid two-factor code".to_string(),
+            ErrorMessage::VerificationRequired => "Please verify your email address to use this feature".to_string(),
+            ErrorMessage::UnsupportedQueryField(field) => format!("Unsupported query field or value: {}", field),
+            ErrorMessage::RequestTimeout => "Request timed out".to_string(),
+            ErrorMessage::CaptchaRequired => "Please complete the captcha challenge".to_string(),
+            ErrorMessage::CaptchaUnavailable => "Captcha verification is currently unavailable".to_string(),
+            ErrorMessage::AccountPendingApproval => "Your account is pending approval".to_string(),
+            ErrorMessage::AccountSuspended(Some(reason)) => format!("Your account has been suspended: {}", reason),
+            ErrorMessage::AccountSuspended(None) => "Your account has been suspended".to_string(),
+            ErrorMessage::AccountDeactivated => "Your account has been deactivated".to_string(),
+            ErrorMessage::PasswordTooCommon => "Password is too common, please choose a less predictable one".to_string(),
+            ErrorMessage::PasswordContainsEmail => "Password must not contain your email address".to_string(),
+            ErrorMessage::PasswordContainsName => "Password must not contain your name".to_string(),
+            ErrorMessage::PasswordContainsAppName => "Password must not contain the application name".to_string(),
+            ErrorMessage::PasswordTooWeak(crack_time) => format!("Password is too weak, it could be guessed in {}", crack_time),
+            ErrorMessage::SessionLimitReached(limit) => format!("You are already signed in on {} devices, sign out of one to continue", limit),
+            ErrorMessage::ApiKeyNotFound => "API key not found".to_string(),
+            ErrorMessage::ApiKeyAlreadyRotating => "API key has already been rotated and is expiring".to_string(),
+            ErrorMessage::EmailDomainUndeliverable => "Email domain does not accept mail, please check the address".to_string(),
+            ErrorMessage::UnknownValidationTarget(dto) => format!("Unknown validation target: {}", dto),
+            ErrorMessage::TrustedDeviceNotFound => "Trusted device not found".to_string(),
+            ErrorMessage::ImpersonationNotAllowed => "Only active non-admin users can be impersonated".to_string(),
+            ErrorMessage::NotImpersonating => "This session is not an impersonation".to_string(),
+            ErrorMessage::AmbiguousLoginEmail => "More than one account matches this email, please enter it with its exact capitalization".to_string(),
+            ErrorMessage::TokenExpired => "Verification token has expired".to_string(),
+            ErrorMessage::ReauthenticationRequired => "Please confirm your password to continue".to_string(),
+            ErrorMessage::ReauthenticationUnavailable => "Password confirmation requires a signed-in session".to_string(),
+            ErrorMessage::AuthMethodDisabled(method) => format!("The {} sign-in method is disabled on this server", method),
+            ErrorMessage::OrganizationNotFound => "Organization not found".to_string(),
+            ErrorMessage::OrganizationMismatch => "These credentials belong to a different organization".to_string(),
+            ErrorMessage::InviteCodeRequired => "An invite code is required to sign up".to_string(),
+            ErrorMessage::InviteCodeInvalid => "Invite code is invalid".to_string(),
+            ErrorMessage::InviteCodeExpired => "Invite code has expired".to_string(),
+            ErrorMessage::InviteCodeExhausted => "Invite code has already been used the maximum number of times".to_string(),
+            ErrorMessage::InviteCodeExists => "An invite code with this value already exists".to_string(),
+            ErrorMessage::InviteCodeNotFound => "Invite code not found or already revoked".to_string(),
+            ErrorMessage::UserNotPendingApproval => "This account is not awaiting approval".to_string(),
+            ErrorMessage::SecurityQuestionsDisabled => "Security question recovery is not enabled".to_string(),
+            ErrorMessage::SecurityQuestionsCount(count) => format!("Exactly {} security questions must be set", count),
+            ErrorMessage::SecurityQuestionsDuplicate => "Security questions must be different from each other".to_string(),
+            ErrorMessage::SecurityQuestionsUnavailable => "Security question recovery is not available for this account".to_string(),
+            ErrorMessage::SecurityQuestionsIncorrect => "The answers could not be verified".to_string(),
+            ErrorMessage::SessionBindingMismatch => "This session was used from a different client and has been ended, please log in again".to_string(),
+            ErrorMessage::RegistrationLimitReached => "Too many accounts have been created from this network today, please try again tomorrow".to_string(),
+            ErrorMessage::IntrospectionDisabled => "Token introspection is not enabled".to_string(),
+            ErrorMessage::InvalidServiceCredentials => "Invalid service credentials".to_string(),
+            ErrorMessage::IntrospectionBatchTooLarge(max) => format!("At most {} tokens can be introspected per request", max),
+            ErrorMessage::TermsDisabled => "Terms of service acceptance is not enabled".to_string(),
+            ErrorMessage::TermsAcceptanceRequired => "You must accept the current terms of service to continue".to_string(),
+            ErrorMessage::TermsVersionMismatch(current) => format!("The current terms of service version is {}", current),
+            ErrorMessage::AccountFrozen(Some(reason)) => format!("Your account is read-only while it is under review: {}", reason),
+            ErrorMessage::AccountFrozen(None) => "Your account is read-only while it is under review".to_string(),
+            ErrorMessage::UserAlreadyFrozen => "This account is already frozen".to_string(),
+            ErrorMessage::UserNotFrozen => "This account is not frozen".to_string(),
+            ErrorMessage::DatabaseBusy => "The service is busy, please try again shortly".to_string(),
+        }
+    }
+
+    // Clients branch on these codes, so they must never change once released.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorMessage::EmptyPassword => "PASSWORD_REQUIRED",
+            ErrorMessage::ExceededMaxPasswordLength(_) => "PASSWORD_TOO_LONG",
+            ErrorMessage::HashingError => "HASHING_FAILED",
+            ErrorMessage::InvalidToken => "INVALID_TOKEN",
+            ErrorMessage::WrongCredentials => "INVALID_CREDENTIALS",
+            ErrorMessage::EmailExist => "EMAIL_TAKEN",
+            ErrorMessage::UserNoLongerExist => "USER_NOT_FOUND",
+            ErrorMessage::TokenNotProvided => "TOKEN_MISSING",
+            ErrorMessage::PermissionDenied => "PERMISSION_DENIED",
+            ErrorMessage::UserNotAuthenticated => "NOT_AUTHENTICATED",
+            ErrorMessage::InvalidHashFormat => "INVALID_HASH_FORMAT",
+            ErrorMessage::TooManyRequests => "RATE_LIMITED",
+            ErrorMessage::InsecureTransport => "INSECURE_TRANSPORT",
+            ErrorMessage::UnknownPasswordPepper => "UNKNOWN_PASSWORD_PEPPER",
+            ErrorMessage::MethodNotAllowed => "METHOD_NOT_ALLOWED",
+            ErrorMessage::InvalidTwoFactorCode => "INVALID_TWO_FACTOR_CODE",
+            ErrorMessage::VerificationRequired => "VERIFICATION_REQUIRED",
+            ErrorMessage::UnsupportedQueryField(_) => "UNSUPPORTED_QUERY_FIELD",
+            ErrorMessage::RequestTimeout => "REQUEST_TIMEOUT",
+            ErrorMessage::CaptchaRequired => "CAPTCHA_REQUIRED",
+            ErrorMessage::CaptchaUnavailable => "CAPTCHA_UNAVAILABLE",
+            ErrorMessage::AccountPendingApproval => "ACCOUNT_PENDING_APPROVAL",
+            ErrorMessage::AccountSuspended(_) => "ACCOUNT_SUSPENDED",
+            ErrorMessage::AccountDeactivated => "ACCOUNT_DEACTIVATED",
+            ErrorMessage::SessionLimitReached(_) => "SESSION_LIMIT_REACHED",
+            ErrorMessage::PasswordTooCommon => "PASSWORD_TOO_COMMON",
+            ErrorMessage::PasswordContainsEmail => "PASSWORD_CONTAINS_EMAIL",
+            ErrorMessage::PasswordContainsName => "PASSWORD_CONTAINS_NAME",
+            ErrorMessage::PasswordContainsAppName => "PASSWORD_CONTAINS_APP_NAME",
+            ErrorMessage::PasswordTooWeak(_) => "PASSWORD_TOO_WEAK",
+            ErrorMessage::ApiKeyNotFound => "API_KEY_NOT_FOUND",
+            ErrorMessage::ApiKeyAlreadyRotating => "API_KEY_ALREADY_ROTATING",
+            ErrorMessage::EmailDomainUndeliverable => "EMAIL_DOMAIN_UNDELIVERABLE",
+            ErrorMessage::UnknownValidationTarget(_) => "UNKNOWN_VALIDATION_TARGET",
+            ErrorMessage::TrustedDeviceNotFound => "TRUSTED_DEVICE_NOT_FOUND",
+            ErrorMessage::ImpersonationNotAllowed => "IMPERSONATION_NOT_ALLOWED",
+            ErrorMessage::NotImpersonating => "NOT_IMPERSONATING",
+            ErrorMessage::AmbiguousLoginEmail => "AMBIGUOUS_LOGIN_EMAIL",
+            ErrorMessage::TokenExpired => "TOKEN_EXPIRED",
+            ErrorMessage::ReauthenticationRequired => "REAUTH_REQUIRED",
+            ErrorMessage::ReauthenticationUnavailable => "REAUTH_UNAVAILABLE",
+            ErrorMessage::AuthMethodDisabled(_) => "AUTH_METHOD_DISABLED",
+            ErrorMessage::OrganizationNotFound => "ORG_NOT_FOUND",
+            ErrorMessage::OrganizationMismatch => "ORG_MISMATCH",
+            ErrorMessage::InviteCodeRequired => "INVITE_CODE_REQUIRED",
+            ErrorMessage::InviteCodeInvalid => "INVITE_CODE_INVALID",
+            ErrorMessage::InviteCodeExpired => "INVITE_CODE_EXPIRED",
+            ErrorMessage::InviteCodeExhausted => "INVITE_CODE_EXHAUSTED",
+            ErrorMessage::InviteCodeExists => "INVITE_CODE_EXISTS",
+            ErrorMessage::InviteCodeNotFound => "INVITE_CODE_NOT_FOUND",
+            ErrorMessage::UserNotPendingApproval => "USER_NOT_PENDING_APPROVAL",
+            ErrorMessage::SecurityQuestionsDisabled => "SECURITY_QUESTIONS_DISABLED",
+            ErrorMessage::SecurityQuestionsCount(_) => "SECURITY_QUESTIONS_COUNT",
+            ErrorMessage::SecurityQuestionsDuplicate => "SECURITY_QUESTIONS_DUPLICATE",
+            ErrorMessage::SecurityQuestionsUnavailable => "SECURITY_QUESTIONS_UNAVAILABLE",
+            ErrorMessage::SecurityQuestionsIncorrect => "SECURITY_QUESTIONS_INCORRECT",
+            ErrorMessage::SessionBindingMismatch => "SESSION_BINDING_MISMATCH",
+            ErrorMessage::RegistrationLimitReached => "REGISTRATION_LIMIT_REACHED",
+            ErrorMessage::IntrospectionDisabled => "INTROSPECTION_DISABLED",
+            ErrorMessage::InvalidServiceCredentials => "INVALID_SERVICE_CREDENTIALS",
+            ErrorMessage::IntrospectionBatchTooLarge(_) => "INTROSPECTION_BATCH_TOO_LARGE",
+            ErrorMessage::TermsDisabled => "TERMS_DISABLED",
+            ErrorMessage::TermsAcceptanceRequired => "TERMS_ACCEPTANCE_REQUIRED",
+            ErrorMessage::TermsVersionMismatch(_) => "TERMS_VERSION_MISMATCH",
+            ErrorMessage::AccountFrozen(_) => "ACCOUNT_FROZEN",
+            ErrorMessage::UserAlreadyFrozen => "USER_ALREADY_FROZEN",
+            ErrorMessage::UserNotFrozen => "USER_NOT_FROZEN",
+            ErrorMessage::DatabaseBusy => "DATABASE_BUSY",
         }
     }
 }
 
+// Errors built from a plain string fall back to a code for their status.
+pub fn status_code(status: StatusCode) -> &'static str {
+    match status {
+        StatusCode::BAD_REQUEST => "BAD_REQUEST",
+        StatusCode::UNAUTHORIZED => "UNAUTHORIZED",
+        StatusCode::FORBIDDEN => "FORBIDDEN",
+        StatusCode::NOT_FOUND => "NOT_FOUND",
+        StatusCode::METHOD_NOT_ALLOWED => "METHOD_NOT_ALLOWED",
+        StatusCode::CONFLICT => "CONFLICT",
+        StatusCode::GONE => "GONE",
+        StatusCode::PAYLOAD_TOO_LARGE => "PAYLOAD_TOO_LARGE",
+        StatusCode::UNPROCESSABLE_ENTITY => "UNPROCESSABLE_ENTITY",
+        StatusCode::TOO_MANY_REQUESTS => "RATE_LIMITED",
+        StatusCode::SERVICE_UNAVAILABLE => "SERVICE_UNAVAILABLE",
+        StatusCode::GATEWAY_TIMEOUT => "REQUEST_TIMEOUT",
+        status if status.is_server_error() => "INTERNAL_ERROR",
+        _ => "ERROR",
+    }
+}
+
+pub struct ErrorText {
+    message: String,
+    code: Option<&'static str>,
+}
+
+impl From<String> for ErrorText {
+    fn from(message: String) -> Self {
+        ErrorText { message, code: None }
+    }
+}
+
+impl From<&str> for ErrorText {
+    fn from(message: &str) -> Self {
+        ErrorText { message: message.to_string(), code: None }
+    }
+}
+
+impl From<ErrorMessage> for ErrorText {
+    fn from(message: ErrorMessage) -> Self {
+        ErrorText { code: Some(message.code()), message: message.to_string() }
+    }
+}
+
 
 #[derive(Debug, Clone)]
 pub struct HttpError {
     pub message:String,
     pub status: StatusCode,
+    pub code: &'static str,
+    pub errors: Option<BTreeMap<String, Vec<String>>>,
 }
 
 impl HttpError {
-    pub fn new(message: impl Into<String>, status: StatusCode) -> Self {
+    pub fn new(message: impl Into<ErrorText>, status: StatusCode) -> Self {
+        let ErrorText { message, code } = message.into();
         HttpError {
-            message: message.into(),
+            message,
             status,
+            code: code.unwrap_or_else(|| status_code(status)),
+            errors: None,
         }
     }
 
-    pub fn server_error(message: impl Into<String>) -> Self {
-        HttpError {
-            message: message.into(),
-            status: StatusCode::INTERNAL_SERVER_ERROR,
-        }
+    pub fn server_error(message: impl Into<ErrorText>) -> Self {
+        HttpError::new(message, StatusCode::INTERNAL_SERVER_ERROR)
     }
     
-    pub fn bad_request(message: impl Into<String>) -> Self {
-        HttpError {
-            message: message.into(),
-            status: StatusCode::BAD_REQUEST,
+    pub fn bad_request(message: impl Into<ErrorText>) -> Self {
+        HttpError::new(message, StatusCode::BAD_REQUEST)
+    }
+
+    pub fn unique_constraint_violation(message: impl Into<ErrorText>) -> Self {
+        HttpError::new(message, StatusCode::CONFLICT)
+    }
+
+     pub fn unauthorized(message: impl Into<ErrorText>) -> Self {
+        HttpError::new(message, StatusCode::UNAUTHORIZED)
+    }
+
+    pub fn not_found(message: impl Into<ErrorText>) -> Self {
+        HttpError::new(message, StatusCode::NOT_FOUND)
+    }
+
+    pub fn too_many_requests(message: impl Into<ErrorText>) -> Self {
+        HttpError::new(message, StatusCode::TOO_MANY_REQUESTS)
+    }
+
+    // Postgres cancels statements that outlive statement_timeout with 57014.
+    pub fn database(error: sqlx::Error) -> Self {
+        match &error {
+            sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some("57014") => {
+                HttpError::new(ErrorMessage::RequestTimeout, StatusCode::GATEWAY_TIMEOUT)
+            }
+            sqlx::Error::PoolTimedOut => HttpError::new(ErrorMessage::DatabaseBusy, StatusCode::SERVICE_UNAVAILABLE),
+            _ => HttpError::server_error(error.to_string()),
         }
     }
 
-    pub fn unique_constraint_violation(message: impl Into<String>) -> Self {
+    pub fn validation(errors: ValidationErrors) -> Self {
         HttpError {
-            message: message.into(),
-            status: StatusCode::CONFLICT,
+            message: errors.to_string(),
+            status: StatusCode::BAD_REQUEST,
+            code: "VALIDATION_FAILED",
+            errors: Some(field_errors(&errors)),
         }
     }
 
-     pub fn unauthorized(message: impl Into<String>) -> Self {
-        HttpError {
-            message: message.into(),
-            status: StatusCode::UNAUTHORIZED,
+    fn generic_message(&self) -> String {
+        match self.status {
+            StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED => "Invalid credentials".to_string(),
+            StatusCode::FORBIDDEN => ErrorMessage::PermissionDenied.to_string(),
+            StatusCode::NOT_FOUND => "Not found".to_string(),
+            StatusCode::CONFLICT => "Request could not be completed".to_string(),
+            status if status.is_server_error() => "Something went wrong".to_string(),
+            _ => self.message.clone(),
         }
     }
 
     pub fn into_http_response(self) -> Response {
+        if self.status.is_server_error() {
+            eprintln!("{}", self);
+        }
+
+        let (message, code) = match error_detail() {
+            ErrorDetail::Detailed => (self.message.clone(), self.code),
+            ErrorDetail::Generic => (self.generic_message(), status_code(self.status)),
+        };
+
+        if error_format() == ErrorFormat::Problem {
+            return self.into_problem_response(message, code);
+        }
+
         let json_response = Json(ErrorResponse {
             status: "fail".to_string(),
-            message: self.message.clone(),
+            code: code.to_string(),
+            message,
         });
 
         (self.status, json_response).into_response()
     }
+
+    fn into_problem_response(self, detail: String, code: &str) -> Response {
+        let errors = match error_detail() {
+            ErrorDetail::Detailed => self.errors,
+            ErrorDetail::Generic => None,
+        };
+
+        let problem = Json(ProblemDetails {
+            problem_type: "about:blank".to_string(),
+            title: self.status.canonical_reason().unwrap_or("Error").to_string(),
+            status: self.status.as_u16(),
+            code: code.to_string(),
+            detail,
+            instance: REQUEST_PATH.try_with(|path| path.clone()).ok(),
+            errors,
+        });
+
+        let mut response = (self.status, problem).into_response();
+        response.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("application/problem+json"));
+        response
+    }
 }
 
 impl fmt::Display for HttpError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "HttpError: message: {}, status: {}", self.message, self.status)
+        write!(f, "HttpError: code: {}, message: {}, status: {}", self.code, self.message, self.status)
     }
 }
 
@@ -0,0 +1,57 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::models::AuditEventType;
+
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthEvent {
+    pub id: String,
+    #[serde(rename="eventType")]
+    pub event_type: AuditEventType,
+    #[serde(rename="userId")]
+    pub user_id: Option<String>,
+    #[serde(rename="orgId")]
+    pub org_id: Option<Uuid>,
+    pub success: bool,
+    pub details: Option<String>,
+    #[serde(rename="occurredAt")]
+    pub occurred_at: DateTime<Utc>,
+}
+
+impl AuthEvent {
+    pub fn new(event_type: AuditEventType, user_id: Option<Uuid>, org_id: Option<Uuid>, success: bool, details: Option<&str>) -> Self {
+        AuthEvent {
+            id: Uuid::new_v4().to_string(),
+            event_type,
+            user_id: user_id.map(|user_id| user_id.to_string()),
+            org_id,
+            success,
+            details: details.map(|details| details.to_string()),
+            occurred_at: Utc::now(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<AuthEvent>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        EventBus { sender }
+    }
+
+    pub fn publish(&self, event: AuthEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<AuthEvent> {
+        self.sender.subscribe()
+    }
+}
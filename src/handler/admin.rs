@@ -0,0 +1,196 @@
+use std::{convert::Infallible, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Path, Query},
+    middleware,
+    http::StatusCode,
+    response::{sse::{Event, KeepAlive, Sse}, IntoResponse},
+    routing::{delete, get, post},
+    Extension,
+    Json,
+    Router
+};
+use futures_util::stream::{self, Stream};
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    db::{EmailJobExt, InviteCodeExt},
+    dtos::{CreateInviteCodeDto, EmailJobDto, EmailJobResponseDto, InviteCodeDto, InviteCodeResponseDto, Paginated, RequestQueryDto, Response},
+    error::{ErrorMessage, HttpError},
+    handler::audit::record_event,
+    middleware::{role_check, JWTAuthMiddleware, RequestMetadata, StrictJson},
+    models::{AuditEventType, UserRole},
+    utils::token,
+    AppState
+};
+
+pub fn admin_handler() -> Router {
+    Router::new()
+        .route(
+            "/events",
+            get(stream_events)
+            .layer(middleware::from_fn(|state, req, next| {
+                role_check(state, req, next, vec![UserRole::Admin])
+            }))
+        )
+        .route(
+            "/emails/dead-letter",
+            get(get_dead_letter_emails)
+            .layer(middleware::from_fn(|state, req, next| {
+                role_check(state, req, next, vec![UserRole::Admin])
+            }))
+        )
+        .route(
+            "/emails/:job_id/retry",
+            post(retry_email)
+            .layer(middleware::from_fn(|state, req, next| {
+                role_check(state, req, next, vec![UserRole::Admin])
+            }))
+        )
+        .route(
+            "/invite-codes",
+            post(create_invite_code)
+            .layer(middleware::from_fn(|state, req, next| {
+                role_check(state, req, next, vec![UserRole::Admin])
+            }))
+        )
+        .route(
+            "/invite-codes/:code_id",
+            delete(revoke_invite_code)
+            .layer(middleware::from_fn(|state, req, next| {
+                role_check(state, req, next, vec![UserRole::Admin])
+            }))
+        )
+}
+
+pub async fn stream_events(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(admin): Extension<JWTAuthMiddleware>
+) -> impl IntoResponse {
+    Sse::new(event_stream(app_state, admin.user.org_id)).keep_alive(
+        KeepAlive::new()
+            .interval(Duration::from_secs(15))
+            .text("heartbeat")
+    )
+}
+
+// Events carry user ids and attempted emails, so admins only see their own
+// organization's. Events without an organization are never streamed.
+fn event_stream(app_state: Arc<AppState>, org_id: Uuid) -> impl Stream<Item = Result<Event, Infallible>> {
+    let receiver = app_state.event_bus.subscribe();
+
+    stream::unfold(receiver, move |mut receiver| async move {
+        let sse_event = loop {
+            match receiver.recv().await {
+                Ok(event) if event.org_id != Some(org_id) => continue,
+                Ok(event) => break Event::default()
+                    .event(event.event_type.to_str())
+                    .id(event.id.clone())
+                    .json_data(&event)
+                    .unwrap_or_else(|_| Event::default().comment("unserializable event")),
+                Err(RecvError::Lagged(skipped)) => break Event::default().comment(format!("skipped {} events", skipped)),
+                Err(RecvError::Closed) => return None,
+            }
+        };
+
+        Some((Ok(sse_event), receiver))
+    })
+}
+
+pub async fn get_dead_letter_emails(
+    Query(query_params): Query<RequestQueryDto>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(admin): Extension<JWTAuthMiddleware>
+) -> Result<impl IntoResponse, HttpError> {
+    query_params.validate()
+        .map_err(HttpError::validation)?;
+
+    let jobs = app_state.db_client
+        .get_dead_email_jobs(admin.user.org_id, query_params.page() as u32, query_params.limit())
+        .await
+        .map_err(HttpError::database)?;
+
+    let job_count = app_state.db_client
+        .get_dead_email_job_count(admin.user.org_id)
+        .await
+        .map_err(HttpError::database)?;
+
+    Ok(Json(Paginated::new(EmailJobDto::filter_jobs(&jobs), &query_params, job_count)))
+}
+
+pub async fn retry_email(
+    Path(job_id): Path<Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(admin): Extension<JWTAuthMiddleware>
+) -> Result<impl IntoResponse, HttpError> {
+    let job = app_state.db_client
+        .retry_email_job(admin.user.org_id, job_id)
+        .await
+        .map_err(HttpError::database)?
+        .ok_or(HttpError::not_found("Dead-lettered email not found".to_string()))?;
+
+    app_state.email_queue.wake();
+
+    Ok(Json(EmailJobResponseDto {
+        status: "success".to_string(),
+        email: EmailJobDto::filter_job(&job),
+    }))
+}
+
+pub async fn create_invite_code(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(admin): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata,
+    StrictJson(body): StrictJson<CreateInviteCodeDto>
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(HttpError::validation)?;
+
+    let code = body.code.unwrap_or_else(token::generate_invite_code);
+
+    let result = app_state.db_client
+        .create_invite_code(admin.user.org_id, &code, body.max_uses, body.expires_at, admin.user.id)
+        .await;
+
+    let invite_code = match result {
+        Ok(invite_code) => invite_code,
+        Err(sqlx::Error::Database(db_err)) if db_err.is_unique_violation() => {
+            return Err(HttpError::unique_constraint_violation(ErrorMessage::InviteCodeExists));
+        }
+        Err(e) => return Err(HttpError::database(e)),
+    };
+
+    let details = format!("invite_code={}", invite_code.id);
+    record_event(&app_state, Some(admin.user.id), AuditEventType::InviteCodeCreated, &metadata, true, Some(&details)).await;
+
+    Ok((StatusCode::CREATED, Json(InviteCodeResponseDto {
+        status: "success".to_string(),
+        invite_code: InviteCodeDto::filter_invite_code(&invite_code),
+    })))
+}
+
+pub async fn revoke_invite_code(
+    Path(code_id): Path<Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(admin): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata
+) -> Result<impl IntoResponse, HttpError> {
+    let revoked = app_state.db_client
+        .revoke_invite_code(admin.user.org_id, code_id)
+        .await
+        .map_err(HttpError::database)?;
+
+    if !revoked {
+        return Err(HttpError::not_found(ErrorMessage::InviteCodeNotFound));
+    }
+
+    let details = format!("invite_code={}", code_id);
+    record_event(&app_state, Some(admin.user.id), AuditEventType::InviteCodeRevoked, &metadata, true, Some(&details)).await;
+
+    Ok(Json(Response {
+        message: "Invite code revoked successfully".to_string(),
+        status: "success",
+    }))
+}
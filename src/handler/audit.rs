@@ -0,0 +1,104 @@
+use std::sync::Arc;
+
+use axum::{extract::Query, middleware, response::IntoResponse, routing::get, Extension, Json, Router};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    db::{AuditExt, UserExt, AUDIT_FILTER_FIELDS, AUDIT_SORT_FIELDS},
+    dtos::{AuditEntryDto, AuditQueryDto, Paginated, RequestQueryDto},
+    error::HttpError,
+    events::AuthEvent,
+    middleware::{role_check, JWTAuthMiddleware, RequestMetadata},
+    models::{AuditEventType, UserRole},
+    security_log::SecurityEvent,
+    utils::query::{FilterOp, FilterValue, ListQuery},
+    AppState
+};
+
+pub fn audit_handler() -> Router {
+    Router::new()
+        .route(
+            "/",
+            get(get_audit_logs)
+            .layer(middleware::from_fn(|state, req, next| {
+                role_check(state, req, next, vec![UserRole::Admin])
+            }))
+        )
+}
+
+pub async fn record_event(
+    app_state: &AppState,
+    user_id: Option<Uuid>,
+    event_type: AuditEventType,
+    metadata: &RequestMetadata,
+    success: bool,
+    details: Option<&str>,
+) {
+    // Known users are scoped to their own organization, which token links can
+    // reach without a tenant header, failures for unknown users to the tenant.
+    let org_id = match user_id {
+        Some(user_id) => app_state.db_client
+            .get_user_org_id(user_id)
+            .await
+            .unwrap_or_else(|e| {
+                eprintln!("Failed to look up organization for event {}: {}", event_type.to_str(), e);
+                None
+            })
+            .or(metadata.org_id),
+        None => metadata.org_id,
+    };
+
+    app_state.event_bus.publish(AuthEvent::new(event_type, user_id, org_id, success, details));
+    app_state.security_log
+        .record(SecurityEvent::new(event_type, user_id, metadata.ip_address.as_deref(), success, details))
+        .await;
+
+    let result = app_state.db_client
+        .save_audit_log(
+            user_id,
+            event_type,
+            metadata.ip_address.as_deref(),
+            metadata.user_agent.as_deref(),
+            success,
+            details,
+        )
+        .await;
+
+    if let Err(e) = result {
+        eprintln!("Failed to record audit event {}: {}", event_type.to_str(), e);
+    }
+}
+
+pub async fn get_audit_logs(
+    Query(page_params): Query<RequestQueryDto>,
+    Query(query_params): Query<AuditQueryDto>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(admin): Extension<JWTAuthMiddleware>
+) -> Result<impl IntoResponse, HttpError> {
+    page_params.validate()
+        .map_err(HttpError::validation)?;
+
+    let query = ListQuery::new(AUDIT_FILTER_FIELDS, AUDIT_SORT_FIELDS, "created_at")
+        .filter("user_id", FilterOp::Eq, query_params.user_id.map(FilterValue::Uuid))
+        .and_then(|query| query.filter("event_type", FilterOp::Eq, query_params.event_type.map(|event_type| FilterValue::Text(event_type.to_str().to_string()))))
+        .and_then(|query| query.filter("from", FilterOp::Gte, query_params.from.map(FilterValue::Timestamp)))
+        .and_then(|query| query.filter("to", FilterOp::Lte, query_params.to.map(FilterValue::Timestamp)))
+        .and_then(|query| query.sort(query_params.sort_by.as_deref(), query_params.order.as_deref()))
+        .map_err(HttpError::bad_request)?
+        .paginate(page_params.page(), page_params.limit());
+
+    let logs = app_state.db_client
+        .get_audit_logs(admin.user.org_id, &query)
+        .await
+        .map_err(HttpError::database)?;
+
+    let log_count = app_state.db_client
+        .get_audit_log_count(admin.user.org_id, &query)
+        .await
+        .map_err(HttpError::database)?;
+
+    let response = Paginated::new(AuditEntryDto::filter_entries(&logs), &page_params, log_count);
+
+    Ok(Json(response))
+}
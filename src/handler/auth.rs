@@ -1,245 +1,1881 @@
-use std::sync::Arc;
+use std::{net::IpAddr, sync::Arc, time::Duration as StdDuration};
 
-use axum::{extract::Query, http::{header, HeaderMap, StatusCode}, response::{IntoResponse, Redirect}, routing::{get, post}, Extension, Json, Router};
-use axum_extra::extract::cookie::Cookie;
-use chrono::{Duration, Utc};
+use axum::{extract::Query, http::{header, HeaderMap, StatusCode}, middleware, response::{IntoResponse, Redirect}, routing::{get, post}, Extension, Json, Router};
+use axum_extra::extract::cookie::{Cookie, CookieJar};
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use futures_util::stream::{self, StreamExt};
+use rand::seq::SliceRandom;
 use validator::Validate;
 
-use crate::{db::UserExt, dtos::{ForgotPasswordRequestDto, LoginUserDto, RegisterUserDto, ResetPasswordRequestDto, Response, UserLoginResponseDto, VerifyEmailQueryDto}, error::{ErrorMessage, HttpError}, mail::mails::{send_forget_password_email, send_verification_email, send_welcome_email}, utils::{password, token}, AppState};
+use crate::{config::{AuthMethod, Config, PasswordResetMode, SessionLimitPolicy}, db::{InviteCodeExt, PasswordResetCodeExt, SecurityQuestionExt, SessionExt, TrustedDeviceExt, TwoFactorExt, UserEmailExt, UserExt, VerificationCodeExt}, dtos::{AccountSummaryDto, AuthMethodsResponseDto, DevEmailDto, EmailAvailabilityDto, EmailSentResponseDto, EmailAvailabilityQueryDto, FilterUserDto, ForgotPasswordRequestDto, IntrospectBatchDto, IntrospectBatchResponseDto, IntrospectionResultDto, LoginUserDto, ReactivateAccountDto, ReactivationChallengeResponseDto, ReauthDto, ReauthResponseDto, RecoveryLoginDto, SessionStatusDto, RegisterUserDto, ResendVerificationCodeDto, ResetPasswordRequestDto, ResetPasswordWithCodeDto, ResetTokenQueryDto, ResetTokenStatusDto, Response, SecurityQuestionChallengeResponseDto, SecurityQuestionDto, SecurityQuestionRecoveryDto, SecurityQuestionRecoveryResponseDto, TwoFactorChallengeResponseDto, TwoFactorLoginDto, UndoEmailChangeDto, UserLoginResponseDto, VerifiedSummaryResponseDto, VerifyEmailCodeDto, VerifyEmailQueryDto, VerifySummaryDto}, error::{ErrorMessage, HttpError}, handler::audit::record_event, mail::mails::{create_verification_link, queue_account_locked_email, queue_approval_request_email, queue_forget_password_email, queue_lockout_alert_email, queue_password_reset_code_email, queue_pending_approval_email, queue_verification_code_email, queue_verification_email, queue_welcome_email}, middleware::{auth, auth_method_check, ensure_active, service_auth, ClientIp, JWTAuthMiddleware, RequestMetadata, StrictJson, Tenant}, models::{AccountStatus, AuditEventType, User, UserRole}, utils::{captcha, device, ip::IpMasking, password, token, totp}, AppState};
 
 pub fn auth_handler() -> Router {
-    Router::new()
+    let password_routes = Router::new()
         .route("/register", post(register))
         .route("/login", post(login))
-        .route("/verify", get(verify_email))
+        .route("/2fa/verify", post(verify_two_factor))
+        .route("/2fa/recovery", post(recover_two_factor))
+        .route("/reactivate", post(reactivate_account))
+        .route("/reauth", post(reauth).layer(middleware::from_fn(auth)))
         .route("/forgot-password", post(forgot_password))
         .route("/reset-password", post(reset_password))
+        .route("/reset-password/code", post(reset_password_with_code))
+        .route("/reset/verify", get(verify_reset_token))
+        .route("/recovery/questions", post(start_security_question_recovery))
+        .route("/recovery/questions/verify", post(verify_security_question_recovery))
+        .layer(middleware::from_fn(|state, req, next| {
+            auth_method_check(state, req, next, AuthMethod::Password)
+        }));
+
+    Router::new()
+        .route("/methods", get(get_auth_methods))
+        .route("/email-available", get(check_email_available))
+        .route("/verify", get(verify_email))
+        .route("/verify/code", post(verify_email_code))
+        .route("/verify/code/resend", post(resend_verification_code))
+        .route("/emails/verify", get(verify_secondary_email))
+        .route("/email-change/undo", post(undo_email_change))
+        .route("/signed-summary/verify", post(verify_signed_summary))
+        .route("/session", get(get_session_status).layer(middleware::from_fn(auth)))
+        .route("/introspect/batch", post(introspect_tokens).layer(middleware::from_fn(service_auth)))
+        .merge(password_routes)
+}
+
+pub fn auth_cookie(token: String, role: UserRole, config: &Config) -> Cookie<'static> {
+    let cookie_duration = time::Duration::minutes(config.jwt_maxage_for(role) * 60);
+    let mut cookie = Cookie::build(("token", token))
+        .path("/")
+        .max_age(cookie_duration)
+        .http_only(true);
+
+    if config.is_prod() {
+        cookie = cookie.secure(true);
+        if let Some(domain) = &config.cookie_domain {
+            cookie = cookie.domain(domain.clone());
+        }
+    }
+
+    cookie.build()
+}
+
+pub const TRUSTED_DEVICE_COOKIE: &str = "trusted_device";
+
+fn trusted_device_cookie(token: String, days: i64, config: &Config) -> Cookie<'static> {
+    let mut cookie = Cookie::build((TRUSTED_DEVICE_COOKIE, token))
+        .path("/")
+        .max_age(time::Duration::days(days))
+        .http_only(true);
+
+    if config.is_prod() {
+        cookie = cookie.secure(true);
+        if let Some(domain) = &config.cookie_domain {
+            cookie = cookie.domain(domain.clone());
+        }
+    }
+
+    cookie.build()
 }
 
 pub async fn register(
+    ClientIp(client_ip): ClientIp,
     Extension(app_state): Extension<Arc<AppState>>,
-    Json(body): Json<RegisterUserDto>
+    Extension(Tenant(org_id)): Extension<Tenant>,
+    metadata: RequestMetadata,
+    StrictJson(body): StrictJson<RegisterUserDto>
 ) -> Result<impl IntoResponse, HttpError> {
     body.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+        .map_err(HttpError::validation)?;
+
+    enforce_captcha(&app_state, client_ip, &metadata, body.captcha_token.as_deref()).await?;
+
+    app_state.env.password_policy
+        .check(&body.password, &body.name, &body.email)
+        .map_err(HttpError::bad_request)?;
+
+    if app_state.env.verify_email_mx && !app_state.mx_verifier.accepts_mail(&body.email).await {
+        return Err(HttpError::bad_request(ErrorMessage::EmailDomainUndeliverable));
+    }
+
+    let registration_cap = app_state.env.registration_cap
+        .as_ref()
+        .filter(|cap| !cap.exempts(client_ip))
+        .map(|cap| (cap, registration_day_key(client_ip, Utc::now())));
+
+    if let Some((cap, (key, _))) = &registration_cap {
+        if app_state.rate_limiter.is_exhausted(key, cap.per_ip_per_day).await {
+            return Err(HttpError::too_many_requests(ErrorMessage::RegistrationLimitReached));
+        }
+    }
+
+    if app_state.env.email_ignore_case && email_taken_ignore_case(&app_state, org_id, &body.email).await? {
+        record_captcha_risk(&app_state, client_ip).await;
+        return Err(HttpError::unique_constraint_violation(ErrorMessage::EmailExist));
+    }
 
     let verification_token = uuid::Uuid::new_v4().to_string();
     let expires_at = Utc::now() + Duration::hours(24);
     
-    let hash_password = password::hash(&body.password)
+    let hash_password = password::hash(&body.password, app_state.env.password_pepper.as_ref())
         .map_err(|e| HttpError::server_error(e.to_string()))?;
 
+    let terms_version = app_state.env.terms_version.as_deref();
+    if let Some(current) = terms_version {
+        match body.terms_version.as_deref() {
+            None => return Err(HttpError::bad_request(ErrorMessage::TermsAcceptanceRequired)),
+            Some(accepted) if accepted != current => {
+                return Err(HttpError::bad_request(ErrorMessage::TermsVersionMismatch(current.to_string())));
+            }
+            Some(_) => {}
+        }
+    }
+
+    let invite_code_id = redeem_invite_code(&app_state, org_id, body.invite_code.as_deref()).await?;
+
     let result = app_state.db_client
-        .save_user(&body.name, 
+        .save_user(org_id,
+                   &body.name, 
                    &body.email, 
                    &hash_password, 
-                   &verification_token, 
-                   expires_at)
+                   app_state.env.current_pepper_id(),
+                   &token::hash_token(&verification_token), 
+                   expires_at,
+                   if app_state.env.registration_requires_approval { AccountStatus::PendingApproval } else { AccountStatus::Active },
+                   terms_version)
         .await;
 
+    // A signup that never happened should not spend one of the code's uses.
+    if let (Err(_), Some(code_id)) = (&result, invite_code_id) {
+        if let Err(e) = app_state.db_client.release_invite_code(code_id).await {
+            eprintln!("Failed to release invite code {}: {}", code_id, e);
+        }
+    }
+
     match result {
-        Ok(_user) => {
-            let send_email_result = send_verification_email(&body.email, &body.name, &verification_token).await;
-            if let Err(e) = send_email_result {
-                eprintln!("Failed to send verification email: {}", e);
+        Ok(user) => {
+            record_event(&app_state, Some(user.id), AuditEventType::Register, &metadata, true, None).await;
+
+            if let Some((_, (key, window))) = &registration_cap {
+                app_state.rate_limiter.record(key, *window).await;
             }
-            Ok((StatusCode::CREATED, Json(Response{
+
+            let mut dev_email = DevEmailDto::default();
+
+            if app_state.env.email_verification_mode.sends_link() {
+                let verify_url = app_state.env.api_url("/auth/verify");
+                queue_verification_email(&app_state.email_queue, user.org_id, &body.email, &body.name, &verification_token, &verify_url)
+                    .await
+                    .map_err(|e| HttpError::server_error(format!("Failed to queue verification email: {}", e)))?;
+                dev_email.link = Some(create_verification_link(&verify_url, &verification_token));
+            }
+
+            if app_state.env.email_verification_mode.sends_code() {
+                dev_email.code = Some(send_verification_code(&app_state, &user).await?);
+            }
+
+            let message = if user.status == AccountStatus::PendingApproval {
+                notify_pending_approval(&app_state, &user).await;
+                "Registration successful! Please check your email to verify your account. An administrator will review it before you can sign in"
+            } else {
+                "Registration successful! Please check your email to verify your account"
+            };
+
+            Ok((StatusCode::CREATED, Json(EmailSentResponseDto {
                 status: "success",
-                message: "Registration successful! Please check your email to verify your account".to_string(),
+                message: message.to_string(),
+                dev_email: app_state.env.expose_email_tokens.then_some(dev_email),
             })))
         },
-        Err(sqlx::Error::Database(db_err)) => {
-            if db_err.is_unique_violation() {
-                Err(HttpError::unique_constraint_violation(ErrorMessage::EmailExist.to_string()))
-            } else {
-                Err(HttpError::server_error(db_err.to_string()))
-            }
+        Err(sqlx::Error::Database(db_err)) if db_err.is_unique_violation() => {
+            record_captcha_risk(&app_state, client_ip).await;
+            Err(HttpError::unique_constraint_violation(ErrorMessage::EmailExist))
+        }
+        Err(e) => Err(HttpError::database(e)),
+    }
+}
+
+// One counter per IP per UTC day that expires at the next midnight, so the cap
+// rolls over on the day boundary rather than 24 hours after the first signup.
+fn registration_day_key(client_ip: IpAddr, now: DateTime<Utc>) -> (String, StdDuration) {
+    let today = now.date_naive();
+    let midnight = today
+        .succ_opt()
+        .map(|tomorrow| tomorrow.and_time(NaiveTime::MIN).and_utc())
+        .unwrap_or(now);
+    let window = (midnight - now)
+        .to_std()
+        .unwrap_or_default()
+        .max(StdDuration::from_secs(1));
+
+    (format!("registrations:{}:{}", client_ip, today), window)
+}
+
+async fn notify_pending_approval(app_state: &AppState, user: &User) {
+    if let Err(e) = queue_pending_approval_email(&app_state.email_queue, user.org_id, &user.email, &user.name).await {
+        eprintln!("Failed to queue pending approval email: {}", e);
+    }
+
+    let admin_emails = match app_state.db_client.get_admin_emails(user.org_id).await {
+        Ok(admin_emails) => admin_emails,
+        Err(e) => {
+            eprintln!("Failed to look up admins to notify about {}: {}", user.id, e);
+            return;
+        }
+    };
+
+    let registered_at = user.created_at.unwrap_or_else(Utc::now).format("%Y-%m-%d %H:%M UTC").to_string();
+
+    for admin_email in admin_emails {
+        if let Err(e) = queue_approval_request_email(&app_state.email_queue, user.org_id, &admin_email, &user.name, &user.email, &registered_at).await {
+            eprintln!("Failed to queue approval request email: {}", e);
         }
-        Err(e) => Err(HttpError::server_error(e.to_string())),
     }
 }
 
+async fn redeem_invite_code(app_state: &AppState, org_id: uuid::Uuid, code: Option<&str>) -> Result<Option<uuid::Uuid>, HttpError> {
+    if !app_state.env.invite_codes_required {
+        return Ok(None);
+    }
+
+    let code = code
+        .map(str::trim)
+        .filter(|code| !code.is_empty())
+        .ok_or(HttpError::bad_request(ErrorMessage::InviteCodeRequired))?;
+
+    let redeemed = app_state.db_client
+        .redeem_invite_code(org_id, code)
+        .await
+        .map_err(HttpError::database)?;
+
+    if redeemed.is_some() {
+        return Ok(redeemed);
+    }
+
+    let invite_code = app_state.db_client
+        .get_invite_code(org_id, code)
+        .await
+        .map_err(HttpError::database)?;
+
+    let error = match invite_code {
+        Some(invite_code) if invite_code.revoked_at.is_some() => ErrorMessage::InviteCodeInvalid,
+        Some(invite_code) if invite_code.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) => ErrorMessage::InviteCodeExpired,
+        Some(_) => ErrorMessage::InviteCodeExhausted,
+        None => ErrorMessage::InviteCodeInvalid,
+    };
+
+    Err(HttpError::bad_request(error))
+}
+
+pub async fn email_taken_ignore_case(app_state: &AppState, org_id: uuid::Uuid, email: &str) -> Result<bool, HttpError> {
+    app_state.db_client
+        .is_email_taken_ignore_case(org_id, email)
+        .await
+        .map_err(HttpError::database)
+}
+
+pub async fn check_email_available(
+    ClientIp(client_ip): ClientIp,
+    Query(query_params): Query<EmailAvailabilityQueryDto>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(Tenant(org_id)): Extension<Tenant>
+) -> Result<impl IntoResponse, HttpError> {
+    query_params.validate()
+        .map_err(HttpError::validation)?;
+
+    let rate_limit_key = format!("email-available:{}", client_ip);
+    if !app_state.rate_limiter.check(&rate_limit_key, app_state.env.email_availability_rate_limit, StdDuration::from_secs(3600)).await {
+        return Ok(Json(EmailAvailabilityDto { available: true }));
+    }
+
+    let taken = if app_state.env.email_ignore_case {
+        email_taken_ignore_case(&app_state, org_id, &query_params.email).await?
+    } else {
+        app_state.db_client
+            .is_email_taken(org_id, &query_params.email)
+            .await
+            .map_err(HttpError::database)?
+    };
+
+    Ok(Json(EmailAvailabilityDto { available: !taken }))
+}
+
 pub async fn login (
+    ClientIp(client_ip): ClientIp,
     Extension(app_state): Extension<Arc<AppState>>,
-    Json(body): Json<LoginUserDto>
+    Extension(Tenant(org_id)): Extension<Tenant>,
+    cookie_jar: CookieJar,
+    metadata: RequestMetadata,
+    StrictJson(body): StrictJson<LoginUserDto>
 ) -> Result<impl IntoResponse, HttpError> {
     body.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+        .map_err(HttpError::validation)?;
 
-    let result = app_state.db_client
-        .get_user(None, None, Some(&body.email), None)
+    let known = app_state.env.login_throttle_known;
+    let unknown = app_state.env.login_throttle_unknown;
+    let unknown_key = format!("login-unknown:{}", client_ip);
+    let attempt_keys = LoginAttemptKeys::new(client_ip, &body.email);
+
+    if app_state.rate_limiter.is_exhausted(&unknown_key, unknown.max_failures).await
+        || attempt_keys.exhausted(&app_state).await
+    {
+        return Err(HttpError::too_many_requests(ErrorMessage::TooManyRequests));
+    }
+
+    enforce_captcha(&app_state, client_ip, &metadata, body.captcha_token.as_deref()).await?;
+
+    let mut result = app_state.db_client
+        .get_user_by_login_email(org_id, &body.email)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::database)?;
+
+    // An exact match always wins, a case-insensitive match is only used when it is unique
+    if result.is_none() && app_state.env.email_ignore_case {
+        let mut candidates = app_state.db_client
+            .get_users_by_login_email_ignore_case(org_id, &body.email)
+            .await
+            .map_err(HttpError::database)?;
+
+        if candidates.len() > 1 {
+            app_state.rate_limiter.record(&unknown_key, unknown.window()).await;
+            attempt_keys.record_failure(&app_state).await;
+            return Err(HttpError::new(ErrorMessage::AmbiguousLoginEmail, StatusCode::CONFLICT));
+        }
+
+        result = candidates.pop();
+    }
     
-    let user = result.ok_or(HttpError::bad_request(ErrorMessage::WrongCredentials.to_string()))?;
+    let user = match result {
+        Some(user) => user,
+        None => {
+            app_state.rate_limiter.record(&unknown_key, unknown.window()).await;
+            attempt_keys.record_failure(&app_state).await;
+            record_captcha_risk(&app_state, client_ip).await;
+            record_event(&app_state, None, AuditEventType::LoginFailed, &metadata, false, Some(&body.email)).await;
+            return Err(HttpError::bad_request(ErrorMessage::WrongCredentials));
+        }
+    };
 
-    let password_matched = password::compare(&body.password, &user.password)
-        .map_err(|_| HttpError::bad_request(ErrorMessage::WrongCredentials.to_string()))?;
+    let known_key = format!("login-known:{}", user.id);
 
-    if password_matched {
-        let token = token::create_token(&user.id.to_string(), &app_state.env.jwt_secret.as_bytes(), app_state.env.jwt_maxage)
-            .map_err(|e| HttpError::server_error(e.to_string()))?;
+    if app_state.rate_limiter.is_exhausted(&known_key, known.max_failures).await {
+        return Err(HttpError::too_many_requests(ErrorMessage::TooManyRequests));
+    }
+
+    let pepper = app_state.env.pepper_for(user.password_pepper_id.as_deref())
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let password_matched = password::compare(&body.password, &user.password, pepper)
+        .unwrap_or(false);
+
+    if password_matched && user.password_pepper_id.as_deref() != app_state.env.current_pepper_id() {
+        rehash_password(&app_state, &user, &body.password).await;
+    }
+
+    if !password_matched {
+        app_state.rate_limiter.record(&known_key, known.window()).await;
+        attempt_keys.record_failure(&app_state).await;
+        record_captcha_risk(&app_state, client_ip).await;
+        record_event(&app_state, Some(user.id), AuditEventType::LoginFailed, &metadata, false, None).await;
+
+        if app_state.rate_limiter.is_exhausted(&known_key, known.max_failures).await {
+            record_event(&app_state, Some(user.id), AuditEventType::AccountLocked, &metadata, true, None).await;
+            notify_lockout(&app_state, &user, &metadata).await;
+        }
 
-        let cookie_duration = time::Duration::minutes(app_state.env.jwt_maxage * 60);
-        let cookie = Cookie::build(("token", token.clone()))
-            .path("/")
-            .max_age(cookie_duration)
-            .http_only(true)
-            .build();
+        return Err(HttpError::bad_request(ErrorMessage::WrongCredentials));
+    }
+
+    app_state.rate_limiter.reset(&known_key).await;
+    attempt_keys.reset(&app_state).await;
 
-        let response = axum::response::Json(UserLoginResponseDto {
+    if user.status == AccountStatus::Deactivated && app_state.env.self_reactivation {
+        let reactivation_token = token::create_purpose_token(
+            &user.id.to_string(),
+            token::REACTIVATION_PURPOSE,
+            &app_state.env.jwt_keys,
+            10
+        ).map_err(|e| HttpError::server_error(e.to_string()))?;
+
+        return Ok(Json(ReactivationChallengeResponseDto {
             status: "success".to_string(),
-            token,
-        });
+            reactivation_required: true,
+            reactivation_token,
+        }).into_response());
+    }
 
-        let mut header = HeaderMap::new();
+    ensure_active(&user)?;
 
-        header.append(header::SET_COOKIE, cookie.to_string().parse().unwrap());
-        let mut response = response.into_response();
-        response.headers_mut().extend(header);
-        Ok(response)
-    } else {
-        Err(HttpError::bad_request(ErrorMessage::WrongCredentials.to_string()))?
+    let trusted_device = cookie_jar
+        .get(TRUSTED_DEVICE_COOKIE)
+        .map(|cookie| cookie.value().to_string());
+
+    if user.totp_enabled && !is_trusted_device(&app_state, &user, trusted_device.as_deref()).await? {
+        let challenge_token = token::create_purpose_token(
+            &user.id.to_string(),
+            token::TWO_FACTOR_PURPOSE,
+            &app_state.env.jwt_keys,
+            5
+        ).map_err(|e| HttpError::server_error(e.to_string()))?;
+
+        return Ok(Json(TwoFactorChallengeResponseDto {
+            status: "success".to_string(),
+            two_factor_required: true,
+            challenge_token,
+        }).into_response());
     }
+
+    record_event(&app_state, Some(user.id), AuditEventType::Login, &metadata, true, user.totp_enabled.then_some("trusted device")).await;
+
+    login_response(&app_state, &user, &metadata).await
 }
 
-pub async fn verify_email(
-    Query(query_params): Query<VerifyEmailQueryDto>,
-    Extension(app_state): Extension<Arc<AppState>>
+pub async fn verify_two_factor(
+    Extension(app_state): Extension<Arc<AppState>>,
+    metadata: RequestMetadata,
+    Json(body): Json<TwoFactorLoginDto>
 ) -> Result<impl IntoResponse, HttpError> {
-    query_params.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    body.validate()
+        .map_err(HttpError::validation)?;
 
-    let result = app_state.db_client
-        .get_user(None, None, None, Some(&query_params.token))
-        .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+    let user = two_factor_challenge_user(&app_state, &body.challenge_token).await?;
 
-    let user = result.ok_or(HttpError::unauthorized(ErrorMessage::InvalidToken.to_string()))?;
+    let code_matched = user.totp_secret
+        .as_deref()
+        .map(|secret| totp::verify_code(secret, &body.code))
+        .unwrap_or(false);
 
-    if let Some(expires_at) = user.token_expires_at {
-        if Utc::now() > expires_at {
-            return Err(HttpError::bad_request("Verification token has expired".to_string()))?;
-        }
-    } else {
-        return Err(HttpError::bad_request("Invalid Verification Token".to_string()))?; 
+    if !code_matched {
+        record_event(&app_state, Some(user.id), AuditEventType::LoginFailed, &metadata, false, Some("invalid two-factor code")).await;
+        return Err(HttpError::unauthorized(ErrorMessage::InvalidTwoFactorCode));
     }
-     
-    app_state.db_client.verified_token(&query_params.token).await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
 
-    let send_welcome_email_result = send_welcome_email(&user.email, &user.name).await;
+    record_event(&app_state, Some(user.id), AuditEventType::TwoFactorLogin, &metadata, true, None).await;
+
+    let mut response = login_response(&app_state, &user, &metadata).await?;
 
-    if let Err(e) = send_welcome_email_result {
-        eprintln!("Failed to send welcome email: {}", e);
+    if let (true, Some(days)) = (body.trust_device, app_state.env.trusted_device_days) {
+        let device_token = trust_device(&app_state, &user, &metadata, days).await?;
+        let cookie = trusted_device_cookie(device_token, days, &app_state.env);
+
+        response.headers_mut().append(header::SET_COOKIE, cookie.to_string().parse().unwrap());
     }
 
-    let token = token::create_token(&user.id.to_string(), &app_state.env.jwt_secret.as_bytes(), app_state.env.jwt_maxage)
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+    Ok(response)
+}
 
-    let cookie_duration = time::Duration::minutes(app_state.env.jwt_maxage * 60);
-    let cookie = Cookie::build(("token", token.clone()))
-        .path("/")
-        .max_age(cookie_duration)
-        .http_only(true)
-        .build();
+pub async fn recover_two_factor(
+    Extension(app_state): Extension<Arc<AppState>>,
+    metadata: RequestMetadata,
+    Json(body): Json<RecoveryLoginDto>
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(HttpError::validation)?;
 
-    let mut headers = HeaderMap::new();
+    let user = two_factor_challenge_user(&app_state, &body.challenge_token).await?;
 
-    headers.append(
-        header::SET_COOKIE,
-        cookie.to_string().parse().unwrap(),
-    );
+    let code_hash = token::hash_token(&totp::normalize_recovery_code(&body.recovery_code));
 
-    let frontend_url = format!("https://localhost:5173/settings");
-    let redirect = Redirect::to(&frontend_url);
-    let mut response = redirect.into_response();
-    response.headers_mut().extend(headers);
-    Ok(response)
+    let consumed = app_state.db_client
+        .consume_recovery_code(user.id, &code_hash)
+        .await
+        .map_err(HttpError::database)?;
+
+    if !consumed {
+        record_event(&app_state, Some(user.id), AuditEventType::LoginFailed, &metadata, false, Some("invalid recovery code")).await;
+        return Err(HttpError::unauthorized(ErrorMessage::InvalidTwoFactorCode));
+    }
+
+    record_event(&app_state, Some(user.id), AuditEventType::RecoveryCodeUsed, &metadata, true, None).await;
+
+    login_response(&app_state, &user, &metadata).await
 }
 
-pub async fn forgot_password(
+pub async fn reactivate_account(
     Extension(app_state): Extension<Arc<AppState>>,
-    Json(body): Json<ForgotPasswordRequestDto>
+    metadata: RequestMetadata,
+    StrictJson(body): StrictJson<ReactivateAccountDto>
 ) -> Result<impl IntoResponse, HttpError> {
     body.validate()
-       .map_err(|e| HttpError::bad_request(e.to_string()))?;
-
-    let result = app_state.db_client
-            .get_user(None, None, Some(&body.email), None)
-            .await
-            .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::validation)?;
 
-    let user = result.ok_or(HttpError::bad_request("Email not found!".to_string()))?;
+    if !app_state.env.self_reactivation {
+        return Err(HttpError::not_found("Self-service reactivation is not enabled".to_string()));
+    }
 
-    let verification_token = uuid::Uuid::new_v4().to_string();
-    let expires_at = Utc::now() + Duration::minutes(30);
+    let claims = token::decode_purpose_token(&body.reactivation_token, token::REACTIVATION_PURPOSE, &app_state.env.jwt_keys)?;
 
-    let user_id = uuid::Uuid::parse_str(&user.id.to_string()).unwrap();
+    let user_id = uuid::Uuid::parse_str(&claims.sub)
+        .map_err(|_| HttpError::unauthorized(ErrorMessage::InvalidToken))?;
 
-    app_state.db_client
-        .add_verified_token(user_id, &verification_token, expires_at)
+    let user = app_state.db_client
+        .reactivate_user(user_id)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::database)?
+        .ok_or(HttpError::new("Account is not deactivated".to_string(), StatusCode::CONFLICT))?;
 
-    let reset_link = format!("http://localhost:5173/reset-password?token={}", &verification_token);
+    record_event(&app_state, Some(user.id), AuditEventType::AccountReactivated, &metadata, true, Some("from=deactivated to=active")).await;
 
-    let email_sent = send_forget_password_email(&user.email, &reset_link, &user.name).await;
+    Ok(Json(Response {
+        message: "Your account has been reactivated, please sign in again".to_string(),
+        status: "success",
+    }))
+}
 
-    if let Err(e) = email_sent {
-        eprintln!("Failed to send forgot password email: {}", e);
-        return Err(HttpError::server_error("Failed to send email".to_string()));
+pub async fn get_auth_methods(
+    Extension(app_state): Extension<Arc<AppState>>
+) -> Result<impl IntoResponse, HttpError> {
+    let methods = app_state.env.auth_methods
+        .iter()
+        .map(|method| method.to_str().to_string())
+        .collect();
+
+    Ok(Json(AuthMethodsResponseDto {
+        status: "success".to_string(),
+        methods,
+    }))
+}
+
+pub async fn get_session_status(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>
+) -> Result<impl IntoResponse, HttpError> {
+    let expires_at = user.token_expires_at
+        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::TokenNotProvided))?;
+
+    // A token inside the clock skew leeway still authenticates but is not worth reporting as live.
+    let seconds_remaining = (expires_at - Utc::now()).num_seconds();
+    if seconds_remaining <= 0 {
+        return Err(HttpError::unauthorized(ErrorMessage::InvalidToken));
     }
 
-    let response = Response {
-        message: "Password reset link has been sent to your email.".to_string(),
-        status: "success",
-    };
+    let refresh_at = expires_at - Duration::seconds(app_state.env.session_refresh_margin_seconds.min(seconds_remaining));
 
-    Ok(Json(response))
+    Ok(Json(SessionStatusDto {
+        status: "success".to_string(),
+        expires_at,
+        seconds_remaining,
+        refresh_at,
+        refresh_expires_at: user.session_expires_at,
+    }))
 }
 
-pub async fn reset_password(
+const INTROSPECTION_CONCURRENCY: usize = 8;
+
+pub async fn introspect_tokens(
     Extension(app_state): Extension<Arc<AppState>>,
-    Json(body): Json<ResetPasswordRequestDto>
+    StrictJson(body): StrictJson<IntrospectBatchDto>
 ) -> Result<impl IntoResponse, HttpError> {
     body.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+        .map_err(HttpError::validation)?;
 
-    let result = app_state.db_client
-        .get_user(None, None, None, Some(&body.token))
+    let max_batch = app_state.env.introspection_max_batch;
+    if body.tokens.len() > max_batch {
+        return Err(HttpError::bad_request(ErrorMessage::IntrospectionBatchTooLarge(max_batch)));
+    }
+
+    let results = stream::iter(body.tokens)
+        .map(|token| introspect_token(app_state.clone(), token))
+        .buffered(INTROSPECTION_CONCURRENCY)
+        .collect::<Vec<_>>()
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
 
-    let user = result.ok_or(HttpError::bad_request("Invalid or expired token".to_string()))?;
+    Ok(Json(IntrospectBatchResponseDto {
+        status: "success".to_string(),
+        results,
+    }))
+}
 
-    if let Some(expires_at) = user.token_expires_at {
-        if Utc::now() > expires_at {
-            return Err(HttpError::bad_request("Verification token has expired".to_string()))?;
+// Runs the same checks as the auth middleware without touching the session,
+// anything that would not authenticate a request is reported as inactive.
+async fn introspect_token(app_state: Arc<AppState>, token: String) -> Result<IntrospectionResultDto, HttpError> {
+    let inactive = IntrospectionResultDto::default();
+
+    let claims = match token::decode_token(&token, &app_state.env.jwt_keys) {
+        Ok(claims) if claims.purpose.is_none() && claims.exp as i64 > Utc::now().timestamp() => claims,
+        _ => return Ok(inactive),
+    };
+
+    let Ok(user_id) = uuid::Uuid::parse_str(&claims.sub) else {
+        return Ok(inactive);
+    };
+
+    let user = app_state.db_client
+        .get_user(Some(user_id), None, None, None)
+        .await
+        .map_err(HttpError::database)?;
+
+    let Some(user) = user else {
+        return Ok(inactive);
+    };
+
+    let revoked = user.tokens_valid_after.is_some_and(|valid_after| (claims.iat as i64) < valid_after.timestamp());
+    let wrong_org = claims.org.as_deref().is_some_and(|org| org != user.org_id.to_string());
+
+    if revoked || wrong_org || ensure_active(&user).is_err() {
+        return Ok(inactive);
+    }
+
+    if let Some(sid) = claims.sid.as_deref() {
+        let Ok(session_id) = uuid::Uuid::parse_str(sid) else {
+            return Ok(inactive);
+        };
+
+        let session_expires_at = app_state.db_client
+            .get_active_session_expiry(session_id, user.id)
+            .await
+            .map_err(HttpError::database)?;
+
+        if session_expires_at.is_none() {
+            return Ok(inactive);
         }
-    }else {
-        return Err(HttpError::bad_request("Invalid verification token".to_string()))?;
     }
 
-    let user_id = uuid::Uuid::parse_str(&user.id.to_string()).unwrap();
+    Ok(IntrospectionResultDto {
+        active: true,
+        sub: Some(user.id.to_string()),
+        role: Some(user.role.to_str().to_string()),
+        exp: Some(claims.exp),
+    })
+}
 
-    let hash_password = password::hash(&body.new_password)
-            .map_err(|e| HttpError::server_error(e.to_string()))?;
+pub async fn reauth(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata,
+    StrictJson(body): StrictJson<ReauthDto>
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(HttpError::validation)?;
 
-    app_state.db_client
-        .update_user_password(user_id.clone(), hash_password)
-        .await
+    let Some(session_id) = user.session_id.filter(|_| user.impersonated_by.is_none()) else {
+        return Err(HttpError::new(ErrorMessage::ReauthenticationUnavailable, StatusCode::FORBIDDEN));
+    };
+
+    let user = user.user;
+    let known = app_state.env.login_throttle_known;
+    let known_key = format!("login-known:{}", user.id);
+
+    if app_state.rate_limiter.is_exhausted(&known_key, known.max_failures).await {
+        return Err(HttpError::too_many_requests(ErrorMessage::TooManyRequests));
+    }
+
+    let pepper = app_state.env.pepper_for(user.password_pepper_id.as_deref())
         .map_err(|e| HttpError::server_error(e.to_string()))?;
 
-    app_state.db_client
-        .verified_token(&body.token)
+    let password_matched = password::compare(&body.password, &user.password, pepper)
+        .unwrap_or(false);
+
+    if !password_matched {
+        app_state.rate_limiter.record(&known_key, known.window()).await;
+        record_event(&app_state, Some(user.id), AuditEventType::Reauthenticated, &metadata, false, None).await;
+
+        if app_state.rate_limiter.is_exhausted(&known_key, known.max_failures).await {
+            record_event(&app_state, Some(user.id), AuditEventType::AccountLocked, &metadata, true, None).await;
+            notify_lockout(&app_state, &user, &metadata).await;
+        }
+
+        return Err(HttpError::bad_request(ErrorMessage::WrongCredentials));
+    }
+
+    let elevated_until = Utc::now() + Duration::minutes(app_state.env.reauth_minutes);
+
+    let elevated = app_state.db_client
+        .elevate_session(session_id, user.id, elevated_until)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::database)?;
 
-    let response = Response {
-        message: "Password has been successfully reset.".to_string(),
-        status: "success",
+    if !elevated {
+        return Err(HttpError::unauthorized(ErrorMessage::InvalidToken));
+    }
+
+    record_event(&app_state, Some(user.id), AuditEventType::Reauthenticated, &metadata, true, None).await;
+
+    Ok(Json(ReauthResponseDto {
+        status: "success".to_string(),
+        elevated_until,
+    }))
+}
+
+async fn record_captcha_risk(app_state: &AppState, client_ip: IpAddr) {
+    if let Some(captcha) = &app_state.env.captcha {
+        app_state.rate_limiter.record(&format!("captcha-risk:{}", client_ip), captcha.window()).await;
+    }
+}
+
+async fn enforce_captcha(
+    app_state: &AppState,
+    client_ip: IpAddr,
+    metadata: &RequestMetadata,
+    captcha_token: Option<&str>
+) -> Result<(), HttpError> {
+    let Some(captcha) = &app_state.env.captcha else {
+        return Ok(());
     };
 
-    Ok(Json(response))
+    let looks_automated = metadata.user_agent.as_deref().is_none_or(|agent| agent.trim().is_empty())
+        || captcha.blocked_ips.iter().any(|network| network.contains(client_ip));
+    let risk_key = format!("captcha-risk:{}", client_ip);
+
+    if !looks_automated && !app_state.rate_limiter.is_exhausted(&risk_key, captcha.failure_threshold).await {
+        return Ok(());
+    }
+
+    let captcha_required = || HttpError::new(ErrorMessage::CaptchaRequired, StatusCode::PRECONDITION_REQUIRED);
+
+    let token = captcha_token
+        .filter(|token| !token.trim().is_empty())
+        .ok_or_else(captcha_required)?;
+
+    let passed = captcha::verify(captcha.provider, &captcha.secret, token, client_ip)
+        .await
+        .map_err(|e| {
+            eprintln!("Captcha verification failed: {}", e);
+            HttpError::new(ErrorMessage::CaptchaUnavailable, StatusCode::SERVICE_UNAVAILABLE)
+        })?;
+
+    if !passed {
+        return Err(captcha_required());
+    }
+
+    Ok(())
+}
+
+async fn two_factor_challenge_user(app_state: &AppState, challenge_token: &str) -> Result<User, HttpError> {
+    let claims = token::decode_purpose_token(challenge_token, token::TWO_FACTOR_PURPOSE, &app_state.env.jwt_keys)?;
+
+    let rate_limit_key = format!("2fa:{}", claims.sub);
+    if !app_state.rate_limiter.check(&rate_limit_key, 5, StdDuration::from_secs(300)).await {
+        return Err(HttpError::too_many_requests(ErrorMessage::TooManyRequests));
+    }
+
+    let user_id = uuid::Uuid::parse_str(&claims.sub)
+        .map_err(|_| HttpError::unauthorized(ErrorMessage::InvalidToken))?;
+
+    let user = app_state.db_client
+        .get_user(Some(user_id), None, None, None)
+        .await
+        .map_err(HttpError::database)?
+        .ok_or(HttpError::unauthorized(ErrorMessage::UserNoLongerExist))?;
+
+    if !user.totp_enabled {
+        return Err(HttpError::unauthorized(ErrorMessage::InvalidToken));
+    }
+
+    Ok(user)
+}
+
+async fn is_trusted_device(app_state: &AppState, user: &User, device_token: Option<&str>) -> Result<bool, HttpError> {
+    let (Some(device_token), Some(_)) = (device_token, app_state.env.trusted_device_days) else {
+        return Ok(false);
+    };
+
+    let Ok(claims) = token::verify_payload::<token::TrustedDeviceClaims>(device_token, token::TRUSTED_DEVICE_PURPOSE, &app_state.env.jwt_keys) else {
+        return Ok(false);
+    };
+
+    let Ok(device_id) = uuid::Uuid::parse_str(&claims.data.did) else {
+        return Ok(false);
+    };
+
+    if claims.sub != user.id.to_string() {
+        return Ok(false);
+    }
+
+    app_state.db_client
+        .use_trusted_device(device_id, user.id)
+        .await
+        .map_err(HttpError::database)
+}
+
+async fn trust_device(app_state: &AppState, user: &User, metadata: &RequestMetadata, days: i64) -> Result<String, HttpError> {
+    let device = app_state.db_client
+        .create_trusted_device(
+            user.id,
+            metadata.ip_address.as_deref(),
+            metadata.user_agent.as_deref(),
+            Utc::now() + Duration::days(days)
+        )
+        .await
+        .map_err(HttpError::database)?;
+
+    let details = format!("device={}", device.id);
+    record_event(app_state, Some(user.id), AuditEventType::TrustedDeviceAdded, metadata, true, Some(&details)).await;
+
+    token::sign_payload(
+        &user.id.to_string(),
+        token::TRUSTED_DEVICE_PURPOSE,
+        token::TrustedDeviceClaims { did: device.id.to_string() },
+        &app_state.env.jwt_keys,
+        days * 24 * 60
+    ).map_err(|e| HttpError::server_error(e.to_string()))
+}
+
+async fn issue_session_token(app_state: &AppState, user: &User, metadata: &RequestMetadata) -> Result<(String, DateTime<Utc>), HttpError> {
+    ensure_active(user)?;
+
+    if let Some(limit) = app_state.env.session_limit_for(user) {
+        enforce_session_limit(app_state, user, metadata, limit).await?;
+    }
+
+    let maxage = app_state.env.jwt_maxage_for(user.role);
+
+    let session = app_state.db_client
+        .create_session(
+            user.id,
+            metadata.ip_address.as_deref(),
+            metadata.user_agent.as_deref(),
+            &metadata.binding(),
+            Utc::now() + Duration::minutes(maxage),
+            true
+        )
+        .await
+        .map_err(HttpError::database)?;
+
+    let token = token::create_token(&user.id.to_string(), &session.id.to_string(), &user.org_id.to_string(), &app_state.env.jwt_keys, maxage)
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    Ok((token, session.expires_at))
+}
+
+async fn enforce_session_limit(app_state: &AppState, user: &User, metadata: &RequestMetadata, limit: i64) -> Result<(), HttpError> {
+    match app_state.env.session_limit_policy {
+        SessionLimitPolicy::Reject => {
+            let active_sessions = app_state.db_client
+                .get_user_session_count(user.id, true)
+                .await
+                .map_err(HttpError::database)?;
+
+            if active_sessions >= limit {
+                return Err(HttpError::new(ErrorMessage::SessionLimitReached(limit), StatusCode::CONFLICT));
+            }
+        }
+        SessionLimitPolicy::EvictOldest => {
+            let evicted = app_state.db_client
+                .revoke_oldest_sessions(user.id, limit - 1)
+                .await
+                .map_err(HttpError::database)?;
+
+            if evicted > 0 {
+                let details = format!("evicted={} limit={}", evicted, limit);
+                record_event(app_state, Some(user.id), AuditEventType::SessionsEvicted, metadata, true, Some(&details)).await;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn login_response(app_state: &AppState, user: &User, metadata: &RequestMetadata) -> Result<axum::response::Response, HttpError> {
+    let (token, expires_at) = issue_session_token(app_state, user, metadata).await?;
+
+    let cookie = auth_cookie(token.clone(), user.role, &app_state.env);
+
+    let response = axum::response::Json(UserLoginResponseDto {
+        status: "success".to_string(),
+        token,
+        expires_at,
+        must_change_password: user.password_expired(app_state.env.password_max_age_days),
+        user: app_state.env.login_response_includes_user.then(|| FilterUserDto::filter_user(user)),
+    });
+
+    let mut header = HeaderMap::new();
+
+    header.append(header::SET_COOKIE, cookie.to_string().parse().unwrap());
+    let mut response = response.into_response();
+    response.headers_mut().extend(header);
+    Ok(response)
+}
+
+// Spreading guesses over many emails from one IP, or one email over many IPs,
+// still trips a counter. The IP counter is never reset by a successful login.
+struct LoginAttemptKeys {
+    ip: String,
+    email: String,
+    pair: String,
+}
+
+impl LoginAttemptKeys {
+    fn new(client_ip: IpAddr, email: &str) -> Self {
+        let email = email.trim().to_lowercase();
+
+        LoginAttemptKeys {
+            ip: format!("login-ip:{}", client_ip),
+            pair: format!("login-pair:{}:{}", client_ip, email),
+            email: format!("login-email:{}", email),
+        }
+    }
+
+    async fn exhausted(&self, app_state: &AppState) -> bool {
+        let env = &app_state.env;
+
+        app_state.rate_limiter.is_exhausted(&self.ip, env.login_throttle_ip.max_failures).await
+            || app_state.rate_limiter.is_exhausted(&self.email, env.login_throttle_email.max_failures).await
+            || app_state.rate_limiter.is_exhausted(&self.pair, env.login_throttle_pair.max_failures).await
+    }
+
+    async fn record_failure(&self, app_state: &AppState) {
+        let env = &app_state.env;
+
+        app_state.rate_limiter.record(&self.ip, env.login_throttle_ip.window()).await;
+        app_state.rate_limiter.record(&self.email, env.login_throttle_email.window()).await;
+        app_state.rate_limiter.record(&self.pair, env.login_throttle_pair.window()).await;
+    }
+
+    async fn reset(&self, app_state: &AppState) {
+        app_state.rate_limiter.reset(&self.email).await;
+        app_state.rate_limiter.reset(&self.pair).await;
+    }
+}
+
+async fn notify_lockout(app_state: &AppState, user: &User, metadata: &RequestMetadata) {
+    let alerts = &app_state.env.lockout_alerts;
+    let locked_at = Utc::now().format("%Y-%m-%d %H:%M UTC").to_string();
+    let location = metadata.ip_address
+        .as_deref()
+        .and_then(|ip| IpMasking::Partial.apply(ip));
+    let device = metadata.user_agent
+        .as_deref()
+        .and_then(device::describe);
+    let source = match (location, device) {
+        (Some(location), Some(device)) => format!("{} ({})", location, device),
+        (Some(source), None) | (None, Some(source)) => source,
+        (None, None) => "an unknown source".to_string(),
+    };
+
+    if alerts.notify_user && app_state.rate_limiter.check(&format!("lockout-notice:{}", user.id), 1, alerts.notify_interval()).await {
+        let unlock_minutes = app_state.env.login_throttle_known.window_seconds.div_ceil(60);
+
+        if let Err(e) = queue_account_locked_email(&app_state.email_queue, user.org_id, &user.email, user.display_name(), &locked_at, &source, unlock_minutes, &user.notification_preferences).await {
+            eprintln!("Failed to queue account locked email: {}", e);
+        }
+    }
+
+    let Some(admin_email) = alerts.admin_email.as_deref() else {
+        return;
+    };
+
+    let lockouts_key = format!("lockouts:{}", user.id);
+    app_state.rate_limiter.record(&lockouts_key, alerts.admin_window()).await;
+
+    if !app_state.rate_limiter.is_exhausted(&lockouts_key, alerts.admin_threshold).await {
+        return;
+    }
+
+    if app_state.rate_limiter.check(&format!("lockout-alert:{}", user.id), 1, alerts.admin_window()).await {
+        let window_hours = alerts.admin_window_seconds.div_ceil(3600);
+
+        if let Err(e) = queue_lockout_alert_email(&app_state.email_queue, user.org_id, admin_email, &user.email, alerts.admin_threshold, window_hours, &locked_at, &source).await {
+            eprintln!("Failed to queue lockout alert email: {}", e);
+        }
+    }
+}
+
+async fn rehash_password(app_state: &AppState, user: &User, plain_password: &str) {
+    let result = password::hash(plain_password, app_state.env.password_pepper.as_ref());
+
+    let hash_password = match result {
+        Ok(hash_password) => hash_password,
+        Err(e) => {
+            eprintln!("Failed to rehash password for {}: {}", user.id, e);
+            return;
+        }
+    };
+
+    let result = app_state.db_client
+        .rehash_user_password(user.id, hash_password, app_state.env.current_pepper_id())
+        .await;
+
+    if let Err(e) = result {
+        eprintln!("Failed to store rehashed password for {}: {}", user.id, e);
+    }
+}
+
+pub async fn verify_email(
+    Query(query_params): Query<VerifyEmailQueryDto>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    metadata: RequestMetadata
+) -> Result<impl IntoResponse, HttpError> {
+    query_params.validate()
+        .map_err(HttpError::validation)?;
+
+    let token_hash = token::hash_token(&query_params.token);
+
+    let result = app_state.db_client
+        .get_user(None, None, None, Some(&token_hash))
+        .await
+        .map_err(HttpError::database)?;
+
+    let Some(user) = result else {
+        return replayed_verification(&app_state, &token_hash).await;
+    };
+
+    if user.verified {
+        return Ok(Redirect::to(&app_state.env.frontend_link(&app_state.env.already_verified_path)).into_response());
+    }
+
+    if let Some(expires_at) = user.token_expires_at {
+        if Utc::now() > expires_at {
+            return Err(HttpError::bad_request(ErrorMessage::TokenExpired))?;
+        }
+    } else {
+        return Err(HttpError::bad_request("Invalid Verification Token".to_string()))?; 
+    }
+     
+    let consumed = app_state.db_client.verified_token(&token_hash).await
+        .map_err(HttpError::database)?;
+
+    if !consumed {
+        return replayed_verification(&app_state, &token_hash).await;
+    }
+
+    record_event(&app_state, Some(user.id), AuditEventType::EmailVerified, &metadata, true, Some(&user.email)).await;
+
+    if let Err(e) = queue_welcome_email(&app_state.email_queue, user.org_id, &user.email, &user.name, &user.notification_preferences).await {
+        eprintln!("Failed to queue welcome email: {}", e);
+    }
+
+    let (token, _) = issue_session_token(&app_state, &user, &metadata).await?;
+
+    let cookie = auth_cookie(token, user.role, &app_state.env);
+
+    let mut headers = HeaderMap::new();
+
+    headers.append(
+        header::SET_COOKIE,
+        cookie.to_string().parse().unwrap(),
+    );
+
+    let frontend_url = app_state.env.frontend_link("/settings");
+    let redirect = Redirect::to(&frontend_url);
+    let mut response = redirect.into_response();
+    response.headers_mut().extend(headers);
+    Ok(response)
+}
+
+// A second click on the link, or one that lost the race to consume it, lands
+// here. Shortly after verification it is answered like the first click but
+// without signing anyone in, later replays are rejected.
+async fn replayed_verification(app_state: &AppState, token_hash: &str) -> Result<axum::response::Response, HttpError> {
+    let consumed_since = Utc::now() - Duration::minutes(app_state.env.verification_replay_window_minutes);
+
+    let consumed = app_state.db_client
+        .is_verification_token_consumed(token_hash, consumed_since)
+        .await
+        .map_err(HttpError::database)?;
+
+    if consumed {
+        return Ok(Redirect::to(&app_state.env.frontend_link(&app_state.env.already_verified_path)).into_response());
+    }
+
+    Err(HttpError::unauthorized(ErrorMessage::InvalidToken))
+}
+
+const VERIFICATION_CODE_TTL_MINUTES: i64 = 15;
+const VERIFICATION_CODE_MAX_ATTEMPTS: i32 = 5;
+
+fn verification_code_hash(user: &User, code: &str) -> String {
+    token::hash_token(&format!("{}:{}", user.id, code))
+}
+
+async fn send_verification_code(app_state: &AppState, user: &User) -> Result<String, HttpError> {
+    let code = token::generate_numeric_code();
+    let expires_at = Utc::now() + Duration::minutes(VERIFICATION_CODE_TTL_MINUTES);
+
+    app_state.db_client
+        .save_verification_code(user.id, &verification_code_hash(user, &code), expires_at)
+        .await
+        .map_err(HttpError::database)?;
+
+    queue_verification_code_email(&app_state.email_queue, user.org_id, &user.email, &user.name, &code, VERIFICATION_CODE_TTL_MINUTES)
+        .await
+        .map_err(|e| HttpError::server_error(format!("Failed to queue verification code email: {}", e)))?;
+
+    Ok(code)
+}
+
+pub async fn verify_email_code(
+    ClientIp(client_ip): ClientIp,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(Tenant(org_id)): Extension<Tenant>,
+    metadata: RequestMetadata,
+    StrictJson(body): StrictJson<VerifyEmailCodeDto>
+) -> Result<impl IntoResponse, HttpError> {
+    if !app_state.env.email_verification_mode.sends_code() {
+        return Err(HttpError::not_found("Code verification is not enabled".to_string()));
+    }
+
+    let rate_limit_key = format!("verify-code:{}", client_ip);
+    if !app_state.rate_limiter.check(&rate_limit_key, app_state.env.reset_verify_rate_limit, StdDuration::from_secs(60)).await {
+        return Err(HttpError::too_many_requests(ErrorMessage::TooManyRequests));
+    }
+
+    body.validate()
+        .map_err(HttpError::validation)?;
+
+    let invalid_code = || HttpError::bad_request("Invalid or expired verification code".to_string());
+
+    let user = app_state.db_client
+        .get_user_by_email(org_id, &body.email)
+        .await
+        .map_err(HttpError::database)?
+        .filter(|user| !user.verified)
+        .ok_or_else(invalid_code)?;
+
+    let code = app_state.db_client
+        .get_verification_code(user.id)
+        .await
+        .map_err(HttpError::database)?
+        .ok_or_else(invalid_code)?;
+
+    if code.attempts >= VERIFICATION_CODE_MAX_ATTEMPTS {
+        return Err(HttpError::too_many_requests("Too many incorrect attempts, please request a new code".to_string()));
+    }
+
+    if Utc::now() > code.expires_at {
+        return Err(invalid_code());
+    }
+
+    let code_hash = verification_code_hash(&user, body.code.trim());
+
+    if code_hash != code.code_hash {
+        app_state.db_client
+            .record_verification_code_attempt(user.id)
+            .await
+            .map_err(HttpError::database)?;
+        return Err(invalid_code());
+    }
+
+    let consumed = app_state.db_client
+        .consume_verification_code(user.id, &code_hash, VERIFICATION_CODE_MAX_ATTEMPTS)
+        .await
+        .map_err(HttpError::database)?;
+
+    if !consumed {
+        return Err(invalid_code());
+    }
+
+    record_event(&app_state, Some(user.id), AuditEventType::EmailVerified, &metadata, true, Some(&user.email)).await;
+
+    if let Err(e) = queue_welcome_email(&app_state.email_queue, user.org_id, &user.email, &user.name, &user.notification_preferences).await {
+        eprintln!("Failed to queue welcome email: {}", e);
+    }
+
+    login_response(&app_state, &user, &metadata).await
+}
+
+pub async fn resend_verification_code(
+    ClientIp(client_ip): ClientIp,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(Tenant(org_id)): Extension<Tenant>,
+    Json(body): Json<ResendVerificationCodeDto>
+) -> Result<impl IntoResponse, HttpError> {
+    if !app_state.env.email_verification_mode.sends_code() {
+        return Err(HttpError::not_found("Code verification is not enabled".to_string()));
+    }
+
+    let rate_limit_key = format!("resend-code:{}", client_ip);
+    if !app_state.rate_limiter.check(&rate_limit_key, 3, StdDuration::from_secs(600)).await {
+        return Err(HttpError::too_many_requests(ErrorMessage::TooManyRequests));
+    }
+
+    body.validate()
+        .map_err(HttpError::validation)?;
+
+    let user = app_state.db_client
+        .get_user_by_email(org_id, &body.email)
+        .await
+        .map_err(HttpError::database)?;
+
+    if let Some(user) = user.filter(|user| !user.verified) {
+        send_verification_code(&app_state, &user).await?;
+    }
+
+    Ok(Json(Response {
+        message: "If the account needs verification, a new code has been sent.".to_string(),
+        status: "success",
+    }))
+}
+
+pub async fn verify_secondary_email(
+    Query(query_params): Query<VerifyEmailQueryDto>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    metadata: RequestMetadata
+) -> Result<impl IntoResponse, HttpError> {
+    query_params.validate()
+        .map_err(HttpError::validation)?;
+
+    let result = app_state.db_client
+        .get_user_email_by_token(&token::hash_token(&query_params.token))
+        .await
+        .map_err(HttpError::database)?;
+
+    let email = result.ok_or(HttpError::unauthorized(ErrorMessage::InvalidToken))?;
+
+    if let Some(expires_at) = email.token_expires_at {
+        if Utc::now() > expires_at {
+            return Err(HttpError::bad_request(ErrorMessage::TokenExpired))?;
+        }
+    } else {
+        return Err(HttpError::bad_request("Invalid Verification Token".to_string()))?;
+    }
+
+    app_state.db_client
+        .verify_user_email(email.id)
+        .await
+        .map_err(HttpError::database)?;
+
+    record_event(&app_state, Some(email.user_id), AuditEventType::EmailVerified, &metadata, true, Some(&email.email)).await;
+
+    let response = Response {
+        message: format!("{} has been verified", email.email),
+        status: "success",
+    };
+
+    Ok(Json(response))
+}
+
+// The emailed link opens a confirmation page on the frontend which posts the
+// token here, so link scanners following the email cannot revert the change.
+pub async fn undo_email_change(
+    Extension(app_state): Extension<Arc<AppState>>,
+    metadata: RequestMetadata,
+    StrictJson(body): StrictJson<UndoEmailChangeDto>
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(HttpError::validation)?;
+
+    let result = app_state.db_client
+        .revert_email_change(&token::hash_token(&body.token))
+        .await
+        .map_err(HttpError::database)?;
+
+    let user = result.ok_or(HttpError::unauthorized(ErrorMessage::InvalidToken))?;
+
+    record_event(&app_state, Some(user.id), AuditEventType::EmailChangeReverted, &metadata, true, Some(&user.email)).await;
+
+    Ok(Json(Response {
+        message: "Your email change has been undone, please reset your password to secure the account".to_string(),
+        status: "success",
+    }))
+}
+
+pub async fn forgot_password(
+    ClientIp(client_ip): ClientIp,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(Tenant(org_id)): Extension<Tenant>,
+    metadata: RequestMetadata,
+    Json(body): Json<ForgotPasswordRequestDto>
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+       .map_err(HttpError::validation)?;
+
+    let message = match app_state.env.password_reset_mode {
+        PasswordResetMode::Link => "Password reset link has been sent to your email.",
+        PasswordResetMode::Code => "A password reset code has been sent to your email.",
+    };
+
+    // Unknown emails and requests over either limit get the same answer as one
+    // that sent an email, so the endpoint neither confirms accounts nor reveals
+    // that it stopped flooding someone's inbox.
+    let accepted = Json(EmailSentResponseDto {
+        message: message.to_string(),
+        status: "success",
+        dev_email: None,
+    });
+
+    let ip_limit = app_state.env.password_reset_limit_ip;
+    if !app_state.rate_limiter.check(&format!("reset-ip:{}", client_ip), ip_limit.max_requests, ip_limit.window()).await {
+        record_event(&app_state, None, AuditEventType::PasswordResetRequested, &metadata, false, Some("rate_limited")).await;
+        return Ok(accepted);
+    }
+
+    let user = app_state.db_client
+        .get_user_by_email(org_id, &body.email)
+        .await
+        .map_err(HttpError::database)?;
+
+    let Some(user) = user else {
+        return Ok(accepted);
+    };
+
+    let account_limit = app_state.env.password_reset_limit_account;
+    if !app_state.rate_limiter.check(&format!("reset-account:{}", user.id), account_limit.max_requests, account_limit.window()).await {
+        record_event(&app_state, Some(user.id), AuditEventType::PasswordResetRequested, &metadata, false, Some("rate_limited")).await;
+        return Ok(accepted);
+    }
+
+    let dev_email = match app_state.env.password_reset_mode {
+        PasswordResetMode::Link => DevEmailDto { link: Some(send_password_reset_link(&app_state, &user).await?), code: None },
+        PasswordResetMode::Code => DevEmailDto { link: None, code: Some(send_password_reset_code(&app_state, &user).await?) },
+    };
+
+    record_event(&app_state, Some(user.id), AuditEventType::PasswordResetRequested, &metadata, true, None).await;
+
+    let response = EmailSentResponseDto {
+        message: message.to_string(),
+        status: "success",
+        dev_email: app_state.env.expose_email_tokens.then_some(dev_email),
+    };
+
+    Ok(Json(response))
+}
+
+async fn send_password_reset_link(app_state: &AppState, user: &User) -> Result<String, HttpError> {
+    let verification_token = uuid::Uuid::new_v4().to_string();
+    let expires_at = Utc::now() + Duration::minutes(30);
+
+    app_state.db_client
+        .add_verified_token(user.id, &token::hash_token(&verification_token), expires_at)
+        .await
+        .map_err(HttpError::database)?;
+
+    let reset_link = format!("{}?token={}", app_state.env.frontend_link("/reset-password"), &verification_token);
+
+    let email_queued = queue_forget_password_email(&app_state.email_queue, user.org_id, &user.email, &reset_link, &user.name).await;
+
+    if let Err(e) = email_queued {
+        eprintln!("Failed to queue forgot password email: {}", e);
+        return Err(HttpError::server_error("Failed to send email".to_string()));
+    }
+
+    Ok(reset_link)
+}
+
+const SECURITY_QUESTION_CHALLENGE_MINUTES: i64 = 10;
+const SECURITY_QUESTION_RESET_MINUTES: i64 = 30;
+
+fn security_question_lockout_key(user_id: uuid::Uuid) -> String {
+    format!("security-questions:{}", user_id)
+}
+
+pub async fn start_security_question_recovery(
+    ClientIp(client_ip): ClientIp,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(Tenant(org_id)): Extension<Tenant>,
+    Json(body): Json<ForgotPasswordRequestDto>
+) -> Result<impl IntoResponse, HttpError> {
+    let Some(config) = &app_state.env.security_questions else {
+        return Err(HttpError::not_found(ErrorMessage::SecurityQuestionsDisabled));
+    };
+
+    body.validate()
+        .map_err(HttpError::validation)?;
+
+    let rate_limit_key = format!("security-questions-ip:{}", client_ip);
+    if !app_state.rate_limiter.check(&rate_limit_key, 10, StdDuration::from_secs(3600)).await {
+        return Err(HttpError::too_many_requests(ErrorMessage::TooManyRequests));
+    }
+
+    // Unknown emails, accounts without questions and locked accounts all get
+    // the same answer so the endpoint can't be used to probe accounts.
+    let unavailable = || HttpError::bad_request(ErrorMessage::SecurityQuestionsUnavailable);
+
+    let user = app_state.db_client
+        .get_user_by_email(org_id, &body.email)
+        .await
+        .map_err(HttpError::database)?
+        .ok_or_else(unavailable)?;
+
+    if app_state.rate_limiter.is_exhausted(&security_question_lockout_key(user.id), config.max_failures).await {
+        return Err(unavailable());
+    }
+
+    let questions = app_state.db_client
+        .get_security_questions(user.id)
+        .await
+        .map_err(HttpError::database)?;
+
+    if questions.len() < config.challenge {
+        return Err(unavailable());
+    }
+
+    let selected: Vec<_> = questions
+        .choose_multiple(&mut rand::thread_rng(), config.challenge)
+        .collect();
+
+    let challenge_token = token::sign_payload(
+        &user.id.to_string(),
+        token::SECURITY_QUESTIONS_PURPOSE,
+        token::SecurityQuestionClaims { qids: selected.iter().map(|question| question.id.to_string()).collect() },
+        &app_state.env.jwt_keys,
+        SECURITY_QUESTION_CHALLENGE_MINUTES,
+    ).map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    Ok(Json(SecurityQuestionChallengeResponseDto {
+        status: "success".to_string(),
+        challenge_token,
+        questions: selected.into_iter().map(SecurityQuestionDto::filter_question).collect(),
+    }))
+}
+
+pub async fn verify_security_question_recovery(
+    Extension(app_state): Extension<Arc<AppState>>,
+    metadata: RequestMetadata,
+    StrictJson(body): StrictJson<SecurityQuestionRecoveryDto>
+) -> Result<impl IntoResponse, HttpError> {
+    let Some(config) = &app_state.env.security_questions else {
+        return Err(HttpError::not_found(ErrorMessage::SecurityQuestionsDisabled));
+    };
+
+    body.validate()
+        .map_err(HttpError::validation)?;
+
+    let claims = token::verify_payload::<token::SecurityQuestionClaims>(&body.challenge_token, token::SECURITY_QUESTIONS_PURPOSE, &app_state.env.jwt_keys)?;
+
+    let user_id = uuid::Uuid::parse_str(&claims.sub)
+        .map_err(|_| HttpError::unauthorized(ErrorMessage::InvalidToken))?;
+
+    let lockout_key = security_question_lockout_key(user_id);
+    if app_state.rate_limiter.is_exhausted(&lockout_key, config.max_failures).await {
+        return Err(HttpError::too_many_requests(ErrorMessage::TooManyRequests));
+    }
+
+    let user = app_state.db_client
+        .get_user(Some(user_id), None, None, None)
+        .await
+        .map_err(HttpError::database)?
+        .ok_or(HttpError::unauthorized(ErrorMessage::UserNoLongerExist))?;
+
+    let questions = app_state.db_client
+        .get_security_questions(user.id)
+        .await
+        .map_err(HttpError::database)?;
+
+    // Every answer is checked even after a miss, the response only ever says
+    // the set was wrong, never which answer.
+    let mut correct = body.answers.len() == claims.data.qids.len();
+    for question_id in &claims.data.qids {
+        let question = questions.iter().find(|question| question.id.to_string() == *question_id);
+        let answer = body.answers.iter().find(|answer| answer.id == *question_id);
+
+        let matched = match (question, answer) {
+            (Some(question), Some(answer)) => {
+                let pepper = app_state.env
+                    .pepper_for(question.answer_pepper_id.as_deref())
+                    .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+                password::compare(&password::normalize_answer(&answer.answer), &question.answer_hash, pepper)
+                    .unwrap_or(false)
+            }
+            _ => false,
+        };
+
+        correct &= matched;
+    }
+
+    if !correct {
+        app_state.rate_limiter.record(&lockout_key, config.lockout()).await;
+        record_event(&app_state, Some(user.id), AuditEventType::SecurityQuestionsRecovery, &metadata, false, None).await;
+        return Err(HttpError::bad_request(ErrorMessage::SecurityQuestionsIncorrect));
+    }
+
+    app_state.rate_limiter.reset(&lockout_key).await;
+
+    let reset_token = uuid::Uuid::new_v4().to_string();
+    let expires_at = Utc::now() + Duration::minutes(SECURITY_QUESTION_RESET_MINUTES);
+
+    app_state.db_client
+        .add_verified_token(user.id, &token::hash_token(&reset_token), expires_at)
+        .await
+        .map_err(HttpError::database)?;
+
+    record_event(&app_state, Some(user.id), AuditEventType::SecurityQuestionsRecovery, &metadata, true, None).await;
+
+    Ok(Json(SecurityQuestionRecoveryResponseDto {
+        status: "success".to_string(),
+        reset_token,
+        expires_at,
+    }))
+}
+
+const PASSWORD_RESET_CODE_TTL_MINUTES: i64 = 10;
+const PASSWORD_RESET_CODE_MAX_ATTEMPTS: i32 = 5;
+
+async fn send_password_reset_code(app_state: &AppState, user: &User) -> Result<String, HttpError> {
+    let code = token::generate_numeric_code();
+    let expires_at = Utc::now() + Duration::minutes(PASSWORD_RESET_CODE_TTL_MINUTES);
+
+    app_state.db_client
+        .save_password_reset_code(user.id, &verification_code_hash(user, &code), expires_at)
+        .await
+        .map_err(HttpError::database)?;
+
+    let email_queued = queue_password_reset_code_email(&app_state.email_queue, user.org_id, &user.email, &user.name, &code, PASSWORD_RESET_CODE_TTL_MINUTES).await;
+
+    if let Err(e) = email_queued {
+        eprintln!("Failed to queue password reset code email: {}", e);
+        return Err(HttpError::server_error("Failed to send email".to_string()));
+    }
+
+    Ok(code)
+}
+
+pub async fn reset_password(
+    Extension(app_state): Extension<Arc<AppState>>,
+    metadata: RequestMetadata,
+    StrictJson(body): StrictJson<ResetPasswordRequestDto>
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(HttpError::validation)?;
+
+    let token_hash = token::hash_token(&body.token);
+
+    let result = app_state.db_client
+        .get_user(None, None, None, Some(&token_hash))
+        .await
+        .map_err(HttpError::database)?;
+
+    let user = result.ok_or(HttpError::bad_request("Invalid or expired token".to_string()))?;
+
+    if let Some(expires_at) = user.token_expires_at {
+        if Utc::now() > expires_at {
+            return Err(HttpError::bad_request(ErrorMessage::TokenExpired))?;
+        }
+    }else {
+        return Err(HttpError::bad_request("Invalid verification token".to_string()))?;
+    }
+
+    app_state.env.password_policy
+        .check(&body.new_password, &user.name, &user.email)
+        .map_err(HttpError::bad_request)?;
+
+    let user_id = uuid::Uuid::parse_str(&user.id.to_string()).unwrap();
+
+    let hash_password = password::hash(&body.new_password, app_state.env.password_pepper.as_ref())
+            .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let consumed = app_state.db_client
+        .verified_token(&token_hash)
+        .await
+        .map_err(HttpError::database)?;
+
+    if !consumed {
+        return Err(HttpError::bad_request("Invalid or expired token".to_string()));
+    }
+
+    app_state.db_client
+        .update_user_password(user_id, hash_password, app_state.env.current_pepper_id())
+        .await
+        .map_err(HttpError::database)?;
+
+    record_event(&app_state, Some(user_id), AuditEventType::PasswordReset, &metadata, true, None).await;
+
+    let response = Response {
+        message: "Password has been successfully reset.".to_string(),
+        status: "success",
+    };
+
+    Ok(Json(response))
+}
+
+pub async fn reset_password_with_code(
+    ClientIp(client_ip): ClientIp,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(Tenant(org_id)): Extension<Tenant>,
+    metadata: RequestMetadata,
+    StrictJson(body): StrictJson<ResetPasswordWithCodeDto>
+) -> Result<impl IntoResponse, HttpError> {
+    if app_state.env.password_reset_mode != PasswordResetMode::Code {
+        return Err(HttpError::not_found("Code password reset is not enabled".to_string()));
+    }
+
+    let rate_limit_key = format!("reset-code:{}", client_ip);
+    if !app_state.rate_limiter.check(&rate_limit_key, app_state.env.reset_verify_rate_limit, StdDuration::from_secs(60)).await {
+        return Err(HttpError::too_many_requests(ErrorMessage::TooManyRequests));
+    }
+
+    body.validate()
+        .map_err(HttpError::validation)?;
+
+    let invalid_code = || HttpError::bad_request("Invalid or expired reset code".to_string());
+
+    let user = app_state.db_client
+        .get_user_by_email(org_id, &body.email)
+        .await
+        .map_err(HttpError::database)?
+        .ok_or_else(invalid_code)?;
+
+    let code = app_state.db_client
+        .get_password_reset_code(user.id)
+        .await
+        .map_err(HttpError::database)?
+        .ok_or_else(invalid_code)?;
+
+    if code.attempts >= PASSWORD_RESET_CODE_MAX_ATTEMPTS {
+        return Err(HttpError::too_many_requests("Too many incorrect attempts, please request a new code".to_string()));
+    }
+
+    if Utc::now() > code.expires_at {
+        return Err(invalid_code());
+    }
+
+    let code_hash = verification_code_hash(&user, body.code.trim());
+
+    if code_hash != code.code_hash {
+        app_state.db_client
+            .record_password_reset_code_attempt(user.id)
+            .await
+            .map_err(HttpError::database)?;
+        return Err(invalid_code());
+    }
+
+    app_state.env.password_policy
+        .check(&body.new_password, &user.name, &user.email)
+        .map_err(HttpError::bad_request)?;
+
+    let hash_password = password::hash(&body.new_password, app_state.env.password_pepper.as_ref())
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let consumed = app_state.db_client
+        .consume_password_reset_code(user.id, &code_hash, PASSWORD_RESET_CODE_MAX_ATTEMPTS)
+        .await
+        .map_err(HttpError::database)?;
+
+    if !consumed {
+        return Err(invalid_code());
+    }
+
+    app_state.db_client
+        .update_user_password(user.id, hash_password, app_state.env.current_pepper_id())
+        .await
+        .map_err(HttpError::database)?;
+
+    record_event(&app_state, Some(user.id), AuditEventType::PasswordReset, &metadata, true, Some("code")).await;
+
+    let response = Response {
+        message: "Password has been successfully reset.".to_string(),
+        status: "success",
+    };
+
+    Ok(Json(response))
+}
+
+pub async fn verify_reset_token(
+    ClientIp(client_ip): ClientIp,
+    Query(query_params): Query<ResetTokenQueryDto>,
+    Extension(app_state): Extension<Arc<AppState>>
+) -> Result<impl IntoResponse, HttpError> {
+    let rate_limit_key = format!("reset-verify:{}", client_ip);
+    if !app_state.rate_limiter.check(&rate_limit_key, app_state.env.reset_verify_rate_limit, StdDuration::from_secs(60)).await {
+        return Err(HttpError::too_many_requests(ErrorMessage::TooManyRequests));
+    }
+
+    query_params.validate()
+        .map_err(HttpError::validation)?;
+
+    let result = app_state.db_client
+        .get_user(None, None, None, Some(&token::hash_token(&query_params.token)))
+        .await
+        .map_err(HttpError::database)?;
+
+    let valid = result
+        .and_then(|user| user.token_expires_at)
+        .map(|expires_at| Utc::now() <= expires_at)
+        .unwrap_or(false);
+
+    Ok(Json(ResetTokenStatusDto { valid }))
+}
+
+pub async fn verify_signed_summary(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Json(body): Json<VerifySummaryDto>
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(HttpError::validation)?;
+
+    let claims = token::verify_payload::<AccountSummaryDto>(
+        &body.summary,
+        token::ACCOUNT_SUMMARY_PURPOSE,
+        &app_state.env.jwt_keys
+    )?;
+
+    let timestamp = |seconds: usize| DateTime::from_timestamp(seconds as i64, 0).unwrap_or_default();
+
+    Ok(Json(VerifiedSummaryResponseDto {
+        status: "success".to_string(),
+        issued_at: timestamp(claims.iat),
+        expires_at: timestamp(claims.exp),
+        data: claims.data,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Method;
+    use serde_json::json;
+    use sqlx::{Pool, Postgres};
+
+    use super::*;
+    use crate::{config::LoginThrottle, db::UserEmailExt, models::Organization, test_support};
+
+    async fn ignore_case_app(pool: Pool<Postgres>) -> (Arc<AppState>, Router) {
+        let mut config = test_support::config();
+        config.email_ignore_case = true;
+
+        let app_state = test_support::app_state(pool, config).await;
+        let app = test_support::router(&app_state);
+        (app_state, app)
+    }
+
+    async fn attempt_login(app: &Router, email: &str, password: &str) -> (StatusCode, serde_json::Value) {
+        test_support::send(app, test_support::json_request(Method::POST, "/api/auth/login", json!({
+            "email": email,
+            "password": password,
+        }))).await
+    }
+
+    #[test]
+    fn registration_day_key_rolls_over_at_utc_midnight() {
+        let client_ip: IpAddr = "203.0.113.10".parse().unwrap();
+        let before = DateTime::parse_from_rfc3339("2024-11-30T23:59:59Z").unwrap().to_utc();
+        let after = DateTime::parse_from_rfc3339("2024-12-01T00:00:00Z").unwrap().to_utc();
+
+        let (key_before, window_before) = registration_day_key(client_ip, before);
+        let (key_after, window_after) = registration_day_key(client_ip, after);
+
+        assert_eq!(key_before, "registrations:203.0.113.10:2024-11-30");
+        assert_eq!(window_before, StdDuration::from_secs(1));
+        assert_eq!(key_after, "registrations:203.0.113.10:2024-12-01");
+        assert_eq!(window_after, StdDuration::from_secs(24 * 60 * 60));
+    }
+
+    #[sqlx::test]
+    async fn colliding_emails_are_rejected_as_ambiguous(pool: Pool<Postgres>) {
+        test_support::block_on(async {
+            let (app_state, app) = ignore_case_app(pool).await;
+            test_support::create_user(&app_state, Organization::DEFAULT_ID, "Casey@example.com", UserRole::User).await;
+            test_support::create_user(&app_state, Organization::DEFAULT_ID, "casey@example.com", UserRole::User).await;
+
+            let (status, body) = attempt_login(&app, "CASEY@example.com", test_support::PASSWORD).await;
+
+            assert_eq!(status, StatusCode::CONFLICT);
+            assert_eq!(body["code"], "AMBIGUOUS_LOGIN_EMAIL");
+        });
+    }
+
+    #[sqlx::test]
+    async fn exact_email_wins_over_a_collision(pool: Pool<Postgres>) {
+        test_support::block_on(async {
+            let (app_state, app) = ignore_case_app(pool).await;
+            test_support::create_user(&app_state, Organization::DEFAULT_ID, "Casey@example.com", UserRole::User).await;
+            test_support::create_user(&app_state, Organization::DEFAULT_ID, "casey@example.com", UserRole::User).await;
+
+            let (status, _) = attempt_login(&app, "Casey@example.com", test_support::PASSWORD).await;
+
+            assert_eq!(status, StatusCode::OK);
+        });
+    }
+
+    #[sqlx::test]
+    async fn unique_case_insensitive_match_logs_in(pool: Pool<Postgres>) {
+        test_support::block_on(async {
+            let (app_state, app) = ignore_case_app(pool).await;
+            test_support::create_user(&app_state, Organization::DEFAULT_ID, "drew@example.com", UserRole::User).await;
+
+            let (status, _) = attempt_login(&app, "DREW@example.com", test_support::PASSWORD).await;
+
+            assert_eq!(status, StatusCode::OK);
+        });
+    }
+
+    // Only the throttle under test can trip, the per IP, email and pair
+    // counters are raised out of the way.
+    async fn throttled_app(pool: Pool<Postgres>, known: u32, unknown: u32) -> (Arc<AppState>, Router) {
+        let mut config = test_support::config();
+        config.login_throttle_known = LoginThrottle { max_failures: known, window_seconds: 300 };
+        config.login_throttle_unknown = LoginThrottle { max_failures: unknown, window_seconds: 300 };
+        config.login_throttle_ip = LoginThrottle { max_failures: 100, window_seconds: 300 };
+        config.login_throttle_email = LoginThrottle { max_failures: 100, window_seconds: 300 };
+        config.login_throttle_pair = LoginThrottle { max_failures: 100, window_seconds: 300 };
+
+        let app_state = test_support::app_state(pool, config).await;
+        let app = test_support::router(&app_state);
+        (app_state, app)
+    }
+
+    #[sqlx::test]
+    async fn known_account_is_throttled_after_wrong_passwords(pool: Pool<Postgres>) {
+        test_support::block_on(async {
+            let (app_state, app) = throttled_app(pool, 2, 100).await;
+            test_support::create_user(&app_state, Organization::DEFAULT_ID, "robin@example.com", UserRole::User).await;
+
+            for _ in 0..2 {
+                let (status, _) = attempt_login(&app, "robin@example.com", "wrong password").await;
+                assert_eq!(status, StatusCode::BAD_REQUEST);
+            }
+
+            let (status, _) = attempt_login(&app, "robin@example.com", test_support::PASSWORD).await;
+            assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        });
+    }
+
+    #[sqlx::test]
+    async fn unknown_emails_throttle_the_ip(pool: Pool<Postgres>) {
+        test_support::block_on(async {
+            let (app_state, app) = throttled_app(pool, 100, 2).await;
+            test_support::create_user(&app_state, Organization::DEFAULT_ID, "robin@example.com", UserRole::User).await;
+
+            for email in ["nobody@example.com", "nobody-else@example.com"] {
+                let (status, _) = attempt_login(&app, email, test_support::PASSWORD).await;
+                assert_eq!(status, StatusCode::BAD_REQUEST);
+            }
+
+            let (status, _) = attempt_login(&app, "robin@example.com", test_support::PASSWORD).await;
+            assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        });
+    }
+
+    #[sqlx::test]
+    async fn successful_login_clears_the_known_account_counter(pool: Pool<Postgres>) {
+        test_support::block_on(async {
+            let (app_state, app) = throttled_app(pool, 2, 100).await;
+            test_support::create_user(&app_state, Organization::DEFAULT_ID, "robin@example.com", UserRole::User).await;
+
+            let (status, _) = attempt_login(&app, "robin@example.com", "wrong password").await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+
+            let (status, _) = attempt_login(&app, "robin@example.com", test_support::PASSWORD).await;
+            assert_eq!(status, StatusCode::OK);
+
+            let (status, _) = attempt_login(&app, "robin@example.com", "wrong password").await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+
+            let (status, _) = attempt_login(&app, "robin@example.com", test_support::PASSWORD).await;
+            assert_eq!(status, StatusCode::OK);
+        });
+    }
+
+    #[sqlx::test]
+    async fn email_change_is_only_undone_by_posting_the_token(pool: Pool<Postgres>) {
+        test_support::block_on(async {
+            let app_state = test_support::app_state(pool, test_support::config()).await;
+            let app = test_support::router(&app_state);
+            let user = test_support::create_user(&app_state, Organization::DEFAULT_ID, "new@example.com", UserRole::User).await;
+
+            let undo_token = "undo-token";
+            app_state.db_client
+                .save_email_change_revert(user.id, "old@example.com", "new@example.com", &token::hash_token(undo_token), Utc::now() + Duration::hours(1))
+                .await
+                .unwrap();
+
+            let scanner = axum::http::Request::builder()
+                .uri(format!("/api/auth/email-change/undo?token={}", undo_token))
+                .body(axum::body::Body::empty())
+                .unwrap();
+            let (status, _) = test_support::send(&app, scanner).await;
+            assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+
+            let undo = || test_support::json_request(Method::POST, "/api/auth/email-change/undo", json!({ "token": undo_token }));
+
+            let (status, _) = test_support::send(&app, undo()).await;
+            assert_eq!(status, StatusCode::OK);
+
+            let reverted = app_state.db_client.get_user(Some(user.id), None, None, None).await.unwrap().unwrap();
+            assert_eq!(reverted.email, "old@example.com");
+
+            let (status, _) = test_support::send(&app, undo()).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+        });
+    }
 }
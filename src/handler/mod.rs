@@ -1,2 +1,5 @@
+pub mod admin;
+pub mod audit;
 pub mod auth;
 pub mod users;
+pub mod validate;
@@ -1,18 +1,81 @@
-use axum::{extract::Query, middleware, response::IntoResponse, routing::{get, put}, Extension, Json, Router};
+use axum::{body::Body, extract::{Path, Query}, handler::Handler, http::{header, HeaderMap, StatusCode}, middleware, response::IntoResponse, routing::{delete, get, post, put}, Extension, Json, Router};
+use chrono::{Duration, Utc};
+use futures_util::stream::{self, StreamExt};
 use validator::Validate;
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc, time::Duration as StdDuration};
 
-use crate::{db::UserExt, dtos::{FilterUserDto, NameUpdateDto, RequestQueryDto, Response, RoleUpdateDto, UserData, UserListResponseDto, UserPasswordUpdateDto, UserResponseDto}, error::{ErrorMessage, HttpError}, middleware::{role_check, JWTAuthMiddleware}, models::UserRole, utils::password, AppState};
+use crate::{config::AuthMethod, db::{ApiKeyExt, AuditExt, SecurityQuestionExt, SessionExt, TrustedDeviceExt, TwoFactorExt, UserEmailExt, UserExt, AUDIT_FILTER_FIELDS, AUDIT_SORT_FIELDS, USER_FILTER_FIELDS, USER_SORT_FIELDS}, dtos::{AcceptTermsDto, AccountSummaryDto, AddEmailDto, ApiKeyCreatedResponseDto, ApiKeyDto, ApiKeyListResponseDto, ApiKeyRotatedResponseDto, BulkRoleResponseDto, BulkRoleResultDto, BulkRoleUpdateDto, CreateApiKeyDto, FieldsQueryDto, FilterUserDto, FilterUserEmailDto, FreezeUserDto, ImpersonationResponseDto, LoginHistoryEntryDto, MergeUsersDto, NameUpdateDto, NotificationPreferencesResponseDto, NotificationPreferencesUpdateDto, Paginated, ProfileDto, ProfileUpdateDto, RecoveryCodesResponseDto, RejectUserDto, RequestQueryDto, Response, RoleUpdateDto, SecurityQuestionDto, SecurityQuestionListResponseDto, SessionDto, SessionFilterQueryDto, SessionLimitUpdateDto, SessionListResponseDto, SetSecurityQuestionsDto, SignedSummaryResponseDto, StatusUpdateDto, TermsStatusDto, TermsStatusResponseDto, TerminateSessionsResponseDto, TrustedDeviceDto, TwoFactorCodeDto, TwoFactorSetupDto, TwoFactorSetupResponseDto, UserData, UserEmailListResponseDto, UserEmailResponseDto, UserListQueryDto, UserViewDto, UserViewResponseDto, UserPasswordUpdateDto, UserResponseDto, UserSecurityDto, UserSecurityResponseDto, UserStatsDto, UserStatsResponseDto}, error::{ErrorMessage, HttpError}, handler::{audit::record_event, auth::email_taken_ignore_case}, mail::mails::{queue_account_approved_email, queue_email_changed_email, queue_secondary_email_verification_email}, middleware::{auth_method_check, elevation_check, role_check, terms_check, verified_check, JWTAuthMiddleware, RequestMetadata, StrictJson}, models::{AccountStatus, AuditEventType, User, UserRole}, utils::{password, query::{FilterOp, FilterValue, ListQuery}, token, totp}, AppState};
 
 pub fn users_handler() -> Router {
     Router::new()
         .route(
             "/me", 
             get(get_me)
+            .patch(update_profile)
             .layer(middleware::from_fn(|state, req, next| {
                 role_check(state, req, next, vec![UserRole::Admin, UserRole::User])
             }))
     )
+    .route("/me/security", get(get_me_security))
+    .route(
+        "/me/notifications",
+        get(get_notification_preferences)
+        .put(update_notification_preferences)
+    )
+    .route(
+        "/me/security-questions",
+        get(get_security_questions)
+        .put(set_security_questions.layer(middleware::from_fn(elevation_check)))
+    )
+    .route("/me/terms", get(get_terms_status).post(accept_terms))
+    .route(
+        "/me/signed-summary",
+        get(get_signed_summary)
+        .layer(middleware::from_fn(terms_check))
+        .layer(middleware::from_fn(verified_check))
+    )
+    .route("/me/sessions", get(get_my_sessions))
+    .route("/me/login-history", get(get_my_login_history))
+    .route(
+        "/me/api-keys",
+        get(get_my_api_keys)
+        .post(create_api_key)
+        .layer(middleware::from_fn(elevation_check))
+        .layer(middleware::from_fn(|state, req, next| {
+            auth_method_check(state, req, next, AuthMethod::ApiKey)
+        }))
+    )
+    .route(
+        "/me/api-keys/:key_id",
+        delete(revoke_api_key)
+        .layer(middleware::from_fn(|state, req, next| {
+            auth_method_check(state, req, next, AuthMethod::ApiKey)
+        }))
+    )
+    .route(
+        "/me/api-keys/:key_id/rotate",
+        post(rotate_api_key)
+        .layer(middleware::from_fn(elevation_check))
+        .layer(middleware::from_fn(|state, req, next| {
+            auth_method_check(state, req, next, AuthMethod::ApiKey)
+        }))
+    )
+    .route("/me/trusted-devices", delete(revoke_trusted_devices))
+    .route("/me/trusted-devices/:device_id", delete(revoke_trusted_device))
+    .route("/me/impersonation/end", post(end_impersonation))
+    .route(
+        "/me/2fa/setup",
+        post(setup_two_factor)
+        .layer(middleware::from_fn(terms_check))
+        .layer(middleware::from_fn(verified_check))
+    )
+    .route(
+        "/me/2fa/enable",
+        post(enable_two_factor)
+        .layer(middleware::from_fn(terms_check))
+        .layer(middleware::from_fn(verified_check))
+    )
+    .route("/me/2fa/recovery-codes", post(regenerate_recovery_codes))
     .route(
         "/users", 
         get(get_users)
@@ -20,70 +83,1180 @@ pub fn users_handler() -> Router {
             role_check(state, req, next, vec![UserRole::Admin])
         }))
     )
+    .route(
+        "/by-role/:role",
+        get(get_users_by_role)
+        .layer(middleware::from_fn(|state, req, next| {
+            role_check(state, req, next, vec![UserRole::Admin])
+        }))
+    )
+    .route(
+        "/stats",
+        get(get_user_stats)
+        .layer(middleware::from_fn(|state, req, next| {
+            role_check(state, req, next, vec![UserRole::Admin])
+        }))
+    )
+    .route("/users/:user_id", get(get_user))
+    .route(
+        "/users/:user_id/status",
+        put(update_user_status)
+        .layer(middleware::from_fn(|state, req, next| {
+            role_check(state, req, next, vec![UserRole::Admin])
+        }))
+    )
+    .route(
+        "/users/:user_id/freeze",
+        post(freeze_user)
+        .delete(unfreeze_user)
+        .layer(middleware::from_fn(|state, req, next| {
+            role_check(state, req, next, vec![UserRole::Admin])
+        }))
+    )
+    .route(
+        "/users/:user_id/approve",
+        post(approve_user)
+        .layer(middleware::from_fn(|state, req, next| {
+            role_check(state, req, next, vec![UserRole::Admin])
+        }))
+    )
+    .route(
+        "/users/:user_id/reject",
+        post(reject_user)
+        .layer(middleware::from_fn(|state, req, next| {
+            role_check(state, req, next, vec![UserRole::Admin])
+        }))
+    )
+    .route(
+        "/users/:user_id/session-limit",
+        put(update_user_session_limit)
+        .layer(middleware::from_fn(|state, req, next| {
+            role_check(state, req, next, vec![UserRole::Admin])
+        }))
+    )
+    .route(
+        "/users/:user_id/sessions/terminate-all",
+        post(terminate_user_sessions)
+        .layer(middleware::from_fn(|state, req, next| {
+            role_check(state, req, next, vec![UserRole::Admin])
+        }))
+    )
+    .route(
+        "/users/:user_id/impersonate",
+        post(impersonate_user)
+        .layer(middleware::from_fn(|state, req, next| {
+            role_check(state, req, next, vec![UserRole::Admin])
+        }))
+    )
+    .route(
+        "/merge",
+        post(merge_users)
+        .layer(middleware::from_fn(|state, req, next| {
+            role_check(state, req, next, vec![UserRole::Admin])
+        }))
+    )
     .route("/name", put(update_user_name))
-    .route("/role", put(update_user_role))
-    .route("/password", put(update_user_password))
+    .route(
+        "/role",
+        put(update_user_role)
+        .layer(middleware::from_fn(|state, req, next| {
+            role_check(state, req, next, vec![UserRole::Admin])
+        }))
+    )
+    .route(
+        "/roles/bulk",
+        post(bulk_update_user_role)
+        .layer(middleware::from_fn(|state, req, next| {
+            role_check(state, req, next, vec![UserRole::Admin])
+        }))
+    )
+    .route(
+        "/password",
+        put(update_user_password)
+        .layer(middleware::from_fn(|state, req, next| {
+            auth_method_check(state, req, next, AuthMethod::Password)
+        }))
+    )
+    .route(
+        "/emails",
+        get(get_user_emails)
+        .post(add_user_email.layer(middleware::from_fn(terms_check)).layer(middleware::from_fn(verified_check)))
+    )
+    .route("/emails/:email_id", delete(remove_user_email))
+    .route("/emails/:email_id/primary", put(set_primary_email).layer(middleware::from_fn(elevation_check)))
+}
+
+pub async fn get_me(
+    Query(fields): Query<FieldsQueryDto>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>
+) -> Result<axum::response::Response, HttpError> {
+    let selection = fields.selection()
+        .map_err(HttpError::bad_request)?;
+
+    let filtered_user = FilterUserDto::filter_user(&user.user);
+
+    if let Some(selection) = selection {
+        return Ok(Json(UserResponseDto {
+            status: "success".to_string(),
+            data: UserData {
+                user: filtered_user.project(&selection),
+            }
+        }).into_response());
+    }
+
+    let response_data = UserResponseDto {
+        status: "success".to_string(),
+        data: UserData {
+            user: ProfileDto {
+                user: filtered_user,
+                notification_preferences: *user.user.notification_preferences,
+                terms_acceptance_required: user.user.terms_acceptance_required(app_state.env.terms_version.as_deref()),
+            },
+        }
+    };
+
+    Ok(Json(response_data).into_response())
+}
+
+pub async fn get_notification_preferences(
+    Extension(user): Extension<JWTAuthMiddleware>
+) -> Result<impl IntoResponse, HttpError> {
+    Ok(Json(NotificationPreferencesResponseDto {
+        status: "success".to_string(),
+        notification_preferences: *user.user.notification_preferences,
+    }))
+}
+
+pub async fn update_notification_preferences(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    StrictJson(body): StrictJson<NotificationPreferencesUpdateDto>
+) -> Result<impl IntoResponse, HttpError> {
+    let preferences = body.apply(*user.user.notification_preferences);
+
+    let updated_user = app_state.db_client
+        .update_notification_preferences(user.user.id, preferences)
+        .await
+        .map_err(HttpError::database)?;
+
+    Ok(Json(NotificationPreferencesResponseDto {
+        status: "success".to_string(),
+        notification_preferences: *updated_user.notification_preferences,
+    }))
+}
+
+pub async fn get_terms_status(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>
+) -> Result<impl IntoResponse, HttpError> {
+    Ok(Json(TermsStatusResponseDto {
+        status: "success".to_string(),
+        data: TermsStatusDto::from_user(&user.user, app_state.env.terms_version.as_deref()),
+    }))
+}
+
+pub async fn accept_terms(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata,
+    StrictJson(body): StrictJson<AcceptTermsDto>
+) -> Result<impl IntoResponse, HttpError> {
+    let Some(current) = app_state.env.terms_version.as_deref() else {
+        return Err(HttpError::not_found(ErrorMessage::TermsDisabled));
+    };
+
+    body.validate()
+        .map_err(HttpError::validation)?;
+
+    if body.version != current {
+        return Err(HttpError::bad_request(ErrorMessage::TermsVersionMismatch(current.to_string())));
+    }
+
+    let updated_user = app_state.db_client
+        .accept_terms(user.user.id, current)
+        .await
+        .map_err(HttpError::database)?;
+
+    let details = format!("version={}", current);
+    record_event(&app_state, Some(user.user.id), AuditEventType::TermsAccepted, &metadata, true, Some(&details)).await;
+
+    Ok(Json(TermsStatusResponseDto {
+        status: "success".to_string(),
+        data: TermsStatusDto::from_user(&updated_user, Some(current)),
+    }))
+}
+
+pub async fn get_security_questions(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>
+) -> Result<impl IntoResponse, HttpError> {
+    if app_state.env.security_questions.is_none() {
+        return Err(HttpError::not_found(ErrorMessage::SecurityQuestionsDisabled));
+    }
+
+    let questions = app_state.db_client
+        .get_security_questions(user.user.id)
+        .await
+        .map_err(HttpError::database)?;
+
+    Ok(Json(SecurityQuestionListResponseDto {
+        status: "success".to_string(),
+        questions: SecurityQuestionDto::filter_questions(&questions),
+    }))
+}
+
+pub async fn set_security_questions(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata,
+    StrictJson(body): StrictJson<SetSecurityQuestionsDto>
+) -> Result<impl IntoResponse, HttpError> {
+    let Some(config) = &app_state.env.security_questions else {
+        return Err(HttpError::not_found(ErrorMessage::SecurityQuestionsDisabled));
+    };
+
+    body.validate()
+        .map_err(HttpError::validation)?;
+
+    if body.questions.len() != config.required {
+        return Err(HttpError::bad_request(ErrorMessage::SecurityQuestionsCount(config.required)));
+    }
+
+    let mut seen = HashSet::new();
+    if !body.questions.iter().all(|entry| seen.insert(password::normalize_answer(&entry.question))) {
+        return Err(HttpError::bad_request(ErrorMessage::SecurityQuestionsDuplicate));
+    }
+
+    let pepper = app_state.env.password_pepper.as_ref();
+    let mut questions = Vec::with_capacity(body.questions.len());
+    let mut answer_hashes = Vec::with_capacity(body.questions.len());
+
+    for entry in &body.questions {
+        let answer = password::normalize_answer(&entry.answer);
+        let answer_hash = password::hash(answer, pepper)
+            .map_err(HttpError::bad_request)?;
+
+        questions.push(entry.question.trim().to_string());
+        answer_hashes.push(answer_hash);
+    }
+
+    app_state.db_client
+        .replace_security_questions(user.user.id, &questions, &answer_hashes, app_state.env.current_pepper_id())
+        .await
+        .map_err(HttpError::database)?;
+
+    let saved = app_state.db_client
+        .get_security_questions(user.user.id)
+        .await
+        .map_err(HttpError::database)?;
+
+    record_event(&app_state, Some(user.user.id), AuditEventType::SecurityQuestionsUpdated, &metadata, true, None).await;
+
+    Ok(Json(SecurityQuestionListResponseDto {
+        status: "success".to_string(),
+        questions: SecurityQuestionDto::filter_questions(&saved),
+    }))
+}
+
+pub async fn get_me_security(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>
+) -> Result<impl IntoResponse, HttpError> {
+    let user = &user.user;
+    let max_age_days = app_state.env.password_max_age_days;
+
+    let recovery_codes_remaining = app_state.db_client
+        .get_recovery_code_count(user.id)
+        .await
+        .map_err(HttpError::database)?;
+
+    let trusted_devices = app_state.db_client
+        .get_trusted_devices(user.id)
+        .await
+        .map_err(HttpError::database)?;
+
+    let response = UserSecurityResponseDto {
+        status: "success".to_string(),
+        data: UserSecurityDto {
+            password_changed_at: user.password_changed_at,
+            password_expires_at: user.password_expires_at(max_age_days),
+            must_change_password: user.password_expired(max_age_days),
+            two_factor_enabled: user.totp_enabled,
+            recovery_codes_remaining,
+            verified: user.verified,
+            verification_deadline: user.verification_deadline(app_state.env.verification_grace_days),
+            trusted_devices: TrustedDeviceDto::filter_devices(&trusted_devices),
+        },
+    };
+
+    Ok(Json(response))
 }
 
-pub async fn get_me(
-    Extension(_app_state): Extension<Arc<AppState>>,
-    Extension(user): Extension<JWTAuthMiddleware>
-) -> Result<impl IntoResponse, HttpError> {
-    let filtered_user = FilterUserDto::filter_user(&user.user);
-    let response_data = UserResponseDto {
-        status: "success".to_string(),
+pub async fn get_signed_summary(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>
+) -> Result<impl IntoResponse, HttpError> {
+    let expires_in_minutes = 10;
+    let summary = AccountSummaryDto::from_filtered(FilterUserDto::filter_user(&user.user));
+
+    let signed = token::sign_payload(
+        &user.user.id.to_string(),
+        token::ACCOUNT_SUMMARY_PURPOSE,
+        summary,
+        &app_state.env.jwt_keys,
+        expires_in_minutes
+    ).map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let response = SignedSummaryResponseDto {
+        status: "success".to_string(),
+        summary: signed,
+        expires_at: Utc::now() + Duration::minutes(expires_in_minutes),
+    };
+
+    Ok(Json(response))
+}
+
+pub async fn get_my_login_history(
+    Query(query_params): Query<RequestQueryDto>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>
+) -> Result<impl IntoResponse, HttpError> {
+    query_params.validate()
+        .map_err(HttpError::validation)?;
+
+    let login_events = AuditEventType::LOGIN_EVENTS
+        .iter()
+        .map(|event_type| event_type.to_str().to_string())
+        .collect();
+
+    let query = ListQuery::new(AUDIT_FILTER_FIELDS, AUDIT_SORT_FIELDS, "created_at")
+        .filter("user_id", FilterOp::Eq, Some(FilterValue::Uuid(user.user.id)))
+        .and_then(|query| query.filter("event_type", FilterOp::In, Some(FilterValue::TextList(login_events))))
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .paginate(query_params.page(), query_params.limit());
+
+    let logs = app_state.db_client
+        .get_audit_logs(user.user.org_id, &query)
+        .await
+        .map_err(HttpError::database)?;
+
+    let log_count = app_state.db_client
+        .get_audit_log_count(user.user.org_id, &query)
+        .await
+        .map_err(HttpError::database)?;
+
+    let masking = app_state.env.login_history_ip_masking;
+    let entries = logs
+        .iter()
+        .map(|log| LoginHistoryEntryDto::filter_entry(log, masking))
+        .collect();
+
+    Ok(Json(Paginated::new(entries, &query_params, log_count)))
+}
+
+pub async fn get_my_sessions(
+    Query(query_params): Query<RequestQueryDto>,
+    Query(filter): Query<SessionFilterQueryDto>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>
+) -> Result<impl IntoResponse, HttpError> {
+    query_params.validate()
+        .map_err(HttpError::validation)?;
+
+    let active_only = filter.active_only.unwrap_or(false);
+
+    let sessions = app_state.db_client
+        .get_user_sessions(user.user.id, active_only, query_params.page() as u32, query_params.limit())
+        .await
+        .map_err(HttpError::database)?;
+
+    let session_count = app_state.db_client
+        .get_user_session_count(user.user.id, active_only)
+        .await
+        .map_err(HttpError::database)?;
+
+    let active_sessions = if active_only {
+        session_count
+    } else {
+        app_state.db_client
+            .get_user_session_count(user.user.id, true)
+            .await
+            .map_err(HttpError::database)?
+    };
+
+    let response = SessionListResponseDto {
+        sessions: Paginated::new(
+            sessions
+                .iter()
+                .map(|session| SessionDto::filter_session(session, user.session_id))
+                .collect(),
+            &query_params,
+            session_count,
+        ),
+        active_sessions,
+        session_limit: app_state.env.session_limit_for(&user.user),
+    };
+
+    Ok(Json(response))
+}
+
+pub async fn get_my_api_keys(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>
+) -> Result<impl IntoResponse, HttpError> {
+    let api_keys = app_state.db_client
+        .get_user_api_keys(user.user.id)
+        .await
+        .map_err(HttpError::database)?;
+
+    Ok(Json(ApiKeyListResponseDto {
+        status: "success".to_string(),
+        api_keys: api_keys.iter().map(ApiKeyDto::filter_api_key).collect(),
+    }))
+}
+
+pub async fn create_api_key(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata,
+    StrictJson(body): StrictJson<CreateApiKeyDto>
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(HttpError::validation)?;
+
+    let key = token::generate_api_key();
+
+    let api_key = app_state.db_client
+        .create_api_key(user.user.id, body.name.trim(), &key[..token::API_KEY_PREFIX_LEN], &token::hash_token(&key))
+        .await
+        .map_err(HttpError::database)?;
+
+    let details = format!("key={}", api_key.id);
+    record_event(&app_state, Some(user.user.id), AuditEventType::ApiKeyCreated, &metadata, true, Some(&details)).await;
+
+    Ok((StatusCode::CREATED, Json(ApiKeyCreatedResponseDto {
+        status: "success".to_string(),
+        key,
+        api_key: ApiKeyDto::filter_api_key(&api_key),
+    })))
+}
+
+pub async fn revoke_api_key(
+    Path(key_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata
+) -> Result<impl IntoResponse, HttpError> {
+    let revoked = app_state.db_client
+        .revoke_api_key(user.user.id, key_id)
+        .await
+        .map_err(HttpError::database)?;
+
+    if !revoked {
+        return Err(HttpError::not_found(ErrorMessage::ApiKeyNotFound));
+    }
+
+    let details = format!("key={}", key_id);
+    record_event(&app_state, Some(user.user.id), AuditEventType::ApiKeyRevoked, &metadata, true, Some(&details)).await;
+
+    Ok(Json(Response {
+        message: "API key revoked successfully".to_string(),
+        status: "success",
+    }))
+}
+
+pub async fn rotate_api_key(
+    Path(key_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata
+) -> Result<impl IntoResponse, HttpError> {
+    let current = app_state.db_client
+        .get_user_api_key(user.user.id, key_id)
+        .await
+        .map_err(HttpError::database)?
+        .ok_or(HttpError::not_found(ErrorMessage::ApiKeyNotFound))?;
+
+    if current.rotated_at.is_some() {
+        return Err(HttpError::new(ErrorMessage::ApiKeyAlreadyRotating, StatusCode::CONFLICT));
+    }
+
+    let key = token::generate_api_key();
+    let grace_until = Utc::now() + Duration::seconds(app_state.env.api_key_rotation_grace_seconds);
+
+    let (previous, replacement) = app_state.db_client
+        .rotate_api_key(user.user.id, current.id, &key[..token::API_KEY_PREFIX_LEN], &token::hash_token(&key), grace_until)
+        .await
+        .map_err(HttpError::database)?
+        .ok_or(HttpError::new(ErrorMessage::ApiKeyAlreadyRotating, StatusCode::CONFLICT))?;
+
+    let details = format!("key={} replaced_by={}", previous.id, replacement.id);
+    record_event(&app_state, Some(user.user.id), AuditEventType::ApiKeyRotated, &metadata, true, Some(&details)).await;
+
+    Ok((StatusCode::CREATED, Json(ApiKeyRotatedResponseDto {
+        status: "success".to_string(),
+        key,
+        api_key: ApiKeyDto::filter_api_key(&replacement),
+        previous_key: ApiKeyDto::filter_api_key(&previous),
+    })))
+}
+
+const CSV_EXPORT_BATCH: usize = 500;
+
+pub async fn revoke_trusted_device(
+    Path(device_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata
+) -> Result<impl IntoResponse, HttpError> {
+    let revoked = app_state.db_client
+        .revoke_trusted_device(user.user.id, device_id)
+        .await
+        .map_err(HttpError::database)?;
+
+    if !revoked {
+        return Err(HttpError::not_found(ErrorMessage::TrustedDeviceNotFound));
+    }
+
+    let details = format!("device={}", device_id);
+    record_event(&app_state, Some(user.user.id), AuditEventType::TrustedDeviceRevoked, &metadata, true, Some(&details)).await;
+
+    Ok(Json(Response {
+        message: "Trusted device revoked successfully".to_string(),
+        status: "success",
+    }))
+}
+
+pub async fn revoke_trusted_devices(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata
+) -> Result<impl IntoResponse, HttpError> {
+    let revoked = app_state.db_client
+        .revoke_trusted_devices(user.user.id)
+        .await
+        .map_err(HttpError::database)?;
+
+    let details = format!("revoked={}", revoked);
+    record_event(&app_state, Some(user.user.id), AuditEventType::TrustedDeviceRevoked, &metadata, true, Some(&details)).await;
+
+    Ok(Json(Response {
+        message: format!("{} trusted devices revoked", revoked),
+        status: "success",
+    }))
+}
+
+pub async fn get_user_stats(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(admin): Extension<JWTAuthMiddleware>
+) -> Result<impl IntoResponse, HttpError> {
+    let ttl = StdDuration::from_secs(app_state.env.user_stats_cache_seconds);
+    let org_id = admin.user.org_id;
+
+    let stats = match app_state.user_stats.get(&org_id, ttl) {
+        Some(stats) => stats,
+        None => {
+            let stats = app_state.db_client
+                .get_user_stats(org_id, Utc::now() - Duration::days(app_state.env.dormant_after_days))
+                .await
+                .map_err(HttpError::database)?;
+
+            let stats = UserStatsDto::from_stats(&stats, app_state.env.dormant_after_days);
+            app_state.user_stats.set(org_id, stats.clone());
+            stats
+        }
+    };
+
+    Ok(Json(UserStatsResponseDto {
+        status: "success".to_string(),
+        data: stats,
+    }))
+}
+
+pub async fn get_users(
+    Query(page_params): Query<RequestQueryDto>,
+    Query(query_params): Query<UserListQueryDto>,
+    Query(fields): Query<FieldsQueryDto>,
+    headers: HeaderMap,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(admin): Extension<JWTAuthMiddleware>
+) -> Result<axum::response::Response, HttpError> {
+    page_params.validate()
+        .map_err(HttpError::validation)?;
+
+    query_params.validate()
+        .map_err(HttpError::validation)?;
+
+    let selection = fields.selection()
+        .map_err(HttpError::bad_request)?;
+
+    let role = query_params.role.as_deref()
+        .map(str::parse::<UserRole>)
+        .transpose()
+        .map_err(HttpError::bad_request)?;
+
+    let as_csv = match query_params.format.as_deref() {
+        Some("csv") => true,
+        Some("json") => false,
+        Some(_) => return Err(HttpError::bad_request("Format must be either json or csv".to_string())),
+        None => headers.get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.split(',').any(|media| media.trim().starts_with("text/csv"))),
+    };
+
+    let query = ListQuery::new(USER_FILTER_FIELDS, USER_SORT_FIELDS, "created_at")
+        .search(&["name", "email"], query_params.search.as_deref())
+        .and_then(|query| query.filter("role", FilterOp::Eq, role.map(|role| FilterValue::Enum(role.to_str().to_string(), "user_role"))))
+        .and_then(|query| query.filter("status", FilterOp::Eq, query_params.status.clone().map(FilterValue::Text)))
+        .and_then(|query| query.filter("verified", FilterOp::Eq, query_params.verified.map(FilterValue::Bool)))
+        .and_then(|query| query.filter("inactive_since", FilterOp::Lte, query_params.inactive_since.map(FilterValue::Timestamp)))
+        .and_then(|query| query.sort(query_params.sort_by.as_deref(), query_params.order.as_deref()))
+        .map_err(HttpError::bad_request)?;
+
+    if as_csv {
+        return Ok(export_users_csv(app_state, admin.user.org_id, query));
+    }
+
+    let query = query.paginate(page_params.page(), page_params.limit());
+
+    let users = app_state.db_client.get_users(admin.user.org_id, &query)
+        .await
+        .map_err(HttpError::database)?;
+
+    let user_count = app_state.db_client.get_user_count(admin.user.org_id, &query)
+        .await
+        .map_err(HttpError::database)?;
+
+    let users = FilterUserDto::filter_users(&users);
+
+    if let Some(selection) = selection {
+        let users = users.iter().map(|user| user.project(&selection)).collect();
+        return Ok(Json(Paginated::new(users, &page_params, user_count)).into_response());
+    }
+
+    Ok(Json(Paginated::new(users, &page_params, user_count)).into_response())
+}
+
+pub async fn get_users_by_role(
+    Path(role): Path<String>,
+    Query(page_params): Query<RequestQueryDto>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(admin): Extension<JWTAuthMiddleware>
+) -> Result<impl IntoResponse, HttpError> {
+    page_params.validate()
+        .map_err(HttpError::validation)?;
+
+    let role: UserRole = role.parse()
+        .map_err(HttpError::bad_request)?;
+
+    let query = ListQuery::new(USER_FILTER_FIELDS, USER_SORT_FIELDS, "created_at")
+        .filter("role", FilterOp::Eq, Some(FilterValue::Enum(role.to_str().to_string(), "user_role")))
+        .map_err(HttpError::bad_request)?
+        .paginate(page_params.page(), page_params.limit());
+
+    let users = app_state.db_client.get_users(admin.user.org_id, &query)
+        .await
+        .map_err(HttpError::database)?;
+
+    let user_count = app_state.db_client.get_user_count(admin.user.org_id, &query)
+        .await
+        .map_err(HttpError::database)?;
+
+    Ok(Json(Paginated::new(FilterUserDto::filter_users(&users), &page_params, user_count)))
+}
+
+fn export_users_csv(app_state: Arc<AppState>, org_id: uuid::Uuid, query: ListQuery) -> axum::response::Response {
+    let header_row = stream::once(async { Ok::<_, sqlx::Error>(FilterUserDto::csv_header()) });
+
+    let rows = stream::try_unfold(Some(1), move |page| {
+        let app_state = app_state.clone();
+        let query = query.clone();
+        async move {
+            let Some(page) = page else {
+                return Ok(None);
+            };
+
+            let users = app_state.db_client
+                .get_users(org_id, &query.paginate(page, CSV_EXPORT_BATCH))
+                .await?;
+
+            if users.is_empty() {
+                return Ok(None);
+            }
+
+            let next = (users.len() == CSV_EXPORT_BATCH).then_some(page + 1);
+            let chunk: String = FilterUserDto::filter_users(&users)
+                .iter()
+                .map(FilterUserDto::to_csv_row)
+                .collect();
+
+            Ok(Some((chunk, next)))
+        }
+    });
+
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"users.csv\""),
+        ],
+        Body::from_stream(header_row.chain(rows)),
+    ).into_response()
+}
+
+pub async fn get_user(
+    Path(user_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(viewer): Extension<JWTAuthMiddleware>
+) -> Result<impl IntoResponse, HttpError> {
+    let user = app_state.db_client
+        .get_user_in_org(viewer.user.org_id, user_id)
+        .await
+        .map_err(HttpError::database)?
+        .ok_or(HttpError::not_found("User not found".to_string()))?;
+
+    let response = UserViewResponseDto {
+        status: "success".to_string(),
+        user: UserViewDto::for_viewer(&viewer.user, &user),
+    };
+
+    Ok(Json(response))
+}
+
+pub async fn terminate_user_sessions(
+    Path(user_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(admin): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata
+) -> Result<impl IntoResponse, HttpError> {
+    let user = app_state.db_client
+        .get_user_in_org(admin.user.org_id, user_id)
+        .await
+        .map_err(HttpError::database)?
+        .ok_or(HttpError::not_found(ErrorMessage::UserNoLongerExist))?;
+
+    let terminated = app_state.db_client
+        .terminate_user_sessions(user.id)
+        .await
+        .map_err(HttpError::database)?;
+
+    let details = format!("terminated={} by={}", terminated, admin.user.id);
+    record_event(&app_state, Some(user.id), AuditEventType::SessionsTerminated, &metadata, true, Some(&details)).await;
+
+    Ok(Json(TerminateSessionsResponseDto {
+        status: "success".to_string(),
+        terminated,
+    }))
+}
+
+pub async fn impersonate_user(
+    Path(user_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(admin): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata
+) -> Result<impl IntoResponse, HttpError> {
+    let user = app_state.db_client
+        .get_user_in_org(admin.user.org_id, user_id)
+        .await
+        .map_err(HttpError::database)?
+        .ok_or(HttpError::not_found(ErrorMessage::UserNoLongerExist))?;
+
+    if admin.impersonated_by.is_some() || user.id == admin.user.id || user.role == UserRole::Admin || user.status != AccountStatus::Active {
+        return Err(HttpError::new(ErrorMessage::ImpersonationNotAllowed, StatusCode::FORBIDDEN));
+    }
+
+    let session = app_state.db_client
+        .create_session(
+            user.id,
+            metadata.ip_address.as_deref(),
+            metadata.user_agent.as_deref(),
+            &metadata.binding(),
+            Utc::now() + Duration::minutes(app_state.env.impersonation_minutes),
+            false
+        )
+        .await
+        .map_err(HttpError::database)?;
+
+    let token = token::create_impersonation_token(
+        &user.id.to_string(),
+        &session.id.to_string(),
+        &admin.user.id.to_string(),
+        &user.org_id.to_string(),
+        &app_state.env.jwt_keys,
+        app_state.env.impersonation_minutes
+    ).map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let details = format!("admin={} session={}", admin.user.id, session.id);
+    record_event(&app_state, Some(user.id), AuditEventType::ImpersonationStarted, &metadata, true, Some(&details)).await;
+
+    Ok(Json(ImpersonationResponseDto {
+        status: "success".to_string(),
+        token,
+        user_id: user.id.to_string(),
+        expires_at: session.expires_at,
+    }))
+}
+
+pub async fn end_impersonation(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata
+) -> Result<impl IntoResponse, HttpError> {
+    let (Some(admin_id), Some(session_id)) = (user.impersonated_by, user.session_id) else {
+        return Err(HttpError::bad_request(ErrorMessage::NotImpersonating));
+    };
+
+    app_state.db_client
+        .revoke_session(session_id, user.user.id)
+        .await
+        .map_err(HttpError::database)?;
+
+    let details = format!("admin={} session={}", admin_id, session_id);
+    record_event(&app_state, Some(user.user.id), AuditEventType::ImpersonationEnded, &metadata, true, Some(&details)).await;
+
+    Ok(Json(Response {
+        message: "Impersonation ended".to_string(),
+        status: "success",
+    }))
+}
+
+pub async fn update_user_status(
+    Path(user_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(admin): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata,
+    StrictJson(body): StrictJson<StatusUpdateDto>
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(HttpError::validation)?;
+
+    if user_id == admin.user.id {
+        return Err(HttpError::bad_request("You cannot change the status of your own account".to_string()));
+    }
+
+    let user = app_state.db_client
+        .get_user_in_org(admin.user.org_id, user_id)
+        .await
+        .map_err(HttpError::database)?
+        .ok_or(HttpError::not_found(ErrorMessage::UserNoLongerExist))?;
+
+    if !user.status.can_transition_to(body.status) {
+        return Err(HttpError::new(
+            format!("Cannot change account status from {} to {}", user.status.to_str(), body.status.to_str()),
+            StatusCode::CONFLICT
+        ));
+    }
+
+    let updated_user = app_state.db_client
+        .update_user_status(user.id, body.status, body.reason.as_deref())
+        .await
+        .map_err(HttpError::database)?;
+
+    let details = format!("from={} to={} by={}", user.status.to_str(), body.status.to_str(), admin.user.id);
+    record_event(&app_state, Some(user.id), AuditEventType::StatusChanged, &metadata, true, Some(&details)).await;
+
+    Ok(Json(UserResponseDto {
+        status: "success".to_string(),
+        data: UserData {
+            user: FilterUserDto::filter_user(&updated_user),
+        },
+    }))
+}
+
+pub async fn freeze_user(
+    Path(user_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(admin): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata,
+    StrictJson(body): StrictJson<FreezeUserDto>
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(HttpError::validation)?;
+
+    if user_id == admin.user.id {
+        return Err(HttpError::bad_request("You cannot freeze your own account".to_string()));
+    }
+
+    let user = app_state.db_client
+        .get_user_in_org(admin.user.org_id, user_id)
+        .await
+        .map_err(HttpError::database)?
+        .ok_or(HttpError::not_found(ErrorMessage::UserNoLongerExist))?;
+
+    if user.frozen {
+        return Err(HttpError::new(ErrorMessage::UserAlreadyFrozen, StatusCode::CONFLICT));
+    }
+
+    let updated_user = app_state.db_client
+        .update_user_frozen(user.id, true, body.reason.as_deref())
+        .await
+        .map_err(HttpError::database)?;
+
+    let details = format!("by={}", admin.user.id);
+    record_event(&app_state, Some(user.id), AuditEventType::AccountFrozen, &metadata, true, Some(&details)).await;
+
+    Ok(Json(UserResponseDto {
+        status: "success".to_string(),
+        data: UserData {
+            user: FilterUserDto::filter_user(&updated_user),
+        },
+    }))
+}
+
+pub async fn unfreeze_user(
+    Path(user_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(admin): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata
+) -> Result<impl IntoResponse, HttpError> {
+    let user = app_state.db_client
+        .get_user_in_org(admin.user.org_id, user_id)
+        .await
+        .map_err(HttpError::database)?
+        .ok_or(HttpError::not_found(ErrorMessage::UserNoLongerExist))?;
+
+    if !user.frozen {
+        return Err(HttpError::new(ErrorMessage::UserNotFrozen, StatusCode::CONFLICT));
+    }
+
+    let updated_user = app_state.db_client
+        .update_user_frozen(user.id, false, None)
+        .await
+        .map_err(HttpError::database)?;
+
+    let details = format!("by={}", admin.user.id);
+    record_event(&app_state, Some(user.id), AuditEventType::AccountUnfrozen, &metadata, true, Some(&details)).await;
+
+    Ok(Json(UserResponseDto {
+        status: "success".to_string(),
+        data: UserData {
+            user: FilterUserDto::filter_user(&updated_user),
+        },
+    }))
+}
+
+pub async fn approve_user(
+    Path(user_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(admin): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata
+) -> Result<impl IntoResponse, HttpError> {
+    let user = app_state.db_client
+        .get_user_in_org(admin.user.org_id, user_id)
+        .await
+        .map_err(HttpError::database)?
+        .ok_or(HttpError::not_found(ErrorMessage::UserNoLongerExist))?;
+
+    if user.status != AccountStatus::PendingApproval {
+        return Err(HttpError::new(ErrorMessage::UserNotPendingApproval, StatusCode::CONFLICT));
+    }
+
+    let approved_user = app_state.db_client
+        .update_user_status(user.id, AccountStatus::Active, None)
+        .await
+        .map_err(HttpError::database)?;
+
+    let details = format!("by={}", admin.user.id);
+    record_event(&app_state, Some(user.id), AuditEventType::UserApproved, &metadata, true, Some(&details)).await;
+
+    let login_link = app_state.env.frontend_link("/login");
+    if let Err(e) = queue_account_approved_email(&app_state.email_queue, approved_user.org_id, &approved_user.email, &approved_user.name, &login_link).await {
+        eprintln!("Failed to queue account approved email: {}", e);
+    }
+
+    Ok(Json(UserResponseDto {
+        status: "success".to_string(),
+        data: UserData {
+            user: FilterUserDto::filter_user(&approved_user),
+        },
+    }))
+}
+
+pub async fn reject_user(
+    Path(user_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(admin): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata,
+    StrictJson(body): StrictJson<RejectUserDto>
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(HttpError::validation)?;
+
+    let user = app_state.db_client
+        .get_user_in_org(admin.user.org_id, user_id)
+        .await
+        .map_err(HttpError::database)?
+        .ok_or(HttpError::not_found(ErrorMessage::UserNoLongerExist))?;
+
+    let rejected = app_state.db_client
+        .reject_pending_user(user.id, body.reason.as_deref())
+        .await
+        .map_err(HttpError::database)?;
+
+    if rejected.is_none() {
+        return Err(HttpError::new(ErrorMessage::UserNotPendingApproval, StatusCode::CONFLICT));
+    }
+
+    let details = format!("by={}", admin.user.id);
+    record_event(&app_state, Some(user.id), AuditEventType::UserRejected, &metadata, true, Some(&details)).await;
+
+    Ok(Json(Response {
+        message: "User rejected successfully".to_string(),
+        status: "success",
+    }))
+}
+
+pub async fn update_user_session_limit(
+    Path(user_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(admin): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata,
+    StrictJson(body): StrictJson<SessionLimitUpdateDto>
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(HttpError::validation)?;
+
+    let user = app_state.db_client
+        .get_user_in_org(admin.user.org_id, user_id)
+        .await
+        .map_err(HttpError::database)?
+        .ok_or(HttpError::not_found(ErrorMessage::UserNoLongerExist))?;
+
+    let updated_user = app_state.db_client
+        .update_user_session_limit(user.id, body.max_sessions)
+        .await
+        .map_err(HttpError::database)?;
+
+    let limit = body.max_sessions.map_or("default".to_string(), |limit| limit.to_string());
+    let details = format!("max_sessions={} by={}", limit, admin.user.id);
+    record_event(&app_state, Some(user.id), AuditEventType::SessionLimitChanged, &metadata, true, Some(&details)).await;
+
+    Ok(Json(UserResponseDto {
+        status: "success".to_string(),
+        data: UserData {
+            user: FilterUserDto::filter_user(&updated_user),
+        },
+    }))
+}
+
+pub async fn merge_users(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(admin): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata,
+    Json(body): Json<MergeUsersDto>
+) -> Result<impl IntoResponse, HttpError> {
+    if body.source_id == body.target_id {
+        return Err(HttpError::bad_request("Source and target accounts must be different".to_string()));
+    }
+
+    if body.source_id == admin.user.id {
+        return Err(HttpError::bad_request("You cannot merge away your own account".to_string()));
+    }
+
+    let source = app_state.db_client
+        .get_user_in_org(admin.user.org_id, body.source_id)
+        .await
+        .map_err(HttpError::database)?
+        .ok_or(HttpError::not_found("Source user not found".to_string()))?;
+
+    let target = app_state.db_client
+        .get_user_in_org(admin.user.org_id, body.target_id)
+        .await
+        .map_err(HttpError::database)?
+        .ok_or(HttpError::not_found("Target user not found".to_string()))?;
+
+    if source.role == UserRole::Admin && target.role != UserRole::Admin {
+        return Err(HttpError::unique_constraint_violation("Source account is an admin and the target is not, demote the source before merging".to_string()));
+    }
+
+    // The target keeps its own authenticator and recovery codes, there is no
+    // way to fold a second secret into them.
+    if source.totp_enabled {
+        return Err(HttpError::unique_constraint_violation("Source account has two-factor authentication enabled, disable it before merging".to_string()));
+    }
+
+    let result = app_state.db_client
+        .merge_users(source.id, target.id)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+                HttpError::unique_constraint_violation("Accounts have conflicting data that cannot be merged".to_string())
+            }
+            e => HttpError::database(e),
+        })?;
+
+    let details = format!("source={} target={} by={}", source.id, target.id, admin.user.id);
+    record_event(&app_state, Some(target.id), AuditEventType::AccountMerged, &metadata, true, Some(&details)).await;
+
+    let filtered_user = FilterUserDto::filter_user(&result);
+
+    let response = UserResponseDto {
         data: UserData {
             user: filtered_user,
-        }
+        },
+        status: "success".to_string(),
     };
 
-    Ok(Json(response_data))
+    Ok(Json(response))
 }
 
-pub async fn get_users(
-    Query(query_params): Query<RequestQueryDto>,
-    Extension(app_state): Extension<Arc<AppState>>
+pub async fn update_user_name(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    StrictJson(body): StrictJson<NameUpdateDto>
 ) -> Result<impl IntoResponse, HttpError> {
-    query_params.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
-
-    let page = query_params.page.unwrap_or(1);
-    let limit = query_params.limit.unwrap_or(10);
+    body.validate()
+        .map_err(HttpError::validation)?;
 
-    let users = app_state.db_client.get_users(page as u32, limit)
-        .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+    let user = &user.user;
+    let user_id = uuid::Uuid::parse_str(&user.id.to_string()).unwrap();
 
-    let user_count = app_state.db_client.get_user_count()
+    let result = app_state.db_client.update_user_name(user_id, &body.name)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::database)?;
 
-    let response = UserListResponseDto {
+    let filtered_user = FilterUserDto::filter_user(&result);
+    
+    let response = UserResponseDto {
+        data: UserData {
+            user: filtered_user,
+        },
         status: "success".to_string(),
-        users: FilterUserDto::filter_users(&users),
-        results: user_count,
     };
 
     Ok(Json(response))
 }
 
-pub async fn update_user_name(
+pub async fn update_profile(
     Extension(app_state): Extension<Arc<AppState>>,
     Extension(user): Extension<JWTAuthMiddleware>,
-    Json(body): Json<NameUpdateDto>
+    metadata: RequestMetadata,
+    StrictJson(body): StrictJson<ProfileUpdateDto>
 ) -> Result<impl IntoResponse, HttpError> {
+    if body.is_empty() {
+        return Err(HttpError::bad_request("At least one profile field must be provided".to_string()));
+    }
+
     body.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+        .map_err(HttpError::validation)?;
 
     let user = &user.user;
-    let user_id = uuid::Uuid::parse_str(&user.id.to_string()).unwrap();
 
-    let result = app_state.db_client.update_user_name(user_id.clone(), &body.name)
+    let result = app_state.db_client
+        .update_user_profile(
+            user.id,
+            body.name.as_deref(),
+            body.display_name.as_deref().map(str::trim),
+            body.locale.as_deref(),
+            body.avatar_url.as_deref(),
+        )
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::database)?;
+
+    record_event(&app_state, Some(user.id), AuditEventType::ProfileUpdated, &metadata, true, None).await;
 
     let filtered_user = FilterUserDto::filter_user(&result);
-    
+
     let response = UserResponseDto {
         data: UserData {
             user: filtered_user,
@@ -96,20 +1269,40 @@ pub async fn update_user_name(
 
 pub async fn update_user_role(
     Extension(app_state): Extension<Arc<AppState>>,
-    Extension(user): Extension<JWTAuthMiddleware>,
-    Json(body): Json<RoleUpdateDto>,
+    Extension(admin): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata,
+    StrictJson(body): StrictJson<RoleUpdateDto>,
 ) -> Result<impl IntoResponse, HttpError> {
     body.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+        .map_err(HttpError::validation)?;
 
-    let user = &user.user;
+    let admin = &admin.user;
+    let user_id = body.user_id.unwrap_or(admin.id);
 
-    let user_id = uuid::Uuid::parse_str(&user.id.to_string()).unwrap();
+    let user = app_state.db_client
+        .get_user_in_org(admin.org_id, user_id)
+        .await
+        .map_err(HttpError::database)?
+        .ok_or(HttpError::not_found(ErrorMessage::UserNoLongerExist))?;
+
+    if let Some(message) = role_change_denied(admin, &user, body.role) {
+        return Err(HttpError::bad_request(message));
+    }
+
+    ensure_admin_remains(&app_state, admin.org_id, std::slice::from_ref(&user), body.role).await?;
 
     let result = app_state.db_client
-        .update_user_role(user_id.clone(), body.role)
+        .update_user_role(user_id, body.role)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::database)?;
+
+    let mut details = format!("{} -> {} by={}", user.role.to_str(), result.role.to_str(), admin.id);
+    if user.role != result.role {
+        if let Some(revoked) = revoke_after_role_change(&app_state, user_id).await? {
+            details.push_str(&format!(" sessions_revoked={}", revoked));
+        }
+    }
+    record_event(&app_state, Some(user_id), AuditEventType::RoleChanged, &metadata, true, Some(&details)).await;
 
     let filtered_user = FilterUserDto::filter_user(&result);
 
@@ -123,39 +1316,179 @@ pub async fn update_user_role(
     Ok(Json(response))
 }
 
+pub async fn bulk_update_user_role(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(admin): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata,
+    StrictJson(body): StrictJson<BulkRoleUpdateDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(HttpError::validation)?;
+
+    let admin = &admin.user;
+    let mut results: Vec<Option<BulkRoleResultDto>> = Vec::with_capacity(body.ids.len());
+    let mut targets: Vec<(usize, User)> = Vec::new();
+
+    for (index, id) in body.ids.iter().enumerate() {
+        let failure = |message: &str| Some(BulkRoleResultDto {
+            id: id.to_owned(),
+            success: false,
+            message: Some(message.to_string()),
+        });
+
+        let Ok(user_id) = uuid::Uuid::parse_str(id) else {
+            results.push(failure("Invalid user id"));
+            continue;
+        };
+
+        let user = app_state.db_client
+            .get_user_in_org(admin.org_id, user_id)
+            .await
+            .map_err(HttpError::database)?;
+
+        match user {
+            None => results.push(failure(&ErrorMessage::UserNoLongerExist.to_string())),
+            Some(user) => match role_change_denied(admin, &user, body.role) {
+                Some(message) => results.push(failure(&message)),
+                None => {
+                    results.push(None);
+                    targets.push((index, user));
+                }
+            },
+        }
+    }
+
+    let users: Vec<User> = targets.iter().map(|(_, user)| user.clone()).collect();
+    ensure_admin_remains(&app_state, admin.org_id, &users, body.role).await?;
+
+    let target_ids: Vec<uuid::Uuid> = users.iter().map(|user| user.id).collect();
+
+    let updated = app_state.db_client
+        .bulk_update_user_role(&target_ids, body.role)
+        .await
+        .map_err(HttpError::database)?;
+
+    for (index, user) in &targets {
+        let success = updated.iter().any(|updated| updated.id == user.id);
+        results[*index] = Some(BulkRoleResultDto {
+            id: body.ids[*index].to_owned(),
+            success,
+            message: (!success).then(|| ErrorMessage::UserNoLongerExist.to_string()),
+        });
+
+        if success {
+            let mut details = format!("{} -> {} by={}", user.role.to_str(), body.role.to_str(), admin.id);
+            if user.role != body.role {
+                if let Some(revoked) = revoke_after_role_change(&app_state, user.id).await? {
+                    details.push_str(&format!(" sessions_revoked={}", revoked));
+                }
+            }
+            record_event(&app_state, Some(user.id), AuditEventType::BulkRoleChanged, &metadata, true, Some(&details)).await;
+        }
+    }
+
+    let response = BulkRoleResponseDto {
+        status: "success".to_string(),
+        results: results.into_iter().flatten().collect(),
+    };
+
+    Ok(Json(response))
+}
+
+fn role_change_denied(admin: &User, user: &User, role: UserRole) -> Option<String> {
+    if user.id == admin.id && role != UserRole::Admin {
+        return Some("You cannot demote yourself".to_string());
+    }
+
+    None
+}
+
+// Sessions opened under the old role keep that role's token lifetime, so a role
+// change ends them all unless the deployment prefers to let them expire.
+async fn revoke_after_role_change(app_state: &AppState, user_id: uuid::Uuid) -> Result<Option<u64>, HttpError> {
+    if !app_state.env.role_change_revokes_sessions {
+        return Ok(None);
+    }
+
+    app_state.db_client
+        .terminate_user_sessions(user_id)
+        .await
+        .map(Some)
+        .map_err(HttpError::database)
+}
+
+async fn ensure_admin_remains(app_state: &AppState, org_id: uuid::Uuid, users: &[User], role: UserRole) -> Result<(), HttpError> {
+    if role == UserRole::Admin {
+        return Ok(());
+    }
+
+    let demoted_admins = users.iter().filter(|user| user.role == UserRole::Admin).count() as i64;
+    if demoted_admins == 0 {
+        return Ok(());
+    }
+
+    let admin_count = app_state.db_client
+        .get_admin_count(org_id)
+        .await
+        .map_err(HttpError::database)?;
+
+    if admin_count - demoted_admins < 1 {
+        return Err(HttpError::bad_request("At least one admin must remain".to_string()));
+    }
+
+    Ok(())
+}
+
 pub async fn update_user_password(
     Extension(user): Extension<JWTAuthMiddleware>,
     Extension(app_state): Extension<Arc<AppState>>,
-    Json(body): Json<UserPasswordUpdateDto>,
+    metadata: RequestMetadata,
+    StrictJson(body): StrictJson<UserPasswordUpdateDto>,
 ) -> Result<impl IntoResponse, HttpError> {
     body.validate()
-       .map_err(|e| HttpError::bad_request(e.to_string()))?;
+       .map_err(HttpError::validation)?;
 
     let user = &user.user;
 
     let user_id = uuid::Uuid::parse_str(&user.id.to_string()).unwrap();
 
     let result = app_state.db_client
-        .get_user(Some(user_id.clone()), None, None, None)
+        .get_user(Some(user_id), None, None, None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::database)?;
+
+    let user = result.ok_or(HttpError::unauthorized(ErrorMessage::InvalidToken))?;
 
-    let user = result.ok_or(HttpError::unauthorized(ErrorMessage::InvalidToken.to_string()))?;
+    if let Some(allowed_at) = user.password_change_allowed_at(app_state.env.password_min_age_hours) {
+        return Err(HttpError::bad_request(format!(
+            "Password was changed too recently, you can change it again after {}",
+            allowed_at.to_rfc3339()
+        )));
+    }
+
+    let pepper = app_state.env.pepper_for(user.password_pepper_id.as_deref())
+            .map_err(|e| HttpError::server_error(e.to_string()))?;
 
-    let password_match = password::compare(&body.old_password, &user.password)
+    let password_match = password::compare(&body.old_password, &user.password, pepper)
             .map_err(|e| HttpError::server_error(e.to_string()))?;
 
     if !password_match {
         return Err(HttpError::bad_request("Old password is incorrect".to_string()));
     }
 
-    let hash_password = password::hash(&body.new_password)
+    app_state.env.password_policy
+        .check(&body.new_password, &user.name, &user.email)
+        .map_err(HttpError::bad_request)?;
+
+    let hash_password = password::hash(&body.new_password, app_state.env.password_pepper.as_ref())
         .map_err(|e| HttpError::server_error(e.to_string()))?;
 
     app_state.db_client
-        .update_user_password(user_id.clone(), hash_password)
+        .update_user_password(user_id, hash_password, app_state.env.current_pepper_id())
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::database)?;
+
+    record_event(&app_state, Some(user_id), AuditEventType::PasswordChanged, &metadata, true, None).await;
 
     let response = Response {
         message: "Password updated Successfully".to_string(),
@@ -165,3 +1498,270 @@ pub async fn update_user_password(
     Ok(Json(response))
 
 }
+
+pub async fn setup_two_factor(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>
+) -> Result<impl IntoResponse, HttpError> {
+    let user = &user.user;
+
+    if user.totp_enabled {
+        return Err(HttpError::bad_request("Two-factor authentication is already enabled".to_string()));
+    }
+
+    let secret = totp::generate_secret();
+
+    app_state.db_client
+        .set_totp_secret(user.id, &secret)
+        .await
+        .map_err(HttpError::database)?;
+
+    let response = TwoFactorSetupResponseDto {
+        status: "success".to_string(),
+        data: TwoFactorSetupDto {
+            otpauth_url: totp::provisioning_uri(&secret, "auth_api", &user.email),
+            secret,
+        },
+    };
+
+    Ok(Json(response))
+}
+
+pub async fn enable_two_factor(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata,
+    Json(body): Json<TwoFactorCodeDto>
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(HttpError::validation)?;
+
+    let user = &user.user;
+
+    if user.totp_enabled {
+        return Err(HttpError::bad_request("Two-factor authentication is already enabled".to_string()));
+    }
+
+    let secret = user.totp_secret
+        .as_deref()
+        .ok_or(HttpError::bad_request("Two-factor setup has not been started".to_string()))?;
+
+    if !totp::verify_code(secret, &body.code) {
+        return Err(HttpError::bad_request(ErrorMessage::InvalidTwoFactorCode));
+    }
+
+    let recovery_codes = totp::generate_recovery_codes();
+
+    app_state.db_client
+        .enable_two_factor(user.id, &hash_recovery_codes(&recovery_codes))
+        .await
+        .map_err(HttpError::database)?;
+
+    record_event(&app_state, Some(user.id), AuditEventType::TwoFactorEnabled, &metadata, true, None).await;
+
+    Ok(Json(RecoveryCodesResponseDto {
+        status: "success".to_string(),
+        recovery_codes,
+    }))
+}
+
+pub async fn regenerate_recovery_codes(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata,
+    Json(body): Json<TwoFactorCodeDto>
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(HttpError::validation)?;
+
+    let user = &user.user;
+
+    let code_matched = user.totp_enabled && user.totp_secret
+        .as_deref()
+        .map(|secret| totp::verify_code(secret, &body.code))
+        .unwrap_or(false);
+
+    if !code_matched {
+        return Err(HttpError::bad_request(ErrorMessage::InvalidTwoFactorCode));
+    }
+
+    let recovery_codes = totp::generate_recovery_codes();
+
+    app_state.db_client
+        .replace_recovery_codes(user.id, &hash_recovery_codes(&recovery_codes))
+        .await
+        .map_err(HttpError::database)?;
+
+    record_event(&app_state, Some(user.id), AuditEventType::RecoveryCodesRegenerated, &metadata, true, None).await;
+
+    Ok(Json(RecoveryCodesResponseDto {
+        status: "success".to_string(),
+        recovery_codes,
+    }))
+}
+
+fn hash_recovery_codes(recovery_codes: &[String]) -> Vec<String> {
+    recovery_codes
+        .iter()
+        .map(|code| token::hash_token(code))
+        .collect()
+}
+
+pub async fn get_user_emails(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>
+) -> Result<impl IntoResponse, HttpError> {
+    let emails = app_state.db_client
+        .get_user_emails(user.user.id)
+        .await
+        .map_err(HttpError::database)?;
+
+    let response = UserEmailListResponseDto {
+        status: "success".to_string(),
+        emails: FilterUserEmailDto::filter_emails(&emails),
+    };
+
+    Ok(Json(response))
+}
+
+pub async fn add_user_email(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata,
+    Json(body): Json<AddEmailDto>
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(HttpError::validation)?;
+
+    let user = &user.user;
+
+    if app_state.env.email_ignore_case && email_taken_ignore_case(&app_state, user.org_id, &body.email).await? {
+        return Err(HttpError::unique_constraint_violation(ErrorMessage::EmailExist));
+    }
+
+    let verification_token = uuid::Uuid::new_v4().to_string();
+    let expires_at = Utc::now() + Duration::hours(24);
+
+    let result = app_state.db_client
+        .add_user_email(user.id, &body.email, &token::hash_token(&verification_token), expires_at)
+        .await;
+
+    match result {
+        Ok(email) => {
+            record_event(&app_state, Some(user.id), AuditEventType::EmailAdded, &metadata, true, Some(&email.email)).await;
+
+            queue_secondary_email_verification_email(&app_state.email_queue, user.org_id, &email.email, &user.name, &verification_token, &app_state.env.api_url("/auth/emails/verify"))
+                .await
+                .map_err(|e| HttpError::server_error(format!("Failed to queue verification email: {}", e)))?;
+
+            let response = UserEmailResponseDto {
+                status: "success".to_string(),
+                email: FilterUserEmailDto::filter_email(&email),
+            };
+
+            Ok((StatusCode::CREATED, Json(response)))
+        },
+        Err(sqlx::Error::Database(db_err)) if db_err.is_unique_violation() => {
+            Err(HttpError::unique_constraint_violation(ErrorMessage::EmailExist))
+        }
+        Err(e) => Err(HttpError::database(e)),
+    }
+}
+
+pub async fn set_primary_email(
+    Path(email_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata
+) -> Result<impl IntoResponse, HttpError> {
+    let user = &user.user;
+
+    let result = app_state.db_client
+        .get_user_email(user.id, email_id)
+        .await
+        .map_err(HttpError::database)?;
+
+    let email = result.ok_or(HttpError::not_found("Email not found".to_string()))?;
+
+    if !email.verified {
+        return Err(HttpError::bad_request("Email must be verified before it can be made primary".to_string()));
+    }
+
+    let result = app_state.db_client
+        .set_primary_email(user.id, email.id)
+        .await
+        .map_err(HttpError::database)?;
+
+    record_event(&app_state, Some(user.id), AuditEventType::PrimaryEmailChanged, &metadata, true, Some(&email.email)).await;
+
+    if user.email != email.email {
+        notify_email_changed(&app_state, user, &email.email).await;
+    }
+
+    let filtered_user = FilterUserDto::filter_user(&result);
+
+    let response = UserResponseDto {
+        data: UserData {
+            user: filtered_user,
+        },
+        status: "success".to_string(),
+    };
+
+    Ok(Json(response))
+}
+
+async fn notify_email_changed(app_state: &AppState, user: &User, new_email: &str) {
+    let Some(undo_hours) = app_state.env.email_change_undo_hours else {
+        return;
+    };
+
+    let undo_token = uuid::Uuid::new_v4().to_string();
+    let expires_at = Utc::now() + Duration::hours(undo_hours);
+
+    let result = app_state.db_client
+        .save_email_change_revert(user.id, &user.email, new_email, &token::hash_token(&undo_token), expires_at)
+        .await;
+
+    if let Err(e) = result {
+        eprintln!("Failed to save email change revert: {}", e);
+        return;
+    }
+
+    if let Err(e) = queue_email_changed_email(&app_state.email_queue, user.org_id, &user.email, user.display_name(), new_email, &undo_token, &app_state.env.frontend_link("/email-change/undo"), undo_hours).await {
+        eprintln!("Failed to queue email changed email: {}", e);
+    }
+}
+
+pub async fn remove_user_email(
+    Path(email_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    metadata: RequestMetadata
+) -> Result<impl IntoResponse, HttpError> {
+    let user = &user.user;
+
+    let result = app_state.db_client
+        .get_user_email(user.id, email_id)
+        .await
+        .map_err(HttpError::database)?;
+
+    let email = result.ok_or(HttpError::not_found("Email not found".to_string()))?;
+
+    if email.is_primary {
+        return Err(HttpError::bad_request("Primary email cannot be removed, promote another verified email first".to_string()));
+    }
+
+    app_state.db_client
+        .delete_user_email(user.id, email.id)
+        .await
+        .map_err(HttpError::database)?;
+
+    record_event(&app_state, Some(user.id), AuditEventType::EmailRemoved, &metadata, true, Some(&email.email)).await;
+
+    let response = Response {
+        message: "Email removed successfully".to_string(),
+        status: "success",
+    };
+
+    Ok(Json(response))
+}
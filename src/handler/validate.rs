@@ -0,0 +1,49 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{extract::Path, response::IntoResponse, routing::post, Extension, Json, Router};
+use serde::de::DeserializeOwned;
+use validator::Validate;
+
+use crate::{
+    dtos::{ForgotPasswordRequestDto, LoginUserDto, ProfileUpdateDto, RegisterUserDto, ResetPasswordRequestDto, ResetPasswordWithCodeDto, UserPasswordUpdateDto, ValidationResultDto},
+    error::{ErrorMessage, HttpError},
+    middleware::{ClientIp, StrictJson},
+    AppState
+};
+
+pub fn validate_handler() -> Router {
+    Router::new()
+        .route("/:dto", post(validate_dto))
+}
+
+pub async fn validate_dto(
+    ClientIp(client_ip): ClientIp,
+    Path(dto): Path<String>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    StrictJson(body): StrictJson<serde_json::Value>
+) -> Result<impl IntoResponse, HttpError> {
+    let rate_limit_key = format!("validate:{}", client_ip);
+    if !app_state.rate_limiter.check(&rate_limit_key, app_state.env.validate_rate_limit, Duration::from_secs(60)).await {
+        return Err(HttpError::too_many_requests(ErrorMessage::TooManyRequests));
+    }
+
+    let result = match dto.as_str() {
+        "register" => dry_run::<RegisterUserDto>(body)?,
+        "login" => dry_run::<LoginUserDto>(body)?,
+        "forgot-password" => dry_run::<ForgotPasswordRequestDto>(body)?,
+        "reset-password" => dry_run::<ResetPasswordRequestDto>(body)?,
+        "reset-password-code" => dry_run::<ResetPasswordWithCodeDto>(body)?,
+        "change-password" => dry_run::<UserPasswordUpdateDto>(body)?,
+        "profile" => dry_run::<ProfileUpdateDto>(body)?,
+        _ => return Err(HttpError::not_found(ErrorMessage::UnknownValidationTarget(dto))),
+    };
+
+    Ok(Json(result))
+}
+
+fn dry_run<T: DeserializeOwned + Validate>(body: serde_json::Value) -> Result<ValidationResultDto, HttpError> {
+    let dto: T = serde_json::from_value(body)
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    Ok(ValidationResultDto::from_result(dto.validate()))
+}
@@ -1,44 +1,140 @@
-use super::sendmail::send_email;
+use uuid::Uuid;
 
-pub async fn send_verification_email(
+use crate::models::{NotificationKind, NotificationPreferences};
+
+use super::queue::EmailQueue;
+
+pub async fn queue_verification_email(
+    queue: &EmailQueue,
+    org_id: Uuid,
     to_email: &str,
     username: &str,
-    token: &str
-) -> Result<(), Box<dyn std::error::Error>> {
+    token: &str,
+    base_url: &str
+) -> Result<(), sqlx::Error> {
     let subject = "Email Verification";
     let template_path = "src/mail/templates/Verification-email.html";
-    let base_url = "http://localhost:8000/api/auth/verify";
     let verification_link = create_verification_link(base_url, token);
     let placeholders = vec![
         ("{{username}}".to_string(), username.to_string()),
         ("{{verification_link}}".to_string(), verification_link)
     ];
 
-    send_email(to_email, subject, template_path, &placeholders).await
+    queue.enqueue(org_id, to_email, subject, template_path, &placeholders).await
+}
+
+pub async fn queue_verification_reminder_email(
+    queue: &EmailQueue,
+    org_id: Uuid,
+    to_email: &str,
+    username: &str,
+    token: &str,
+    base_url: &str
+) -> Result<(), sqlx::Error> {
+    let subject = "Reminder: verify your email address";
+    let template_path = "src/mail/templates/VerificationReminder-email.html";
+    let verification_link = create_verification_link(base_url, token);
+    let placeholders = vec![
+        ("{{username}}".to_string(), username.to_string()),
+        ("{{verification_link}}".to_string(), verification_link)
+    ];
+
+    queue.enqueue(org_id, to_email, subject, template_path, &placeholders).await
+}
+
+pub async fn queue_secondary_email_verification_email(
+    queue: &EmailQueue,
+    org_id: Uuid,
+    to_email: &str,
+    username: &str,
+    token: &str,
+    base_url: &str
+) -> Result<(), sqlx::Error> {
+    let subject = "Verify your new email address";
+    let template_path = "src/mail/templates/Verification-email.html";
+    let verification_link = create_verification_link(base_url, token);
+    let placeholders = vec![
+        ("{{username}}".to_string(), username.to_string()),
+        ("{{verification_link}}".to_string(), verification_link)
+    ];
+
+    queue.enqueue(org_id, to_email, subject, template_path, &placeholders).await
 }
 
-fn create_verification_link(base_url: &str, token: &str) -> String {
+pub async fn queue_verification_code_email(
+    queue: &EmailQueue,
+    org_id: Uuid,
+    to_email: &str,
+    username: &str,
+    code: &str,
+    expires_in_minutes: i64
+) -> Result<(), sqlx::Error> {
+    let subject = "Your verification code";
+    let template_path = "src/mail/templates/VerificationCode-email.html";
+    let placeholders = vec![
+        ("{{username}}".to_string(), username.to_string()),
+        ("{{verification_code}}".to_string(), code.to_string()),
+        ("{{expires_in_minutes}}".to_string(), expires_in_minutes.to_string())
+    ];
+
+    queue.enqueue(org_id, to_email, subject, template_path, &placeholders).await
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn queue_email_changed_email(
+    queue: &EmailQueue,
+    org_id: Uuid,
+    to_email: &str,
+    username: &str,
+    new_email: &str,
+    token: &str,
+    base_url: &str,
+    undo_hours: i64
+) -> Result<(), sqlx::Error> {
+    let subject = "Your email address was changed";
+    let template_path = "src/mail/templates/EmailChanged-email.html";
+    let undo_link = create_verification_link(base_url, token);
+    let placeholders = vec![
+        ("{{username}}".to_string(), username.to_string()),
+        ("{{new_email}}".to_string(), new_email.to_string()),
+        ("{{undo_link}}".to_string(), undo_link),
+        ("{{undo_hours}}".to_string(), undo_hours.to_string())
+    ];
+
+    queue.enqueue(org_id, to_email, subject, template_path, &placeholders).await
+}
+
+pub fn create_verification_link(base_url: &str, token: &str) -> String {
     format!("{}?token={}", base_url, token)
 }
 
-pub async fn send_welcome_email(
+pub async fn queue_welcome_email(
+    queue: &EmailQueue,
+    org_id: Uuid,
     to_email: &str,
     username: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
+    preferences: &NotificationPreferences
+) -> Result<(), sqlx::Error> {
+    if !preferences.allows(NotificationKind::Welcome) {
+        return Ok(());
+    }
+
     let subject = "Welcome to Application";
     let template_path = "src/mail/templates/Welcome-email.html";
     let placeholders = vec![
         ("{{username}}".to_string(), username.to_string())
     ];
 
-    send_email(to_email, subject, template_path, &placeholders).await
+    queue.enqueue(org_id, to_email, subject, template_path, &placeholders).await
 }
 
-pub async fn send_forget_password_email(
+pub async fn queue_forget_password_email(
+    queue: &EmailQueue,
+    org_id: Uuid,
     to_email: &str,
     reset_link: &str,
     username: &str 
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<(), sqlx::Error> {
     let subject = "Reset Password";
     let template_path = "src/mail/templates/ResetPassword-email.html";
     let placeholders = vec![
@@ -46,5 +142,126 @@ pub async fn send_forget_password_email(
         ("{{reset_link}}".to_string(), reset_link.to_string())
     ];
 
-    send_email(to_email, subject, template_path, &placeholders).await
+    queue.enqueue(org_id, to_email, subject, template_path, &placeholders).await
+}
+
+pub async fn queue_password_reset_code_email(
+    queue: &EmailQueue,
+    org_id: Uuid,
+    to_email: &str,
+    username: &str,
+    code: &str,
+    expires_in_minutes: i64
+) -> Result<(), sqlx::Error> {
+    let subject = "Your password reset code";
+    let template_path = "src/mail/templates/ResetPasswordCode-email.html";
+    let placeholders = vec![
+        ("{{username}}".to_string(), username.to_string()),
+        ("{{reset_code}}".to_string(), code.to_string()),
+        ("{{expires_in_minutes}}".to_string(), expires_in_minutes.to_string())
+    ];
+
+    queue.enqueue(org_id, to_email, subject, template_path, &placeholders).await
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn queue_account_locked_email(
+    queue: &EmailQueue,
+    org_id: Uuid,
+    to_email: &str,
+    username: &str,
+    locked_at: &str,
+    source: &str,
+    unlock_minutes: u64,
+    preferences: &NotificationPreferences
+) -> Result<(), sqlx::Error> {
+    if !preferences.allows(NotificationKind::LockoutNotice) {
+        return Ok(());
+    }
+
+    let subject = "Your account was temporarily locked";
+    let template_path = "src/mail/templates/AccountLocked-email.html";
+    let placeholders = vec![
+        ("{{username}}".to_string(), username.to_string()),
+        ("{{locked_at}}".to_string(), locked_at.to_string()),
+        ("{{source}}".to_string(), source.to_string()),
+        ("{{unlock_minutes}}".to_string(), unlock_minutes.to_string())
+    ];
+
+    queue.enqueue(org_id, to_email, subject, template_path, &placeholders).await
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn queue_lockout_alert_email(
+    queue: &EmailQueue,
+    org_id: Uuid,
+    to_email: &str,
+    account_email: &str,
+    lockouts: u32,
+    window_hours: u64,
+    locked_at: &str,
+    source: &str
+) -> Result<(), sqlx::Error> {
+    let subject = "Repeated account lockouts";
+    let template_path = "src/mail/templates/LockoutAlert-email.html";
+    let placeholders = vec![
+        ("{{account_email}}".to_string(), account_email.to_string()),
+        ("{{lockouts}}".to_string(), lockouts.to_string()),
+        ("{{window_hours}}".to_string(), window_hours.to_string()),
+        ("{{locked_at}}".to_string(), locked_at.to_string()),
+        ("{{source}}".to_string(), source.to_string())
+    ];
+
+    queue.enqueue(org_id, to_email, subject, template_path, &placeholders).await
+}
+
+pub async fn queue_pending_approval_email(
+    queue: &EmailQueue,
+    org_id: Uuid,
+    to_email: &str,
+    username: &str
+) -> Result<(), sqlx::Error> {
+    let subject = "Your account is pending approval";
+    let template_path = "src/mail/templates/PendingApproval-email.html";
+    let placeholders = vec![
+        ("{{username}}".to_string(), username.to_string())
+    ];
+
+    queue.enqueue(org_id, to_email, subject, template_path, &placeholders).await
+}
+
+pub async fn queue_approval_request_email(
+    queue: &EmailQueue,
+    org_id: Uuid,
+    to_email: &str,
+    account_name: &str,
+    account_email: &str,
+    registered_at: &str
+) -> Result<(), sqlx::Error> {
+    let subject = "New account awaiting approval";
+    let template_path = "src/mail/templates/ApprovalRequest-email.html";
+    let placeholders = vec![
+        ("{{account_name}}".to_string(), account_name.to_string()),
+        ("{{account_email}}".to_string(), account_email.to_string()),
+        ("{{registered_at}}".to_string(), registered_at.to_string())
+    ];
+
+    queue.enqueue(org_id, to_email, subject, template_path, &placeholders).await
+}
+
+pub async fn queue_account_approved_email(
+    queue: &EmailQueue,
+    org_id: Uuid,
+    to_email: &str,
+    username: &str,
+    login_link: &str
+) -> Result<(), sqlx::Error> {
+    let subject = "Your account has been approved";
+    let template_path = "src/mail/templates/AccountApproved-email.html";
+    let placeholders = vec![
+        ("{{username}}".to_string(), username.to_string()),
+        ("{{login_link}}".to_string(), login_link.to_string())
+    ];
+
+    queue.enqueue(org_id, to_email, subject, template_path, &placeholders).await
 }
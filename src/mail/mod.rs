@@ -1,2 +1,3 @@
 pub mod sendmail;
 pub mod mails;
+pub mod queue;
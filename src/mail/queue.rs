@@ -0,0 +1,112 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+use crate::{config::Config, db::{DBClient, EmailJobExt}, models::EmailJob};
+
+use super::sendmail::{send_email, SendError};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+const LEASE_DURATION: Duration = Duration::from_secs(300);
+const MAX_BACKOFF: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Clone)]
+pub struct EmailQueue {
+    db_client: DBClient,
+    notify: Arc<Notify>,
+    max_attempts: i32,
+    retry_base: Duration,
+}
+
+impl EmailQueue {
+    pub fn new(db_client: DBClient, config: &Config) -> Self {
+        EmailQueue {
+            db_client,
+            notify: Arc::new(Notify::new()),
+            max_attempts: config.email_max_attempts,
+            retry_base: Duration::from_secs(config.email_retry_base_seconds),
+        }
+    }
+
+    pub async fn enqueue(
+        &self,
+        org_id: Uuid,
+        to_email: &str,
+        subject: &str,
+        template_path: &str,
+        placeholders: &[(String, String)]
+    ) -> Result<(), sqlx::Error> {
+        let placeholders = serde_json::to_string(placeholders)
+            .map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+
+        self.db_client
+            .enqueue_email_job(org_id, to_email, subject, template_path, &placeholders, self.max_attempts)
+            .await?;
+
+        self.wake();
+
+        Ok(())
+    }
+
+    pub fn wake(&self) {
+        self.notify.notify_one();
+    }
+
+    pub fn spawn_worker(&self) {
+        let queue = self.clone();
+        tokio::spawn(async move { queue.run().await });
+    }
+
+    async fn run(&self) {
+        loop {
+            let lease_until = Utc::now() + LEASE_DURATION;
+
+            match self.db_client.claim_email_job(lease_until).await {
+                Ok(Some(job)) => self.process(job).await,
+                Ok(None) => {
+                    tokio::select! {
+                        _ = self.notify.notified() => {}
+                        _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Failed to claim email job: {}", e);
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+            }
+        }
+    }
+
+    async fn process(&self, job: EmailJob) {
+        let result = match serde_json::from_str::<Vec<(String, String)>>(&job.placeholders) {
+            Ok(placeholders) => send_email(&job.to_email, &job.subject, &job.template_path, &placeholders).await,
+            Err(e) => Err(SendError::Permanent(e.to_string())),
+        };
+
+        let result = match result {
+            Ok(()) => self.db_client.mark_email_job_sent(job.id).await,
+            Err(SendError::Transient(error)) if job.attempts < job.max_attempts => {
+                eprintln!("Email job {} failed on attempt {}: {}", job.id, job.attempts, error);
+                let next_attempt_at = Utc::now() + self.backoff(job.attempts);
+                self.db_client.reschedule_email_job(job.id, next_attempt_at, &error).await
+            }
+            Err(e) => {
+                eprintln!("Email job {} moved to dead-letter queue: {}", job.id, e);
+                self.db_client.dead_letter_email_job(job.id, &e.to_string()).await
+            }
+        };
+
+        if let Err(e) = result {
+            eprintln!("Failed to update email job {}: {}", job.id, e);
+        }
+    }
+
+    fn backoff(&self, attempts: i32) -> Duration {
+        let exponent = attempts.saturating_sub(1).clamp(0, 16) as u32;
+        self.retry_base
+            .saturating_mul(2u32.saturating_pow(exponent))
+            .min(MAX_BACKOFF)
+    }
+}
@@ -1,4 +1,4 @@
-use std::{env, fs};
+use std::{env, fmt, fs};
 use lettre::{
     message::{header, SinglePart},
     transport::smtp::authentication::Credentials,
@@ -7,46 +7,72 @@ use lettre::{
     Transport
 };
 
+#[derive(Debug)]
+pub enum SendError {
+    Permanent(String),
+    Transient(String),
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::Permanent(message) => write!(f, "permanent failure: {}", message),
+            SendError::Transient(message) => write!(f, "transient failure: {}", message),
+        }
+    }
+}
+
+fn permanent(error: impl fmt::Display) -> SendError {
+    SendError::Permanent(error.to_string())
+}
+
+fn transient(error: impl fmt::Display) -> SendError {
+    SendError::Transient(error.to_string())
+}
+
 pub async fn send_email(
     to_email: &str,
     subject: &str,
     template_path: &str,
     placeholders: &[(String, String)]
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<(), SendError> {
     
-    let smtp_username = env::var("SMTP_USERNAME")?;
-    let smtp_password = env::var("SMTP_PASSWORD")?;
-    let smtp_server = env::var("SMTP_SERVER")?;
-    let smtp_port:u16 = env::var("SMTP_PORT")?.parse()?;
+    let smtp_username = env::var("SMTP_USERNAME").map_err(transient)?;
+    let smtp_password = env::var("SMTP_PASSWORD").map_err(transient)?;
+    let smtp_server = env::var("SMTP_SERVER").map_err(transient)?;
+    let smtp_port:u16 = env::var("SMTP_PORT").map_err(transient)?.parse().map_err(transient)?;
 
-    let mut html_template = fs::read_to_string(template_path)?;
+    let mut html_template = fs::read_to_string(template_path).map_err(permanent)?;
 
     for (key, value) in placeholders {
         html_template = html_template.replace(key, value);
     }
 
     let email = Message::builder()
-        .from(smtp_username.parse()?)
-        .to(to_email.parse()?)
+        .from(smtp_username.parse().map_err(permanent)?)
+        .to(to_email.parse().map_err(permanent)?)
         .subject(subject)
         .header(header::ContentType::TEXT_HTML)
         .singlepart(SinglePart::builder()
                     .header(header::ContentType::TEXT_HTML)
                     .body(html_template)
-        )?;
+        ).map_err(permanent)?;
     
     let creds = Credentials::new(smtp_username.clone(), smtp_password.clone());
-    let mailer = SmtpTransport::starttls_relay(&smtp_server)?
+    let mailer = SmtpTransport::starttls_relay(&smtp_server).map_err(transient)?
         .credentials(creds)
         .port(smtp_port)
         .build();
 
-    let result = mailer.send(&email);
+    mailer.send(&email).map_err(|e| {
+        if e.is_permanent() {
+            permanent(e)
+        } else {
+            transient(e)
+        }
+    })?;
 
-    match result {
-        Ok(_) => println!("Email sent successfully!"),
-        Err(e) => println!("Failed to send email: {:?}", e),
-    }
+    println!("Email sent successfully!");
 
     Ok(())
 }
@@ -8,22 +8,38 @@ mod middleware;
 mod mail;
 mod handler;
 mod routes;
+mod events;
+mod security_log;
+mod bootstrap;
+mod reminders;
+mod retention;
+#[cfg(test)]
+mod test_support;
 
-use std::sync::Arc;
+use std::{net::SocketAddr, str::FromStr, sync::Arc};
 
-use axum::http::{header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE}, HeaderValue, Method};
 use config::Config;
 use db::DBClient;
+use dtos::UserStatsDto;
 use dotenv::dotenv;
+use events::EventBus;
+use mail::queue::EmailQueue;
 use routes::create_router;
-use sqlx::postgres::PgPoolOptions;
-use tower_http::cors::CorsLayer;
+use security_log::SecurityLog;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
 use tracing_subscriber::filter::LevelFilter;
+use utils::{cache::TtlCache, mx::MxVerifier, rate_limit::RateLimiter, state_store};
 
 #[derive(Debug, Clone)]
 pub struct AppState{
     pub env: Config,
     pub db_client: DBClient,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub mx_verifier: Arc<MxVerifier>,
+    pub event_bus: EventBus,
+    pub email_queue: EmailQueue,
+    pub user_stats: Arc<TtlCache<uuid::Uuid, UserStatsDto>>,
+    pub security_log: SecurityLog,
 }
 
 #[tokio::main]
@@ -35,9 +51,23 @@ async fn main() {
     dotenv().ok();
 
     let config = Config::init();
+    error::set_error_detail(config.error_detail);
+    error::set_error_format(config.error_format);
+    dtos::set_name_length(config.name_length);
+    dtos::set_name_blocklist(config.name_blocklist.clone());
+    let connect_options = match PgConnectOptions::from_str(&config.database_url) {
+        Ok(options) => options.options([
+            ("statement_timeout", format!("{}s", config.db_statement_timeout_seconds)),
+        ]),
+        Err(err) => {
+            println!("Invalid DATABASE_URL: {:?}", err);
+            std::process::exit(1);
+        }
+    };
+
     let pool = match PgPoolOptions::new()
         .max_connections(10)
-        .connect(&config.database_url)
+        .connect_with(connect_options)
         .await 
     {
         Ok(pool) => {
@@ -50,27 +80,37 @@ async fn main() {
         }
     };
 
-    let cors = CorsLayer::new()
-        .allow_origin("http://localhost:3000".parse::<HeaderValue>().unwrap())
-        .allow_headers([AUTHORIZATION, ACCEPT, CONTENT_TYPE])
-        .allow_credentials(true)
-        .allow_methods([Method::GET, Method::POST, Method::PUT]);
+    let db_client = DBClient::new(pool, config.db_heavy_statement_timeout_seconds);
+    bootstrap::seed_admin(&config, &db_client).await;
+
+    let email_queue = EmailQueue::new(db_client.clone(), &config);
+    email_queue.spawn_worker();
+
+    retention::spawn_anonymizer(db_client.clone(), config.deleted_user_retention.clone());
+    reminders::spawn_verification_reminders(db_client.clone(), email_queue.clone(), &config);
 
-    let db_client = DBClient::new(pool);
     let app_state = AppState {
         env: config.clone(),
         db_client,
+        rate_limiter: Arc::new(RateLimiter::new(state_store::from_backend(&config.state_store))),
+        mx_verifier: Arc::new(MxVerifier::new()),
+        event_bus: EventBus::new(),
+        email_queue,
+        user_stats: Arc::new(TtlCache::new()),
+        security_log: SecurityLog::spawn(config.security_log.clone()).await,
     };
 
-    let app = create_router(Arc::new(app_state.clone())).layer(cors.clone());
+    let app = create_router(Arc::new(app_state.clone()));
 
-    println!("{}", format!("Server is running on http://localhost:{}", config.port));
+    println!("Server is running on http://localhost:{}", config.port);
 
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", config.port))
         .await
         .unwrap();
 
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+        .unwrap();
         
 }
 
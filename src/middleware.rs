@@ -1,81 +1,412 @@
-use std::sync::Arc;
+use std::{net::{IpAddr, SocketAddr}, sync::Arc, time::Duration};
+use async_trait::async_trait;
 use axum::{
-    extract::Request,
-    http::{header, StatusCode},
+    extract::{ConnectInfo, FromRequest, FromRequestParts, OriginalUri, Request},
+    http::{header, request::Parts, HeaderValue, Method, StatusCode},
     middleware::Next,
-    response::IntoResponse,
-    Extension
+    response::{IntoResponse, Response},
+    Extension,
+    Json
 };
 use axum_extra::extract::cookie::CookieJar;
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::{
-    db::UserExt,
-    error::{ErrorMessage, HttpError},
-    models::{UserRole, User},
-    utils::token,
+    config::{AuthMethod, TokenBinding},
+    db::{ApiKeyExt, OrganizationExt, SessionExt, UserExt},
+    error::{self, ErrorMessage, HttpError},
+    handler::audit::record_event,
+    models::{AccountStatus, AuditEventType, Organization, SessionBinding, UserRole, User},
+    utils::{ip::{client_ip, IpMasking}, token},
     AppState
 };
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct JWTAuthMiddleware {
     pub user: User,
+    pub session_id: Option<uuid::Uuid>,
+    pub impersonated_by: Option<uuid::Uuid>,
+    pub token_expires_at: Option<DateTime<Utc>>,
+    pub session_expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+pub const TENANT_HEADER: &str = "x-organization";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tenant(pub uuid::Uuid);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = HttpError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())
+            .ok_or_else(|| HttpError::server_error("Client address is unavailable".to_string()))?;
+
+        let trusted_proxies = parts
+            .extensions
+            .get::<Arc<AppState>>()
+            .map(|app_state| app_state.env.trusted_proxies.as_slice())
+            .unwrap_or_default();
+
+        let forwarded_for = parts
+            .headers
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok());
+
+        Ok(ClientIp(client_ip(peer, forwarded_for, trusted_proxies)))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RequestMetadata {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub org_id: Option<uuid::Uuid>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for RequestMetadata
+where
+    S: Send + Sync,
+{
+    type Rejection = HttpError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let ip_address = ClientIp::from_request_parts(parts, state)
+            .await
+            .ok()
+            .map(|ClientIp(ip)| ip.to_string());
+
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_owned());
+
+        let org_id = parts
+            .extensions
+            .get::<Tenant>()
+            .map(|Tenant(org_id)| *org_id);
+
+        Ok(RequestMetadata {
+            ip_address,
+            user_agent,
+            org_id,
+        })
+    }
+}
+
+impl RequestMetadata {
+    // Networks are compared at /24 (IPv4) or /48 (IPv6) so a session survives
+    // address changes within the same provider network.
+    pub fn binding(&self) -> SessionBinding {
+        SessionBinding {
+            user_agent_hash: self.user_agent.as_deref().map(token::hash_token),
+            network_hash: self.ip_address
+                .as_deref()
+                .and_then(|ip| IpMasking::Partial.apply(ip))
+                .map(|network| token::hash_token(&network)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StrictJson<T>(pub T);
+
+#[async_trait]
+impl<S, T> FromRequest<S> for StrictJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = HttpError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        Json::<T>::from_request(req, state)
+            .await
+            .map(|Json(value)| StrictJson(value))
+            .map_err(|rejection| HttpError::bad_request(rejection.body_text()))
+    }
+}
+
+pub async fn tenant(
+    Extension(app_state): Extension<Arc<AppState>>,
+    mut req: Request,
+    next: Next
+) -> Result<impl IntoResponse, HttpError> {
+    let slug = req.headers()
+        .get(TENANT_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_lowercase())
+        .filter(|value| !value.is_empty())
+        .or_else(|| tenant_subdomain(&req, app_state.env.tenant_base_domain.as_deref()));
+
+    let org_id = match slug {
+        Some(slug) => app_state.db_client
+            .get_organization_by_slug(&slug)
+            .await
+            .map_err(HttpError::database)?
+            .ok_or_else(|| HttpError::not_found(ErrorMessage::OrganizationNotFound))?
+            .id,
+        None => Organization::DEFAULT_ID,
+    };
+
+    req.extensions_mut().insert(Tenant(org_id));
+
+    Ok(next.run(req).await)
+}
+
+fn tenant_subdomain(req: &Request, base_domain: Option<&str>) -> Option<String> {
+    let host = req.headers()
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())?
+        .split(':')
+        .next()?
+        .to_lowercase();
+
+    host.strip_suffix(base_domain?)?
+        .strip_suffix('.')
+        .filter(|slug| !slug.is_empty() && !slug.contains('.'))
+        .map(|slug| slug.to_string())
 }
 
 pub async fn auth(
     cookie_jar: CookieJar,
     Extension(app_state): Extension<Arc<AppState>>,
+    Extension(tenant): Extension<Tenant>,
     mut req: Request,
     next: Next
 ) -> Result<impl IntoResponse, HttpError> {
-    let cookies = cookie_jar
+    let cookie_token = cookie_jar
         .get("token")
-        .map(|cookie| cookie.value().to_string())
+        .map(|cookie| cookie.value().to_string());
+
+    if cookie_token.is_some() && app_state.env.is_prod() && !is_https(&req, &app_state) {
+        return Err(HttpError::new(ErrorMessage::InsecureTransport, StatusCode::FORBIDDEN));
+    }
+
+    let cookies = cookie_token
         .or_else(|| {
             req.headers()
                 .get(header::AUTHORIZATION)
                 .and_then(|auth_header| auth_header.to_str().ok())
                 .and_then(|auth_value| {
-                    if auth_value.starts_with("Bearer ") {
-                        Some(auth_value[7..].to_owned())
-                    } else {
-                        None
-                    }
+                    auth_value
+                        .strip_prefix("Bearer ")
+                        .map(|token| token.to_owned())
                 })
         });
 
+    if cookies.is_none() {
+        if let Some(api_key) = req.headers().get("x-api-key").and_then(|value| value.to_str().ok()) {
+            if !app_state.env.auth_method_enabled(AuthMethod::ApiKey) {
+                return Err(HttpError::new(ErrorMessage::AuthMethodDisabled(AuthMethod::ApiKey.to_str().to_string()), StatusCode::FORBIDDEN));
+            }
+
+            let user = authenticate_api_key(&app_state, api_key).await?;
+            ensure_tenant(&user, tenant)?;
+            ensure_writable(&user, &req, &app_state)?;
+
+            req.extensions_mut().insert(JWTAuthMiddleware {
+                user,
+                session_id: None,
+                impersonated_by: None,
+                token_expires_at: None,
+                session_expires_at: None,
+            });
+
+            return Ok(next.run(req).await);
+        }
+    }
+
     let token = cookies.ok_or_else(|| {
-        HttpError::unauthorized(ErrorMessage::TokenNotProvided.to_string())
+        HttpError::unauthorized(ErrorMessage::TokenNotProvided)
     })?;
-    let token_details = match token::decode_token(token, app_state.env.jwt_secret.as_bytes()) {
-        Ok(token_details) => token_details,
-        Err(_) => {
-            return Err(HttpError::unauthorized(ErrorMessage::InvalidToken.to_string()));
+    let token_details = match token::decode_token(token, &app_state.env.jwt_keys) {
+        Ok(token_details) if token_details.purpose.is_none() => token_details,
+        _ => {
+            return Err(HttpError::unauthorized(ErrorMessage::InvalidToken));
         }
     };
 
-    let user_id = uuid::Uuid::parse_str(&token_details.to_string())
+    let user_id = uuid::Uuid::parse_str(&token_details.sub)
         .map_err(|_| {
-            HttpError::unauthorized(ErrorMessage::InvalidToken.to_string())
+            HttpError::unauthorized(ErrorMessage::InvalidToken)
         })?;
 
     let user = app_state.db_client.get_user(Some(user_id), None, None, None)
         .await
         .map_err(|_| {
-            HttpError::unauthorized(ErrorMessage::UserNoLongerExist.to_string())
+            HttpError::unauthorized(ErrorMessage::UserNoLongerExist)
         })?;
 
     let user = user.ok_or_else(|| {
-        HttpError::unauthorized(ErrorMessage::UserNoLongerExist.to_string())
+        HttpError::unauthorized(ErrorMessage::UserNoLongerExist)
     })?;
 
+    if let Some(valid_after) = user.tokens_valid_after {
+        if (token_details.iat as i64) < valid_after.timestamp() {
+            return Err(HttpError::unauthorized(ErrorMessage::InvalidToken));
+        }
+    }
+
+    // Tokens minted before organizations existed carry no org claim and fall back to the user's.
+    if token_details.org.as_deref().is_some_and(|org| org != user.org_id.to_string()) {
+        return Err(HttpError::unauthorized(ErrorMessage::InvalidToken));
+    }
+
+    ensure_tenant(&user, tenant)?;
+    ensure_active(&user)?;
+    ensure_writable(&user, &req, &app_state)?;
+
+    let (session_id, session_expires_at) = match token_details.sid.as_deref() {
+        Some(sid) => {
+            let session_id = uuid::Uuid::parse_str(sid)
+                .map_err(|_| HttpError::unauthorized(ErrorMessage::InvalidToken))?;
+
+            let (expires_at, binding) = app_state.db_client
+                .touch_session(session_id, user.id)
+                .await
+                .map_err(HttpError::database)?
+                .ok_or_else(|| HttpError::unauthorized(ErrorMessage::InvalidToken))?;
+
+            if app_state.env.token_binding != TokenBinding::Off {
+                let (mut parts, body) = req.into_parts();
+                let metadata = RequestMetadata::from_request_parts(&mut parts, &()).await?;
+                req = Request::from_parts(parts, body);
+
+                if let Some(mismatch) = binding.mismatch(&metadata.binding(), app_state.env.token_binding) {
+                    app_state.db_client
+                        .revoke_session(session_id, user.id)
+                        .await
+                        .map_err(HttpError::database)?;
+
+                    let details = format!("session={} mismatch={}", session_id, mismatch);
+                    record_event(&app_state, Some(user.id), AuditEventType::SessionBindingMismatch, &metadata, false, Some(&details)).await;
+
+                    return Err(HttpError::unauthorized(ErrorMessage::SessionBindingMismatch));
+                }
+            }
+
+            (Some(session_id), Some(expires_at))
+        }
+        None => (None, None),
+    };
+
+    let impersonated_by = match token_details.impersonated_by.as_deref() {
+        Some(admin_id) => {
+            let admin_id = uuid::Uuid::parse_str(admin_id)
+                .map_err(|_| HttpError::unauthorized(ErrorMessage::InvalidToken))?;
+
+            if session_id.is_none() {
+                return Err(HttpError::unauthorized(ErrorMessage::InvalidToken));
+            }
+
+            Some(admin_id)
+        }
+        None => None,
+    };
+
+    if let Some(admin_id) = impersonated_by {
+        let (mut parts, body) = req.into_parts();
+        let metadata = RequestMetadata::from_request_parts(&mut parts, &()).await?;
+        let details = format!("admin={} {} {}", admin_id, parts.method, parts.uri.path());
+
+        record_event(&app_state, Some(user.id), AuditEventType::ImpersonatedRequest, &metadata, true, Some(&details)).await;
+
+        req = Request::from_parts(parts, body);
+    }
+
     req.extensions_mut().insert(JWTAuthMiddleware {
         user: user.clone(),
+        session_id,
+        impersonated_by,
+        token_expires_at: DateTime::from_timestamp(token_details.exp as i64, 0),
+        session_expires_at,
     });
 
     Ok(next.run(req).await)
 }
 
+async fn authenticate_api_key(app_state: &AppState, api_key: &str) -> Result<User, HttpError> {
+    let user_id = app_state.db_client
+        .use_api_key(&token::hash_token(api_key.trim()))
+        .await
+        .map_err(HttpError::database)?
+        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::InvalidToken))?;
+
+    let user = app_state.db_client.get_user(Some(user_id), None, None, None)
+        .await
+        .map_err(HttpError::database)?
+        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::UserNoLongerExist))?;
+
+    ensure_active(&user)?;
+
+    Ok(user)
+}
+
+fn ensure_tenant(user: &User, tenant: Tenant) -> Result<(), HttpError> {
+    if Tenant(user.org_id) != tenant {
+        return Err(HttpError::new(ErrorMessage::OrganizationMismatch, StatusCode::FORBIDDEN));
+    }
+
+    Ok(())
+}
+
+// Frozen accounts keep read access during a review, these stay open so an
+// admin can still end an impersonation and the user can re-authenticate.
+const FROZEN_ALLOWED_ROUTES: &[&str] = &[
+    "/api/auth/reauth",
+    "/api/users/me/impersonation/end",
+];
+
+fn ensure_writable(user: &User, req: &Request, app_state: &AppState) -> Result<(), HttpError> {
+    if !user.frozen || req.method().is_safe() {
+        return Ok(());
+    }
+
+    let path = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|OriginalUri(uri)| uri.path())
+        .unwrap_or_else(|| req.uri().path());
+    let path = path.strip_prefix(app_state.env.base_path.as_str()).unwrap_or(path);
+
+    if FROZEN_ALLOWED_ROUTES.contains(&path) {
+        return Ok(());
+    }
+
+    Err(HttpError::new(ErrorMessage::AccountFrozen(user.frozen_reason.clone()), StatusCode::FORBIDDEN))
+}
+
+fn is_https(req: &Request, app_state: &AppState) -> bool {
+    let peer_trusted = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| {
+            app_state.env.trusted_proxies.iter().any(|network| network.contains(addr.ip()))
+        })
+        .unwrap_or(false);
+
+    peer_trusted && req
+        .headers()
+        .get("x-forwarded-proto")
+        .and_then(|value| value.to_str().ok())
+        .map(|proto| proto.eq_ignore_ascii_case("https"))
+        .unwrap_or(false)
+}
+
 pub async fn role_check(
     Extension(_app_state): Extension<Arc<AppState>>,
     req: Request,
@@ -86,12 +417,465 @@ pub async fn role_check(
         .extensions()
         .get::<JWTAuthMiddleware>()
         .ok_or_else(|| {
-            HttpError::unauthorized(ErrorMessage::UserNotAuthenticated.to_string())
+            HttpError::unauthorized(ErrorMessage::UserNotAuthenticated)
         })?;
 
     if !required_roles.contains(&user.user.role) {
-        return Err(HttpError::new(ErrorMessage::PermissionDenied.to_string(), StatusCode::FORBIDDEN));
+        return Err(HttpError::new(ErrorMessage::PermissionDenied, StatusCode::FORBIDDEN));
+    }
+
+    Ok(next.run(req).await)
+}
+
+pub async fn auth_method_check(
+    Extension(app_state): Extension<Arc<AppState>>,
+    req: Request,
+    next: Next,
+    method: AuthMethod
+) -> Result<impl IntoResponse, HttpError> {
+    if !app_state.env.auth_method_enabled(method) {
+        return Err(HttpError::not_found(ErrorMessage::AuthMethodDisabled(method.to_str().to_string())));
+    }
+
+    Ok(next.run(req).await)
+}
+
+pub async fn verified_check(
+    Extension(app_state): Extension<Arc<AppState>>,
+    req: Request,
+    next: Next
+) -> Result<impl IntoResponse, HttpError> {
+    let user = req
+        .extensions()
+        .get::<JWTAuthMiddleware>()
+        .ok_or_else(|| {
+            HttpError::unauthorized(ErrorMessage::UserNotAuthenticated)
+        })?;
+
+    if user.user.verification_required(app_state.env.verification_grace_days) {
+        return Err(HttpError::new(ErrorMessage::VerificationRequired, StatusCode::FORBIDDEN));
+    }
+
+    Ok(next.run(req).await)
+}
+
+pub async fn terms_check(
+    Extension(app_state): Extension<Arc<AppState>>,
+    req: Request,
+    next: Next
+) -> Result<impl IntoResponse, HttpError> {
+    let user = req
+        .extensions()
+        .get::<JWTAuthMiddleware>()
+        .ok_or_else(|| {
+            HttpError::unauthorized(ErrorMessage::UserNotAuthenticated)
+        })?;
+
+    if user.user.terms_acceptance_required(app_state.env.terms_version.as_deref()) {
+        return Err(HttpError::new(ErrorMessage::TermsAcceptanceRequired, StatusCode::FORBIDDEN));
     }
 
     Ok(next.run(req).await)
 }
+
+pub async fn elevation_check(
+    Extension(app_state): Extension<Arc<AppState>>,
+    req: Request,
+    next: Next
+) -> Result<impl IntoResponse, HttpError> {
+    let user = req
+        .extensions()
+        .get::<JWTAuthMiddleware>()
+        .ok_or_else(|| {
+            HttpError::unauthorized(ErrorMessage::UserNotAuthenticated)
+        })?;
+
+    let Some(session_id) = user.session_id.filter(|_| user.impersonated_by.is_none()) else {
+        return Err(HttpError::new(ErrorMessage::ReauthenticationUnavailable, StatusCode::FORBIDDEN));
+    };
+
+    let elevated = app_state.db_client
+        .is_session_elevated(session_id, user.user.id)
+        .await
+        .map_err(HttpError::database)?;
+
+    if !elevated {
+        return Err(HttpError::new(ErrorMessage::ReauthenticationRequired, StatusCode::FORBIDDEN));
+    }
+
+    Ok(next.run(req).await)
+}
+
+// Gateways call service endpoints with a shared secret rather than a user
+// session, the endpoints answer 404 until a secret is configured.
+pub async fn service_auth(
+    Extension(app_state): Extension<Arc<AppState>>,
+    req: Request,
+    next: Next
+) -> Result<impl IntoResponse, HttpError> {
+    let Some(secret) = app_state.env.introspection_secret.as_deref() else {
+        return Err(HttpError::not_found(ErrorMessage::IntrospectionDisabled));
+    };
+
+    let authorized = req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|provided| token::hash_token(provided) == token::hash_token(secret));
+
+    if !authorized {
+        return Err(HttpError::unauthorized(ErrorMessage::InvalidServiceCredentials));
+    }
+
+    Ok(next.run(req).await)
+}
+
+pub fn ensure_active(user: &User) -> Result<(), HttpError> {
+    let message = match user.status {
+        AccountStatus::Active => return Ok(()),
+        AccountStatus::PendingApproval => ErrorMessage::AccountPendingApproval,
+        AccountStatus::Suspended => ErrorMessage::AccountSuspended(user.status_reason.clone()),
+        AccountStatus::Deactivated => ErrorMessage::AccountDeactivated,
+    };
+
+    Err(HttpError::new(message, StatusCode::FORBIDDEN))
+}
+
+const HEAVY_ROUTES: &[&str] = &[
+    "/api/users/roles/bulk",
+    "/api/users/merge",
+];
+
+const USER_EXPORT_ROUTE: &str = "/api/users/users";
+
+// The user list only counts as heavy when it is exported, which it decides the
+// same way the handler does: an explicit format wins over the Accept header.
+fn is_heavy_route(req: &Request, base_path: &str) -> bool {
+    let uri = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|OriginalUri(uri)| uri)
+        .unwrap_or_else(|| req.uri());
+    let path = uri.path().strip_prefix(base_path).unwrap_or(uri.path());
+
+    if HEAVY_ROUTES.contains(&path) {
+        return true;
+    }
+
+    if path != USER_EXPORT_ROUTE || req.method() != Method::GET {
+        return false;
+    }
+
+    let format = uri.query().and_then(|query| {
+        url::form_urlencoded::parse(query.as_bytes())
+            .find(|(key, _)| key == "format")
+            .map(|(_, value)| value.into_owned())
+    });
+
+    match format {
+        Some(format) => format == "csv",
+        None => req.headers()
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.split(',').any(|media| media.trim().starts_with("text/csv"))),
+    }
+}
+
+pub async fn request_timeout(
+    Extension(app_state): Extension<Arc<AppState>>,
+    req: Request,
+    next: Next
+) -> Result<Response, HttpError> {
+    let timeout_seconds = if is_heavy_route(&req, &app_state.env.base_path) {
+        app_state.env.heavy_request_timeout_seconds
+    } else {
+        app_state.env.request_timeout_seconds
+    };
+
+    tokio::time::timeout(Duration::from_secs(timeout_seconds), next.run(req))
+        .await
+        .map_err(|_| HttpError::new(ErrorMessage::RequestTimeout, StatusCode::GATEWAY_TIMEOUT))
+}
+
+pub async fn method_not_allowed(response: Response) -> Response {
+    if response.status() != StatusCode::METHOD_NOT_ALLOWED {
+        return response;
+    }
+
+    let allow = response.headers().get(header::ALLOW).cloned();
+
+    let mut response = HttpError::new(ErrorMessage::MethodNotAllowed, StatusCode::METHOD_NOT_ALLOWED)
+        .into_response();
+
+    if let Some(allow) = allow {
+        response.headers_mut().insert(header::ALLOW, allow);
+    }
+
+    response
+}
+
+pub async fn security_headers(
+    Extension(app_state): Extension<Arc<AppState>>,
+    req: Request,
+    next: Next
+) -> Response {
+    let mut response = next.run(req).await;
+    let config = &app_state.env.security_headers;
+
+    if !config.enabled {
+        return response;
+    }
+
+    let headers = response.headers_mut();
+
+    if let Some(hsts) = &config.strict_transport_security {
+        headers.entry(header::STRICT_TRANSPORT_SECURITY).or_insert_with(|| hsts.clone());
+    }
+
+    headers.entry(header::X_CONTENT_TYPE_OPTIONS).or_insert(HeaderValue::from_static("nosniff"));
+    headers.entry(header::X_FRAME_OPTIONS).or_insert_with(|| config.frame_options.clone());
+    headers.entry(header::REFERRER_POLICY).or_insert_with(|| config.referrer_policy.clone());
+    headers.entry(header::CONTENT_SECURITY_POLICY).or_insert_with(|| config.content_security_policy.clone());
+
+    response
+}
+
+pub async fn error_instance(req: Request, next: Next) -> Response {
+    let path = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|OriginalUri(uri)| uri.path().to_string())
+        .unwrap_or_else(|| req.uri().path().to_string());
+
+    error::with_request_path(path, next.run(req)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Uri};
+
+    use super::*;
+    use crate::test_support;
+
+    fn nested_request(method: Method, original: &str, accept: Option<&str>) -> Request {
+        let original: Uri = original.parse().unwrap();
+        let nested = original.path().trim_start_matches("/v1").trim_start_matches("/api").to_string();
+
+        let mut builder = Request::builder().method(method).uri(nested);
+        if let Some(accept) = accept {
+            builder = builder.header(header::ACCEPT, accept);
+        }
+
+        let mut req = builder.body(Body::empty()).unwrap();
+        req.extensions_mut().insert(OriginalUri(original));
+        req
+    }
+
+    #[test]
+    fn heavy_routes_match_the_original_path() {
+        assert!(is_heavy_route(&nested_request(Method::PUT, "/api/users/roles/bulk", None), ""));
+        assert!(is_heavy_route(&nested_request(Method::POST, "/v1/api/users/merge", None), "/v1"));
+        assert!(!is_heavy_route(&nested_request(Method::GET, "/api/users/me", None), ""));
+    }
+
+    #[test]
+    fn user_export_is_heavy_only_as_csv() {
+        assert!(is_heavy_route(&nested_request(Method::GET, "/api/users/users?format=csv", None), ""));
+        assert!(is_heavy_route(&nested_request(Method::GET, "/api/users/users", Some("text/csv")), ""));
+        assert!(!is_heavy_route(&nested_request(Method::GET, "/api/users/users?format=json", Some("text/csv")), ""));
+        assert!(!is_heavy_route(&nested_request(Method::GET, "/api/users/users", None), ""));
+    }
+
+    async fn two_tenants(pool: sqlx::Pool<sqlx::Postgres>) -> (axum::Router, String, User) {
+        let app_state = test_support::app_state(pool, test_support::config()).await;
+        let app = test_support::router(&app_state);
+
+        let org_a = test_support::create_organization(&app_state, "org-a").await;
+        let org_b = test_support::create_organization(&app_state, "org-b").await;
+        test_support::create_user(&app_state, org_a, "admin@org-a.example", UserRole::Admin).await;
+        let victim = test_support::create_user(&app_state, org_b, "member@org-b.example", UserRole::User).await;
+
+        let token = test_support::login(&app, Some("org-a"), "admin@org-a.example").await;
+        (app, token, victim)
+    }
+
+    fn authorized(method: Method, uri: &str, slug: &str, token: &str, body: serde_json::Value) -> Request {
+        let mut req = test_support::json_request(method, uri, body);
+        req.headers_mut().insert(TENANT_HEADER, slug.parse().unwrap());
+        req.headers_mut().insert(header::AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
+        req
+    }
+
+    #[sqlx::test]
+    async fn org_admin_cannot_read_another_orgs_user(pool: sqlx::Pool<sqlx::Postgres>) {
+        test_support::block_on(async {
+            let (app, token, victim) = two_tenants(pool).await;
+            let uri = format!("/api/users/users/{}", victim.id);
+
+            let (status, _) = test_support::send(&app, authorized(Method::GET, &uri, "org-a", &token, serde_json::Value::Null)).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+
+            let (status, body) = test_support::send(&app, authorized(Method::GET, &uri, "org-b", &token, serde_json::Value::Null)).await;
+            assert_eq!(status, StatusCode::FORBIDDEN);
+            assert_eq!(body["code"], ErrorMessage::OrganizationMismatch.code());
+
+            let (status, body) = test_support::send(&app, authorized(Method::GET, "/api/users/users", "org-a", &token, serde_json::Value::Null)).await;
+            assert_eq!(status, StatusCode::OK);
+            let listed: Vec<&str> = body["data"].as_array().unwrap().iter().filter_map(|user| user["email"].as_str()).collect();
+            assert_eq!(listed, vec!["admin@org-a.example"]);
+        });
+    }
+
+    #[sqlx::test]
+    async fn org_admin_cannot_modify_another_orgs_user(pool: sqlx::Pool<sqlx::Postgres>) {
+        test_support::block_on(async {
+            let (app, token, victim) = two_tenants(pool.clone()).await;
+            let uri = format!("/api/users/users/{}/status", victim.id);
+            let body = serde_json::json!({ "status": "suspended", "reason": "cross tenant" });
+
+            let (status, _) = test_support::send(&app, authorized(Method::PUT, &uri, "org-a", &token, body.clone())).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+
+            let (status, _) = test_support::send(&app, authorized(Method::PUT, &uri, "org-b", &token, body)).await;
+            assert_eq!(status, StatusCode::FORBIDDEN);
+
+            let victim_status: AccountStatus = sqlx::query_scalar("SELECT status FROM users WHERE id = $1")
+                .bind(victim.id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            assert_eq!(victim_status, AccountStatus::Active);
+        });
+    }
+
+    #[sqlx::test]
+    async fn org_admin_only_streams_own_orgs_events(pool: sqlx::Pool<sqlx::Postgres>) {
+        use futures_util::StreamExt;
+        use tower::ServiceExt;
+
+        test_support::block_on(async {
+            let (app, token, victim) = two_tenants(pool).await;
+
+            let mut req = authorized(Method::GET, "/api/admin/events", "org-a", &token, serde_json::Value::Null);
+            req.extensions_mut().insert(ConnectInfo(test_support::PEER.parse::<SocketAddr>().unwrap()));
+            let response = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let mut events = response.into_body().into_data_stream();
+
+            for (slug, email, password) in [
+                ("org-b", victim.email.as_str(), "wrong password"),
+                ("org-b", "nobody@org-b.example", test_support::PASSWORD),
+                ("org-a", "nobody@org-a.example", test_support::PASSWORD),
+            ] {
+                let mut req = test_support::json_request(Method::POST, "/api/auth/login", serde_json::json!({
+                    "email": email,
+                    "password": password,
+                }));
+                req.headers_mut().insert(TENANT_HEADER, slug.parse().unwrap());
+                let (status, _) = test_support::send(&app, req).await;
+                assert_eq!(status, StatusCode::BAD_REQUEST);
+            }
+
+            let frame = tokio::time::timeout(Duration::from_secs(5), events.next())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            let frame = String::from_utf8(frame.to_vec()).unwrap();
+
+            assert!(frame.contains("nobody@org-a.example"), "{}", frame);
+            assert!(!frame.contains(&victim.id.to_string()), "{}", frame);
+        });
+    }
+
+    #[sqlx::test]
+    async fn org_admin_cannot_see_or_retry_another_orgs_dead_letters(pool: sqlx::Pool<sqlx::Postgres>) {
+        test_support::block_on(async {
+            let (app, token, victim) = two_tenants(pool.clone()).await;
+
+            let job_id: uuid::Uuid = sqlx::query_scalar(
+                "INSERT INTO email_jobs (org_id, to_email, subject, template_path, placeholders, status, max_attempts) VALUES ($1, $2, 'Subject', 'template', '[]', 'dead', 1) RETURNING id"
+            )
+                .bind(victim.org_id)
+                .bind(&victim.email)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+
+            let (status, body) = test_support::send(&app, authorized(Method::GET, "/api/admin/emails/dead-letter", "org-a", &token, serde_json::Value::Null)).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body["total"], 0);
+            assert_eq!(body["data"], serde_json::json!([]));
+
+            let uri = format!("/api/admin/emails/{}/retry", job_id);
+            let (status, _) = test_support::send(&app, authorized(Method::POST, &uri, "org-a", &token, serde_json::Value::Null)).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+
+            let job_status: String = sqlx::query_scalar("SELECT status FROM email_jobs WHERE id = $1")
+                .bind(job_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            assert_eq!(job_status, "dead");
+        });
+    }
+
+    #[sqlx::test]
+    async fn merged_away_api_key_authenticates_as_the_target(pool: sqlx::Pool<sqlx::Postgres>) {
+        test_support::block_on(async {
+            let app_state = test_support::app_state(pool, test_support::config()).await;
+            let app = test_support::router(&app_state);
+
+            let default_org = crate::models::Organization::DEFAULT_ID;
+            test_support::create_user(&app_state, default_org, "admin@example.com", UserRole::Admin).await;
+            let source = test_support::create_user(&app_state, default_org, "old@example.com", UserRole::User).await;
+            let target = test_support::create_user(&app_state, default_org, "new@example.com", UserRole::User).await;
+
+            let key = token::generate_api_key();
+            app_state.db_client
+                .create_api_key(source.id, "ci", &key[..token::API_KEY_PREFIX_LEN], &token::hash_token(&key))
+                .await
+                .unwrap();
+
+            let admin_token = test_support::login(&app, None, "admin@example.com").await;
+            let mut req = test_support::json_request(Method::POST, "/api/users/merge", serde_json::json!({
+                "source_id": source.id,
+                "target_id": target.id,
+            }));
+            req.headers_mut().insert(header::AUTHORIZATION, format!("Bearer {}", admin_token).parse().unwrap());
+            let (status, body) = test_support::send(&app, req).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+
+            let mut req = test_support::json_request(Method::GET, "/api/users/me", serde_json::Value::Null);
+            req.headers_mut().insert("x-api-key", key.parse().unwrap());
+            let (status, body) = test_support::send(&app, req).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+            assert_eq!(body["data"]["user"]["id"], target.id.to_string());
+        });
+    }
+
+    #[sqlx::test]
+    async fn suspended_user_is_refused_with_its_code(pool: sqlx::Pool<sqlx::Postgres>) {
+        test_support::block_on(async {
+            let app_state = test_support::app_state(pool.clone(), test_support::config()).await;
+            let app = test_support::router(&app_state);
+
+            let user = test_support::create_user(&app_state, crate::models::Organization::DEFAULT_ID, "member@example.com", UserRole::User).await;
+            let token = test_support::login(&app, None, "member@example.com").await;
+
+            sqlx::query("UPDATE users SET status = $2 WHERE id = $1")
+                .bind(user.id)
+                .bind(AccountStatus::Suspended)
+                .execute(&pool)
+                .await
+                .unwrap();
+
+            let mut req = test_support::json_request(Method::GET, "/api/users/me", serde_json::Value::Null);
+            req.headers_mut().insert(header::AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
+            let (status, body) = test_support::send(&app, req).await;
+
+            assert_eq!(status, StatusCode::FORBIDDEN);
+            assert_eq!(body["code"], "ACCOUNT_SUSPENDED");
+        });
+    }
+}
@@ -1,6 +1,10 @@
+use std::str::FromStr;
+
 use chrono::prelude::*;
 use serde::{Serialize, Deserialize};
 
+use crate::config::TokenBinding;
+
 #[derive(Debug, Serialize, Deserialize, Clone, Copy,sqlx::Type, PartialEq)]
 #[sqlx(type_name = "user_role", rename_all = "lowercase")]
 pub enum UserRole {
@@ -9,12 +13,67 @@ pub enum UserRole {
 }
 
 impl UserRole {
-    pub fn to_str(&self) -> &str {
+    pub fn to_str(self) -> &'static str {
         match self {
             UserRole::Admin => "admin",
             UserRole::User => "user",
         }
     }
+
+    // Numeric privilege for clients that compare roles, higher outranks lower.
+    // The gaps leave room for roles in between without renumbering.
+    pub fn level(self) -> i32 {
+        match self {
+            UserRole::Admin => 100,
+            UserRole::User => 10,
+        }
+    }
+}
+
+impl FromStr for UserRole {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "admin" => Ok(UserRole::Admin),
+            "user" => Ok(UserRole::User),
+            _ => Err(format!("Unknown role '{}', expected admin or user", value)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, sqlx::Type, PartialEq)]
+#[sqlx(type_name = "account_status", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum AccountStatus {
+    PendingApproval,
+    Active,
+    Suspended,
+    Deactivated,
+}
+
+impl AccountStatus {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            AccountStatus::PendingApproval => "pending_approval",
+            AccountStatus::Active => "active",
+            AccountStatus::Suspended => "suspended",
+            AccountStatus::Deactivated => "deactivated",
+        }
+    }
+
+    pub fn can_transition_to(self, next: AccountStatus) -> bool {
+        matches!(
+            (self, next),
+            (AccountStatus::PendingApproval, AccountStatus::Active)
+                | (AccountStatus::PendingApproval, AccountStatus::Deactivated)
+                | (AccountStatus::Active, AccountStatus::Suspended)
+                | (AccountStatus::Active, AccountStatus::Deactivated)
+                | (AccountStatus::Suspended, AccountStatus::Active)
+                | (AccountStatus::Suspended, AccountStatus::Deactivated)
+                | (AccountStatus::Deactivated, AccountStatus::Active)
+        )
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, sqlx::FromRow, sqlx::Type, Clone)]
@@ -27,8 +86,419 @@ pub struct User {
     pub verified: bool,
     pub verification_token: Option<String>,
     pub token_expires_at: Option<DateTime<Utc>>,
+    pub password_changed_at: Option<DateTime<Utc>>,
+    pub locale: Option<String>,
+    pub avatar_url: Option<String>,
+    pub display_name: Option<String>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub tokens_valid_after: Option<DateTime<Utc>>,
+    pub password_pepper_id: Option<String>,
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+    pub status: AccountStatus,
+    pub status_reason: Option<String>,
+    pub max_sessions: Option<i32>,
+    pub org_id: uuid::Uuid,
+    pub notification_preferences: sqlx::types::Json<NotificationPreferences>,
+    #[serde(rename="lastLoginAt")]
+    pub last_login_at: Option<DateTime<Utc>>,
+    #[serde(rename="acceptedTermsVersion")]
+    pub accepted_terms_version: Option<String>,
+    #[serde(rename="acceptedTermsAt")]
+    pub accepted_terms_at: Option<DateTime<Utc>>,
+    pub frozen: bool,
+    #[serde(rename="frozenReason")]
+    pub frozen_reason: Option<String>,
     #[serde(rename="createdAt")]
     pub created_at: Option<DateTime<Utc>>,
     #[serde(rename="updatedAt")]
     pub updated_at: Option<DateTime<Utc>>,
 }
+
+impl User {
+    pub fn display_name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.name)
+    }
+
+    pub fn password_expires_at(&self, max_age_days: Option<i64>) -> Option<DateTime<Utc>> {
+        let max_age_days = max_age_days?;
+        let changed_at = self.password_changed_at.or(self.created_at)?;
+        Some(changed_at + chrono::Duration::days(max_age_days))
+    }
+
+    pub fn verification_deadline(&self, grace_days: Option<i64>) -> Option<DateTime<Utc>> {
+        if self.verified {
+            return None;
+        }
+        let grace_days = grace_days?;
+        Some(self.created_at? + chrono::Duration::days(grace_days))
+    }
+
+    pub fn verification_required(&self, grace_days: Option<i64>) -> bool {
+        self.verification_deadline(grace_days)
+            .map(|deadline| Utc::now() > deadline)
+            .unwrap_or(false)
+    }
+
+    pub fn terms_acceptance_required(&self, current_version: Option<&str>) -> bool {
+        current_version.is_some_and(|version| self.accepted_terms_version.as_deref() != Some(version))
+    }
+
+    pub fn password_change_allowed_at(&self, min_age_hours: Option<i64>) -> Option<DateTime<Utc>> {
+        let min_age_hours = min_age_hours?;
+        let changed_at = self.password_changed_at?;
+        Some(changed_at + chrono::Duration::hours(min_age_hours))
+            .filter(|allowed_at| Utc::now() < *allowed_at)
+    }
+
+    pub fn password_expired(&self, max_age_days: Option<i64>) -> bool {
+        self.password_expires_at(max_age_days)
+            .map(|expires_at| Utc::now() > expires_at)
+            .unwrap_or(false)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct UserEmail {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub email: String,
+    pub verified: bool,
+    pub is_primary: bool,
+    pub verification_token: Option<String>,
+    pub token_expires_at: Option<DateTime<Utc>>,
+    #[serde(rename="createdAt")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(rename="updatedAt")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEventType {
+    Register,
+    Login,
+    LoginFailed,
+    EmailVerified,
+    PasswordResetRequested,
+    PasswordReset,
+    PasswordChanged,
+    RoleChanged,
+    BulkRoleChanged,
+    ProfileUpdated,
+    AccountMerged,
+    EmailAdded,
+    EmailRemoved,
+    PrimaryEmailChanged,
+    TwoFactorEnabled,
+    TwoFactorLogin,
+    RecoveryCodeUsed,
+    RecoveryCodesRegenerated,
+    SessionsTerminated,
+    StatusChanged,
+    SessionLimitChanged,
+    SessionsEvicted,
+    ApiKeyCreated,
+    ApiKeyRevoked,
+    ApiKeyRotated,
+    AccountLocked,
+    EmailChangeReverted,
+    TrustedDeviceAdded,
+    TrustedDeviceRevoked,
+    ImpersonationStarted,
+    ImpersonationEnded,
+    ImpersonatedRequest,
+    AccountReactivated,
+    Reauthenticated,
+    InviteCodeCreated,
+    InviteCodeRevoked,
+    UserApproved,
+    UserRejected,
+    SecurityQuestionsUpdated,
+    SecurityQuestionsRecovery,
+    SessionBindingMismatch,
+    TermsAccepted,
+    AccountFrozen,
+    AccountUnfrozen,
+}
+
+impl AuditEventType {
+    pub const LOGIN_EVENTS: [AuditEventType; 4] = [
+        AuditEventType::Login,
+        AuditEventType::LoginFailed,
+        AuditEventType::TwoFactorLogin,
+        AuditEventType::RecoveryCodeUsed,
+    ];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            AuditEventType::Register => "register",
+            AuditEventType::Login => "login",
+            AuditEventType::LoginFailed => "login_failed",
+            AuditEventType::EmailVerified => "email_verified",
+            AuditEventType::PasswordResetRequested => "password_reset_requested",
+            AuditEventType::PasswordReset => "password_reset",
+            AuditEventType::PasswordChanged => "password_changed",
+            AuditEventType::RoleChanged => "role_changed",
+            AuditEventType::BulkRoleChanged => "bulk_role_changed",
+            AuditEventType::ProfileUpdated => "profile_updated",
+            AuditEventType::AccountMerged => "account_merged",
+            AuditEventType::EmailAdded => "email_added",
+            AuditEventType::EmailRemoved => "email_removed",
+            AuditEventType::PrimaryEmailChanged => "primary_email_changed",
+            AuditEventType::TwoFactorEnabled => "two_factor_enabled",
+            AuditEventType::TwoFactorLogin => "two_factor_login",
+            AuditEventType::RecoveryCodeUsed => "recovery_code_used",
+            AuditEventType::RecoveryCodesRegenerated => "recovery_codes_regenerated",
+            AuditEventType::SessionsTerminated => "sessions_terminated",
+            AuditEventType::StatusChanged => "status_changed",
+            AuditEventType::SessionLimitChanged => "session_limit_changed",
+            AuditEventType::SessionsEvicted => "sessions_evicted",
+            AuditEventType::ApiKeyCreated => "api_key_created",
+            AuditEventType::ApiKeyRevoked => "api_key_revoked",
+            AuditEventType::ApiKeyRotated => "api_key_rotated",
+            AuditEventType::AccountLocked => "account_locked",
+            AuditEventType::EmailChangeReverted => "email_change_reverted",
+            AuditEventType::TrustedDeviceAdded => "trusted_device_added",
+            AuditEventType::TrustedDeviceRevoked => "trusted_device_revoked",
+            AuditEventType::ImpersonationStarted => "impersonation_started",
+            AuditEventType::ImpersonationEnded => "impersonation_ended",
+            AuditEventType::ImpersonatedRequest => "impersonated_request",
+            AuditEventType::AccountReactivated => "account_reactivated",
+            AuditEventType::Reauthenticated => "reauthenticated",
+            AuditEventType::InviteCodeCreated => "invite_code_created",
+            AuditEventType::InviteCodeRevoked => "invite_code_revoked",
+            AuditEventType::UserApproved => "user_approved",
+            AuditEventType::UserRejected => "user_rejected",
+            AuditEventType::SecurityQuestionsUpdated => "security_questions_updated",
+            AuditEventType::SecurityQuestionsRecovery => "security_questions_recovery",
+            AuditEventType::SessionBindingMismatch => "session_binding_mismatch",
+            AuditEventType::TermsAccepted => "terms_accepted",
+            AuditEventType::AccountFrozen => "account_frozen",
+            AuditEventType::AccountUnfrozen => "account_unfrozen",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct AuditLog {
+    pub id: uuid::Uuid,
+    pub user_id: Option<uuid::Uuid>,
+    pub event_type: String,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub success: bool,
+    pub details: Option<String>,
+    #[serde(rename="createdAt")]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct Session {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    #[serde(rename="createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename="lastUsedAt")]
+    pub last_used_at: DateTime<Utc>,
+    #[serde(rename="expiresAt")]
+    pub expires_at: DateTime<Utc>,
+    #[serde(rename="revokedAt")]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl Session {
+    pub fn is_active(&self) -> bool {
+        self.revoked_at.is_none() && self.expires_at > Utc::now()
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct VerificationReminder {
+    pub org_id: uuid::Uuid,
+    pub name: String,
+    pub email: String,
+    pub position: i64,
+}
+
+// Hashes of the client a session was issued to. Sessions created before
+// binding existed have neither and are never treated as a mismatch.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionBinding {
+    pub user_agent_hash: Option<String>,
+    pub network_hash: Option<String>,
+}
+
+impl SessionBinding {
+    pub fn mismatch(&self, current: &SessionBinding, mode: TokenBinding) -> Option<&'static str> {
+        let differs = |stored: &Option<String>, current: &Option<String>| stored.is_some() && stored != current;
+
+        match mode {
+            TokenBinding::Off => None,
+            _ if differs(&self.user_agent_hash, &current.user_agent_hash) => Some("user_agent"),
+            TokenBinding::Network if differs(&self.network_hash, &current.network_hash) => Some("network"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct TrustedDevice {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    #[serde(rename="createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename="lastUsedAt")]
+    pub last_used_at: Option<DateTime<Utc>>,
+    #[serde(rename="expiresAt")]
+    pub expires_at: DateTime<Utc>,
+    #[serde(rename="revokedAt")]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct ApiKey {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub name: String,
+    pub prefix: String,
+    pub key_hash: String,
+    #[serde(rename="createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename="lastUsedAt")]
+    pub last_used_at: Option<DateTime<Utc>>,
+    #[serde(rename="expiresAt")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(rename="revokedAt")]
+    pub revoked_at: Option<DateTime<Utc>>,
+    #[serde(rename="rotatedAt")]
+    pub rotated_at: Option<DateTime<Utc>>,
+    #[serde(rename="replacedBy")]
+    pub replaced_by: Option<uuid::Uuid>,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct EmailJob {
+    pub id: uuid::Uuid,
+    pub to_email: String,
+    pub subject: String,
+    pub template_path: String,
+    pub placeholders: String,
+    pub status: String,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    #[serde(rename="createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename="updatedAt")]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct UserStats {
+    pub total: i64,
+    pub verified: i64,
+    pub admins: i64,
+    pub users: i64,
+    pub signups_24h: i64,
+    pub signups_7d: i64,
+    pub signups_30d: i64,
+    pub active: i64,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct EmailVerificationCode {
+    pub user_id: uuid::Uuid,
+    pub code_hash: String,
+    pub attempts: i32,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct PasswordResetCode {
+    pub user_id: uuid::Uuid,
+    pub code_hash: String,
+    pub attempts: i32,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct Organization {
+    pub id: uuid::Uuid,
+    pub slug: String,
+    pub name: String,
+    #[serde(rename="createdAt")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+impl Organization {
+    pub const DEFAULT_ID: uuid::Uuid = uuid::Uuid::nil();
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct InviteCode {
+    pub id: uuid::Uuid,
+    pub org_id: uuid::Uuid,
+    pub code: String,
+    pub max_uses: Option<i32>,
+    pub used_count: i32,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_by: Option<uuid::Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct SecurityQuestion {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub question: String,
+    pub answer_hash: String,
+    pub answer_pepper_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NotificationKind {
+    Welcome,
+    LockoutNotice,
+}
+
+// Only optional emails have a switch here, security and transactional mail
+// (verification, resets, email changes) is always sent.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct NotificationPreferences {
+    #[serde(default = "enabled")]
+    pub welcome: bool,
+    #[serde(rename="lockoutNotices", default = "enabled")]
+    pub lockout_notices: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl Default for NotificationPreferences {
+    fn default() -> Self {
+        NotificationPreferences {
+            welcome: true,
+            lockout_notices: true,
+        }
+    }
+}
+
+impl NotificationPreferences {
+    pub fn allows(&self, kind: NotificationKind) -> bool {
+        match kind {
+            NotificationKind::Welcome => self.welcome,
+            NotificationKind::LockoutNotice => self.lockout_notices,
+        }
+    }
+}
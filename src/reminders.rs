@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::{config::{Config, VerificationReminders}, db::{DBClient, UserExt}, mail::{mails::queue_verification_reminder_email, queue::EmailQueue}, utils::token};
+
+const RUN_INTERVAL: Duration = Duration::from_secs(900);
+const BATCH_SIZE: usize = 100;
+const TOKEN_TTL_HOURS: i64 = 24;
+
+pub fn spawn_verification_reminders(db_client: DBClient, email_queue: EmailQueue, config: &Config) {
+    let Some(reminders) = config.verification_reminders.clone() else {
+        return;
+    };
+
+    let verify_url = config.api_url("/auth/verify");
+
+    tokio::spawn(async move {
+        loop {
+            send_verification_reminders(&db_client, &email_queue, &reminders, &verify_url).await;
+            tokio::time::sleep(RUN_INTERVAL).await;
+        }
+    });
+}
+
+async fn send_verification_reminders(db_client: &DBClient, email_queue: &EmailQueue, reminders: &VerificationReminders, verify_url: &str) {
+    let now = Utc::now();
+    let created_after = now - chrono::Duration::hours(reminders.max_age_hours);
+    let due_before = now - chrono::Duration::hours(reminders.delay_hours);
+    let mut sent = 0;
+
+    loop {
+        let tokens: Vec<String> = (0..BATCH_SIZE).map(|_| uuid::Uuid::new_v4().to_string()).collect();
+        let token_hashes: Vec<String> = tokens.iter().map(|token| token::hash_token(token)).collect();
+
+        let claimed = db_client
+            .claim_verification_reminders(
+                created_after,
+                due_before,
+                due_before,
+                reminders.max_reminders,
+                &token_hashes,
+                now + chrono::Duration::hours(TOKEN_TTL_HOURS)
+            )
+            .await;
+
+        let claimed = match claimed {
+            Ok(claimed) => claimed,
+            Err(e) => {
+                eprintln!("Failed to claim verification reminders: {}", e);
+                break;
+            }
+        };
+
+        for reminder in &claimed {
+            let token = &tokens[reminder.position as usize - 1];
+
+            if let Err(e) = queue_verification_reminder_email(email_queue, reminder.org_id, &reminder.email, &reminder.name, token, verify_url).await {
+                eprintln!("Failed to queue verification reminder: {}", e);
+            }
+        }
+
+        sent += claimed.len();
+
+        if claimed.len() < BATCH_SIZE {
+            break;
+        }
+    }
+
+    if sent > 0 {
+        println!("Queued {} verification reminders", sent);
+    }
+}
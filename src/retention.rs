@@ -0,0 +1,29 @@
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::{config::DeletedUserRetention, db::{DBClient, UserExt}};
+
+const RUN_INTERVAL: Duration = Duration::from_secs(3600);
+
+pub fn spawn_anonymizer(db_client: DBClient, retention: Option<DeletedUserRetention>) {
+    let Some(retention) = retention else {
+        return;
+    };
+
+    tokio::spawn(async move {
+        loop {
+            anonymize_deleted_users(&db_client, &retention).await;
+            tokio::time::sleep(RUN_INTERVAL).await;
+        }
+    });
+}
+
+async fn anonymize_deleted_users(db_client: &DBClient, retention: &DeletedUserRetention) {
+    let deleted_before = Utc::now() - chrono::Duration::days(retention.days);
+
+    match db_client.anonymize_deleted_users(deleted_before, &retention.fields).await {
+        Ok(count) => println!("Anonymized {} users deleted before {}", count, deleted_before.to_rfc3339()),
+        Err(e) => eprintln!("Failed to anonymize deleted users: {}", e),
+    }
+}
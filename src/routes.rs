@@ -1,20 +1,133 @@
 use std::sync::Arc;
 
-use axum::{middleware, Extension, Router};
-use tower_http::trace::TraceLayer;
+use axum::{http::{header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE}, HeaderName, HeaderValue, Method}, middleware, Extension, Router};
+use tower_http::{
+    compression::{predicate::{NotForContentType, SizeAbove}, CompressionLayer, Predicate},
+    cors::{AllowOrigin, CorsLayer},
+    trace::TraceLayer
+};
 
-use crate::{handler::{auth::auth_handler, users::users_handler}, middleware::auth, AppState};
+use crate::{config::{Compression, Config, CorsOrigins}, handler::{admin::admin_handler, audit::audit_handler, auth::auth_handler, users::users_handler, validate::validate_handler}, middleware::{auth, error_instance, method_not_allowed, request_timeout, security_headers, tenant, TENANT_HEADER}, AppState};
 
 pub fn create_router(app_state: Arc<AppState>) -> Router {
+    let base_path = app_state.env.base_path.clone();
+
     let api_route = Router::new()
-        .nest("/auth", auth_handler())
+        .nest("/auth", auth_handler().layer(cors_layer(&app_state.env, "auth")))
         .nest(
             "/users", 
             users_handler()
                 .layer(middleware::from_fn(auth))
+                .layer(cors_layer(&app_state.env, "users"))
+        )
+        .nest(
+            "/admin",
+            admin_handler()
+                .layer(middleware::from_fn(auth))
+                .layer(cors_layer(&app_state.env, "admin"))
+        )
+        .nest(
+            "/audit",
+            audit_handler()
+                .layer(middleware::from_fn(auth))
+                .layer(cors_layer(&app_state.env, "audit"))
         )
+        .nest("/validate", validate_handler().layer(cors_layer(&app_state.env, "validate")))
+        .layer(middleware::from_fn(tenant))
+        .layer(middleware::from_fn(request_timeout))
+        .layer(middleware::map_response(method_not_allowed))
+        .layer(middleware::from_fn(error_instance))
+        .layer(middleware::from_fn(security_headers))
+        .layer(compression_layer(app_state.env.compression))
         .layer(TraceLayer::new_for_http())
         .layer(Extension(app_state));
 
-    Router::new().nest("/api", api_route)
+    let router = Router::new().nest("/api", api_route);
+
+    if base_path.is_empty() {
+        router
+    } else {
+        Router::new().nest(&base_path, router)
+    }
+}
+
+// Images are already compressed and event streams must flush as they go, the
+// rest is compressed once it is larger than the configured threshold.
+fn compression_layer(compression: Compression) -> CompressionLayer<impl Predicate> {
+    let predicate = SizeAbove::new(compression.min_bytes)
+        .and(NotForContentType::IMAGES)
+        .and(NotForContentType::SSE);
+
+    CompressionLayer::new()
+        .gzip(compression.gzip)
+        .br(compression.br)
+        .no_deflate()
+        .no_zstd()
+        .compress_when(predicate)
+}
+
+fn cors_layer(config: &Config, group: &str) -> CorsLayer {
+    let cors = CorsLayer::new()
+        .allow_headers([AUTHORIZATION, ACCEPT, CONTENT_TYPE, HeaderName::from_static(TENANT_HEADER)])
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE]);
+
+    match config.cors.origins_for(group) {
+        CorsOrigins::Any => cors.allow_origin(AllowOrigin::any()),
+        CorsOrigins::List(origins) => {
+            let origins: Vec<HeaderValue> = origins
+                .iter()
+                .map(|origin| origin.parse().unwrap())
+                .collect();
+
+            cors.allow_origin(origins).allow_credentials(true)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::{header, Request}, routing::get};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn app(compression: Compression, body_len: usize) -> Router {
+        Router::new()
+            .route("/", get(move || async move { "a".repeat(body_len) }))
+            .layer(compression_layer(compression))
+    }
+
+    async fn content_encoding(app: Router, accept_encoding: &str) -> Option<String> {
+        let req = Request::builder()
+            .uri("/")
+            .header(header::ACCEPT_ENCODING, accept_encoding)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(req).await.unwrap();
+
+        response.headers()
+            .get(header::CONTENT_ENCODING)
+            .map(|value| value.to_str().unwrap().to_string())
+    }
+
+    const BOTH: Compression = Compression { gzip: true, br: true, min_bytes: 1024 };
+
+    #[tokio::test]
+    async fn large_responses_are_compressed_with_a_negotiated_codec() {
+        assert_eq!(content_encoding(app(BOTH, 4096), "gzip").await.as_deref(), Some("gzip"));
+        assert_eq!(content_encoding(app(BOTH, 4096), "br").await.as_deref(), Some("br"));
+    }
+
+    #[tokio::test]
+    async fn small_responses_are_left_alone() {
+        assert_eq!(content_encoding(app(BOTH, 100), "gzip, br").await, None);
+    }
+
+    #[tokio::test]
+    async fn disabled_codecs_are_not_offered() {
+        let gzip_only = Compression { br: false, ..BOTH };
+
+        assert_eq!(content_encoding(app(gzip_only, 4096), "br").await, None);
+        assert_eq!(content_encoding(app(gzip_only, 4096), "br, gzip").await.as_deref(), Some("gzip"));
+    }
 }
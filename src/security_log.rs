@@ -0,0 +1,238 @@
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::AsyncWriteExt,
+    net::UnixDatagram,
+    sync::mpsc
+};
+use uuid::Uuid;
+
+use crate::{
+    config::{SecurityLogConfig, SecurityLogFormat, SecurityLogSink},
+    models::AuditEventType,
+    utils::token
+};
+
+const CHANNEL_CAPACITY: usize = 1024;
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+// authpriv facility, info for successes and notice for failures.
+const SYSLOG_SUCCESS_PRIORITY: u8 = 86;
+const SYSLOG_FAILURE_PRIORITY: u8 = 85;
+
+#[derive(Debug, Clone)]
+pub struct SecurityEvent {
+    pub timestamp: DateTime<Utc>,
+    pub action: AuditEventType,
+    pub actor: Option<String>,
+    pub target: Option<String>,
+    pub ip: Option<String>,
+    pub success: bool,
+    pub details: Option<String>,
+}
+
+impl SecurityEvent {
+    // Admin actions name the acting admin in their details, every other event
+    // is performed by the user it concerns.
+    pub fn new(action: AuditEventType, user_id: Option<Uuid>, ip: Option<&str>, success: bool, details: Option<&str>) -> Self {
+        let target = user_id.map(|user_id| user_id.to_string());
+        let actor = details
+            .and_then(|details| {
+                details
+                    .split_whitespace()
+                    .find_map(|pair| pair.strip_prefix("by=").or_else(|| pair.strip_prefix("admin=")))
+            })
+            .map(|actor| actor.to_string())
+            .or_else(|| target.clone());
+
+        SecurityEvent {
+            timestamp: Utc::now(),
+            action,
+            actor,
+            target,
+            ip: ip.map(|ip| ip.to_string()),
+            success,
+            details: details.map(|details| details.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SecurityLog {
+    sender: Option<mpsc::Sender<SecurityEvent>>,
+}
+
+impl SecurityLog {
+    pub async fn spawn(config: Option<SecurityLogConfig>) -> Self {
+        let Some(config) = config else {
+            return SecurityLog { sender: None };
+        };
+
+        let mut writer = Writer::open(config).await;
+        let (sender, mut receiver) = mpsc::channel(CHANNEL_CAPACITY);
+
+        tokio::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                writer.write(event).await;
+            }
+        });
+
+        SecurityLog { sender: Some(sender) }
+    }
+
+    pub async fn record(&self, event: SecurityEvent) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+
+        if sender.send(event).await.is_err() {
+            eprintln!("Security log writer has stopped, event dropped");
+        }
+    }
+}
+
+enum Output {
+    Stdout,
+    File(File),
+    Syslog(UnixDatagram, String),
+}
+
+// Every record carries the hash of the one before it and a hash of itself
+// computed without the hash field, so a removed or edited line breaks the chain.
+struct Writer {
+    format: SecurityLogFormat,
+    output: Output,
+    seq: u64,
+    prev_hash: String,
+}
+
+impl Writer {
+    async fn open(config: SecurityLogConfig) -> Self {
+        let mut seq = 0;
+        let mut prev_hash = GENESIS_HASH.to_string();
+
+        let output = match config.sink {
+            SecurityLogSink::Stdout => Output::Stdout,
+            SecurityLogSink::File(path) => {
+                let existing = tokio::fs::read_to_string(&path).await.unwrap_or_default();
+                if let Some((last_seq, last_hash)) = existing.lines().last().and_then(|line| resume(line, config.format)) {
+                    seq = last_seq;
+                    prev_hash = last_hash;
+                }
+
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+                    .await
+                    .unwrap_or_else(|e| panic!("SECURITY_LOG_FILE {} cannot be opened: {}", path, e));
+
+                Output::File(file)
+            }
+            SecurityLogSink::Syslog(socket) => {
+                let datagram = UnixDatagram::unbound()
+                    .unwrap_or_else(|e| panic!("Failed to create syslog socket: {}", e));
+
+                Output::Syslog(datagram, socket)
+            }
+        };
+
+        Writer {
+            format: config.format,
+            output,
+            seq,
+            prev_hash,
+        }
+    }
+
+    async fn write(&mut self, event: SecurityEvent) {
+        self.seq += 1;
+
+        let mut fields = vec![
+            ("seq", Value::from(self.seq)),
+            ("timestamp", Value::from(event.timestamp.to_rfc3339())),
+            ("action", Value::from(event.action.to_str())),
+            ("actor", event.actor.map(Value::from).unwrap_or(Value::Null)),
+            ("target", event.target.map(Value::from).unwrap_or(Value::Null)),
+            ("ip", event.ip.map(Value::from).unwrap_or(Value::Null)),
+            ("outcome", Value::from(if event.success { "success" } else { "failure" })),
+            ("details", event.details.map(Value::from).unwrap_or(Value::Null)),
+            ("prev_hash", Value::from(self.prev_hash.clone())),
+        ];
+
+        let hash = token::hash_token(&render(self.format, &fields));
+        fields.push(("hash", Value::from(hash.clone())));
+        let line = render(self.format, &fields);
+
+        let result = match &mut self.output {
+            Output::Stdout => {
+                let mut stdout = tokio::io::stdout();
+                match stdout.write_all(format!("{}\n", line).as_bytes()).await {
+                    Ok(()) => stdout.flush().await,
+                    Err(e) => Err(e),
+                }
+            }
+            Output::File(file) => match file.write_all(format!("{}\n", line).as_bytes()).await {
+                Ok(()) => file.flush().await,
+                Err(e) => Err(e),
+            },
+            Output::Syslog(datagram, socket) => {
+                let priority = if event.success { SYSLOG_SUCCESS_PRIORITY } else { SYSLOG_FAILURE_PRIORITY };
+                datagram
+                    .send_to(format!("<{}>auth_api: {}", priority, line).as_bytes(), socket.as_str())
+                    .await
+                    .map(|_| ())
+            }
+        };
+
+        match result {
+            Ok(()) => self.prev_hash = hash,
+            Err(e) => {
+                eprintln!("Failed to write security event {}: {}", self.seq, e);
+                self.seq -= 1;
+            }
+        }
+    }
+}
+
+fn render(format: SecurityLogFormat, fields: &[(&str, Value)]) -> String {
+    match format {
+        SecurityLogFormat::Json => {
+            let object: Map<String, Value> = fields
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect();
+
+            Value::Object(object).to_string()
+        }
+        SecurityLogFormat::Logfmt => fields
+            .iter()
+            .filter_map(|(key, value)| match value {
+                Value::Null => None,
+                Value::String(text) if text.is_empty() || text.contains(|c: char| c.is_whitespace() || c == '"' || c == '=') => {
+                    Some(format!("{}={}", key, Value::from(text.as_str())))
+                }
+                Value::String(text) => Some(format!("{}={}", key, text)),
+                other => Some(format!("{}={}", key, other)),
+            })
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+fn resume(line: &str, format: SecurityLogFormat) -> Option<(u64, String)> {
+    match format {
+        SecurityLogFormat::Json => {
+            let record: Value = serde_json::from_str(line).ok()?;
+            Some((record.get("seq")?.as_u64()?, record.get("hash")?.as_str()?.to_string()))
+        }
+        SecurityLogFormat::Logfmt => {
+            let field = |name: &str| {
+                line.split_whitespace()
+                    .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+            };
+
+            Some((field("seq")?.parse().ok()?, field("hash")?.to_string()))
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::{future::Future, net::SocketAddr, sync::{Arc, Once}};
+
+use axum::{
+    body::{self, Body},
+    extract::ConnectInfo,
+    http::{header, Method, Request, StatusCode},
+    Router
+};
+use chrono::{Duration, Utc};
+use serde_json::Value;
+use sqlx::{Pool, Postgres};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use crate::{
+    config::{Config, StateStoreBackend},
+    db::{DBClient, UserExt},
+    events::EventBus,
+    mail::queue::EmailQueue,
+    middleware::TENANT_HEADER,
+    models::{AccountStatus, User, UserRole},
+    routes::create_router,
+    security_log::SecurityLog,
+    utils::{cache::TtlCache, mx::MxVerifier, password, rate_limit::RateLimiter, state_store},
+    AppState
+};
+
+pub const PASSWORD: &str = "correct horse battery staple";
+pub const PEER: &str = "203.0.113.10:40000";
+
+static ENV: Once = Once::new();
+
+// Only the variables Config::init insists on are filled in, everything else
+// keeps its default so tests see the same behaviour as a bare deployment.
+pub fn config() -> Config {
+    ENV.call_once(|| {
+        if std::env::var("JWT_SECRET").is_err() {
+            std::env::set_var("JWT_SECRET", "test-secret");
+        }
+        if std::env::var("JWT_MAXAGE").is_err() {
+            std::env::set_var("JWT_MAXAGE", "60");
+        }
+    });
+
+    let mut config = Config::init();
+    config.terms_version = None;
+    config.captcha = None;
+    config.state_store = StateStoreBackend::Memory;
+    config
+}
+
+// sqlx::test drives its tests on async-std, the router needs tokio for its
+// timeouts and background tasks.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+pub async fn app_state(pool: Pool<Postgres>, config: Config) -> Arc<AppState> {
+    let db_client = DBClient::new(pool, config.db_heavy_statement_timeout_seconds);
+
+    Arc::new(AppState {
+        email_queue: EmailQueue::new(db_client.clone(), &config),
+        rate_limiter: Arc::new(RateLimiter::new(state_store::from_backend(&config.state_store))),
+        mx_verifier: Arc::new(MxVerifier::new()),
+        event_bus: EventBus::new(),
+        user_stats: Arc::new(TtlCache::new()),
+        security_log: SecurityLog::spawn(None).await,
+        db_client,
+        env: config,
+    })
+}
+
+pub fn router(app_state: &Arc<AppState>) -> Router {
+    create_router(app_state.clone())
+}
+
+pub async fn create_organization(app_state: &AppState, slug: &str) -> Uuid {
+    sqlx::query_scalar("INSERT INTO organizations (slug, name) VALUES ($1, $1) RETURNING id")
+        .bind(slug)
+        .fetch_one(app_state.db_client.pool())
+        .await
+        .unwrap()
+}
+
+pub async fn create_user(app_state: &AppState, org_id: Uuid, email: &str, role: UserRole) -> User {
+    let hashed = password::hash(PASSWORD, None).unwrap();
+    let user = app_state.db_client
+        .save_user(org_id, email.to_string(), email.to_string(), hashed, None, Uuid::new_v4().to_string(), Utc::now() + Duration::hours(1), AccountStatus::Active, None)
+        .await
+        .unwrap();
+
+    sqlx::query("UPDATE users SET verified = true, role = $2 WHERE id = $1")
+        .bind(user.id)
+        .bind(role)
+        .execute(app_state.db_client.pool())
+        .await
+        .unwrap();
+
+    user
+}
+
+pub fn json_request(method: Method, uri: &str, body: Value) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+pub async fn send(app: &Router, mut req: Request<Body>) -> (StatusCode, Value) {
+    req.extensions_mut().insert(ConnectInfo(PEER.parse::<SocketAddr>().unwrap()));
+
+    let response = app.clone().oneshot(req).await.unwrap();
+    let status = response.status();
+    let bytes = body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+    (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+}
+
+pub async fn login(app: &Router, org_slug: Option<&str>, email: &str) -> String {
+    let mut req = json_request(Method::POST, "/api/auth/login", serde_json::json!({
+        "email": email,
+        "password": PASSWORD,
+    }));
+    if let Some(slug) = org_slug {
+        req.headers_mut().insert(TENANT_HEADER, slug.parse().unwrap());
+    }
+
+    let (status, body) = send(app, req).await;
+
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    body["token"].as_str().unwrap().to_string()
+}
@@ -0,0 +1,36 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug)]
+pub struct TtlCache<K, T> {
+    entries: Mutex<HashMap<K, (T, Instant)>>,
+}
+
+impl<K: Eq + Hash, T: Clone> TtlCache<K, T> {
+    pub fn new() -> Self {
+        TtlCache { entries: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn get(&self, key: &K, ttl: Duration) -> Option<T> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(key)
+            .filter(|(_, cached_at)| cached_at.elapsed() < ttl)
+            .map(|(value, _)| value.clone())
+    }
+
+    pub fn set(&self, key: K, value: T) {
+        self.entries.lock().unwrap().insert(key, (value, Instant::now()));
+    }
+}
+
+impl<K: Eq + Hash, T: Clone> Default for TtlCache<K, T> {
+    fn default() -> Self {
+        TtlCache::new()
+    }
+}
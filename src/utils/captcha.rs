@@ -0,0 +1,90 @@
+use std::{
+    io::{Read, Write},
+    net::{IpAddr, TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use native_tls::TlsConnector;
+use serde::Deserialize;
+
+use crate::config::CaptchaProvider;
+
+const PROVIDER_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Deserialize)]
+struct VerifyResponse {
+    success: bool,
+}
+
+pub async fn verify(
+    provider: CaptchaProvider,
+    secret: &str,
+    token: &str,
+    remote_ip: IpAddr,
+) -> Result<bool, String> {
+    let body = format!(
+        "secret={}&response={}&remoteip={}",
+        form_encode(secret),
+        form_encode(token),
+        remote_ip
+    );
+
+    let response = tokio::task::spawn_blocking(move || post_form(provider, &body))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    let response: VerifyResponse = serde_json::from_str(&response)
+        .map_err(|e| format!("Unexpected captcha provider response: {}", e))?;
+
+    Ok(response.success)
+}
+
+fn post_form(provider: CaptchaProvider, body: &str) -> Result<String, String> {
+    let (host, path) = provider.verify_endpoint();
+
+    let addr = (host, 443)
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())?
+        .next()
+        .ok_or(format!("Could not resolve {}", host))?;
+
+    let stream = TcpStream::connect_timeout(&addr, PROVIDER_TIMEOUT).map_err(|e| e.to_string())?;
+    stream.set_read_timeout(Some(PROVIDER_TIMEOUT)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(PROVIDER_TIMEOUT)).map_err(|e| e.to_string())?;
+
+    let connector = TlsConnector::new().map_err(|e| e.to_string())?;
+    let mut stream = connector.connect(host, stream).map_err(|e| e.to_string())?;
+
+    let request = format!(
+        "POST {} HTTP/1.0\r\nHost: {}\r\nContent-Type: application/x-www-form-urlencoded\r\nContent-Length: {}\r\n\r\n{}",
+        path,
+        host,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).map_err(|e| e.to_string())?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).map_err(|e| e.to_string())?;
+
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or("Malformed captcha provider response".to_string())?;
+
+    let status = head.split_whitespace().nth(1).unwrap_or_default();
+    if status != "200" {
+        return Err(format!("Captcha provider returned status {}", status));
+    }
+
+    Ok(body.to_string())
+}
+
+fn form_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
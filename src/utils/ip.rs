@@ -0,0 +1,165 @@
+use std::{net::IpAddr, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IpMasking {
+    None,
+    Partial,
+    Full,
+}
+
+impl IpMasking {
+    pub fn apply(self, ip: &str) -> Option<String> {
+        match self {
+            IpMasking::None => Some(ip.to_string()),
+            IpMasking::Full => None,
+            IpMasking::Partial => match ip.parse::<IpAddr>().ok()?.to_canonical() {
+                IpAddr::V4(ip) => {
+                    let [a, b, c, _] = ip.octets();
+                    Some(format!("{}.{}.{}.x", a, b, c))
+                }
+                IpAddr::V6(ip) => {
+                    let [a, b, c, ..] = ip.segments();
+                    Some(format!("{:x}:{:x}:{:x}::x", a, b, c))
+                }
+            },
+        }
+    }
+}
+
+impl FromStr for IpMasking {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "none" => Ok(IpMasking::None),
+            "partial" => Ok(IpMasking::Partial),
+            "full" => Ok(IpMasking::Full),
+            _ => Err(format!("Unknown IP masking mode: {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IpNetwork {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpNetwork {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        let (addr, prefix) = match value.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (value, None),
+        };
+
+        let addr = addr
+            .parse::<IpAddr>()
+            .map_err(|_| format!("Invalid IP address: {}", value))?
+            .to_canonical();
+        let max_prefix = if addr.is_ipv4() { 32 } else { 128 };
+
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix| *prefix <= max_prefix)
+                .ok_or_else(|| format!("Invalid network prefix: {}", value))?,
+            None => max_prefix,
+        };
+
+        Ok(IpNetwork { addr, prefix })
+    }
+}
+
+// X-Forwarded-For is only read when the peer is a trusted proxy, and is walked
+// right to left so client-supplied entries on the left can't spoof the address.
+pub fn client_ip(peer: IpAddr, forwarded_for: Option<&str>, trusted_proxies: &[IpNetwork]) -> IpAddr {
+    let is_trusted = |ip: IpAddr| trusted_proxies.iter().any(|network| network.contains(ip));
+
+    if !is_trusted(peer) {
+        return peer;
+    }
+
+    let Some(forwarded_for) = forwarded_for else {
+        return peer;
+    };
+
+    let mut client = peer;
+    for hop in forwarded_for.rsplit(',') {
+        let Ok(ip) = hop.trim().parse::<IpAddr>() else {
+            break;
+        };
+
+        client = ip;
+        if !is_trusted(ip) {
+            break;
+        }
+    }
+
+    client
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    fn proxies() -> Vec<IpNetwork> {
+        vec!["10.0.0.0/8".parse().unwrap(), "192.0.2.1".parse().unwrap()]
+    }
+
+    #[test]
+    fn untrusted_peer_cannot_spoof_forwarded_for() {
+        let resolved = client_ip(ip("198.51.100.7"), Some("203.0.113.10"), &proxies());
+
+        assert_eq!(resolved, ip("198.51.100.7"));
+    }
+
+    #[test]
+    fn trusted_proxy_chain_resolves_to_first_untrusted_hop() {
+        let resolved = client_ip(ip("10.0.0.2"), Some("203.0.113.10, 192.0.2.1, 10.0.0.1"), &proxies());
+
+        assert_eq!(resolved, ip("203.0.113.10"));
+    }
+
+    #[test]
+    fn entries_left_of_the_client_are_ignored() {
+        let resolved = client_ip(ip("10.0.0.2"), Some("1.1.1.1, 203.0.113.10, 10.0.0.1"), &proxies());
+
+        assert_eq!(resolved, ip("203.0.113.10"));
+    }
+
+    #[test]
+    fn malformed_hop_stops_the_walk() {
+        let resolved = client_ip(ip("10.0.0.2"), Some("203.0.113.10, not-an-ip, 10.0.0.1"), &proxies());
+
+        assert_eq!(resolved, ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn trusted_peer_without_header_is_the_client() {
+        assert_eq!(client_ip(ip("10.0.0.2"), None, &proxies()), ip("10.0.0.2"));
+    }
+}
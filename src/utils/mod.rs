@@ -1,2 +1,14 @@
+pub mod cache;
+pub mod captcha;
+pub mod csv;
+pub mod device;
+pub mod ip;
+pub mod mx;
+pub mod names;
 pub mod password;
+pub mod query;
+pub mod rate_limit;
+pub mod state_store;
+pub mod strength;
 pub mod token;
+pub mod totp;
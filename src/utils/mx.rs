@@ -0,0 +1,169 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use rand::Rng;
+use tokio::net::UdpSocket;
+
+const CACHE_TTL: Duration = Duration::from_secs(300);
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(3);
+const MAX_CACHED_DOMAINS: usize = 10_000;
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+const TYPE_MX: u16 = 15;
+const CLASS_IN: u16 = 1;
+
+#[derive(Debug, Default)]
+pub struct MxVerifier {
+    cache: Mutex<HashMap<String, (bool, Instant)>>,
+}
+
+impl MxVerifier {
+    pub fn new() -> Self {
+        MxVerifier::default()
+    }
+
+    pub async fn accepts_mail(&self, email: &str) -> bool {
+        let Some((_, domain)) = email.rsplit_once('@') else {
+            return false;
+        };
+        let domain = domain.trim().trim_end_matches('.').to_ascii_lowercase();
+
+        if let Some(&(accepts, cached_at)) = self.cache.lock().unwrap().get(&domain) {
+            if cached_at.elapsed() < CACHE_TTL {
+                return accepts;
+            }
+        }
+
+        let accepts = match tokio::time::timeout(LOOKUP_TIMEOUT, lookup_mx(&domain)).await {
+            Ok(Ok(accepts)) => accepts,
+            Ok(Err(e)) => {
+                eprintln!("MX lookup for {} failed, accepting: {}", domain, e);
+                return true;
+            }
+            Err(_) => {
+                eprintln!("MX lookup for {} timed out, accepting", domain);
+                return true;
+            }
+        };
+
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() > MAX_CACHED_DOMAINS {
+            cache.retain(|_, (_, cached_at)| cached_at.elapsed() < CACHE_TTL);
+        }
+        cache.insert(domain, (accepts, Instant::now()));
+
+        accepts
+    }
+}
+
+async fn lookup_mx(domain: &str) -> io::Result<bool> {
+    let Some(query) = build_query(domain) else {
+        return Ok(false);
+    };
+
+    let nameserver = nameserver()?;
+    let bind_addr: SocketAddr = match nameserver.ip() {
+        IpAddr::V4(_) => "0.0.0.0:0".parse().unwrap(),
+        IpAddr::V6(_) => "[::]:0".parse().unwrap(),
+    };
+
+    let socket = UdpSocket::bind(bind_addr).await?;
+    socket.connect(nameserver).await?;
+    socket.send(&query).await?;
+
+    let mut response = [0u8; 4096];
+    let len = socket.recv(&mut response).await?;
+
+    parse_response(&query[..2], &response[..len])
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed DNS response"))?
+}
+
+fn nameserver() -> io::Result<SocketAddr> {
+    std::fs::read_to_string(RESOLV_CONF)?
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .filter_map(|address| address.trim().parse::<IpAddr>().ok())
+        .map(|ip| SocketAddr::new(ip, 53))
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no nameserver configured"))
+}
+
+fn build_query(domain: &str) -> Option<Vec<u8>> {
+    if domain.is_empty() || domain.len() > 253 {
+        return None;
+    }
+
+    let id: u16 = rand::thread_rng().gen();
+    let mut query = Vec::with_capacity(domain.len() + 18);
+    query.extend_from_slice(&id.to_be_bytes());
+    query.extend_from_slice(&[0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+
+    for label in domain.split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return None;
+        }
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&TYPE_MX.to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+    Some(query)
+}
+
+fn parse_response(id: &[u8], response: &[u8]) -> Option<io::Result<bool>> {
+    if response.len() < 12 || &response[..2] != id {
+        return None;
+    }
+
+    if response[2] & 0x02 != 0 {
+        return Some(Err(io::Error::new(io::ErrorKind::InvalidData, "truncated DNS response")));
+    }
+
+    match response[3] & 0x0f {
+        0 => {}
+        3 => return Some(Ok(false)),
+        rcode => return Some(Err(io::Error::other(format!("DNS error code {}", rcode)))),
+    }
+
+    let questions = u16::from_be_bytes([response[4], response[5]]);
+    let answers = u16::from_be_bytes([response[6], response[7]]);
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(response, pos)? + 4;
+    }
+
+    for _ in 0..answers {
+        pos = skip_name(response, pos)?;
+        let header = response.get(pos..pos + 10)?;
+        let record_type = u16::from_be_bytes([header[0], header[1]]);
+        let data_len = u16::from_be_bytes([header[8], header[9]]) as usize;
+        pos += 10;
+        let data = response.get(pos..pos + data_len)?;
+        pos += data_len;
+
+        let null_mx = data.len() == 3 && data[2] == 0;
+        if record_type == TYPE_MX && !null_mx {
+            return Some(Ok(true));
+        }
+    }
+
+    Some(Ok(false))
+}
+
+fn skip_name(response: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *response.get(pos)? as usize;
+        match len {
+            0 => return Some(pos + 1),
+            len if len & 0xc0 == 0xc0 => return Some(pos + 2),
+            len => pos += 1 + len,
+        }
+    }
+}
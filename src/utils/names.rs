@@ -0,0 +1,81 @@
+use std::{collections::HashSet, sync::Arc};
+
+const BUNDLED_RESERVED_NAMES: &str = include_str!("reserved_names.txt");
+const BUNDLED_BLOCKED_TERMS: &str = include_str!("blocked_name_terms.txt");
+
+#[derive(Debug, Clone, Default)]
+pub struct NameBlocklist {
+    reserved: Arc<HashSet<String>>,
+    blocked: Arc<Vec<String>>,
+}
+
+impl NameBlocklist {
+    pub fn new(extra_reserved: Option<&str>, extra_blocked: Option<&str>) -> Self {
+        let reserved = entries(BUNDLED_RESERVED_NAMES, extra_reserved)
+            .map(|entry| compact(&entry))
+            .filter(|entry| !entry.is_empty())
+            .collect();
+
+        let mut blocked: Vec<String> = entries(BUNDLED_BLOCKED_TERMS, extra_blocked)
+            .map(|entry| unleet(&compact(&entry)))
+            .filter(|entry| !entry.is_empty())
+            .collect();
+        blocked.sort();
+        blocked.dedup();
+
+        NameBlocklist {
+            reserved: Arc::new(reserved),
+            blocked: Arc::new(blocked),
+        }
+    }
+
+    pub fn check(&self, name: &str) -> Result<(), &'static str> {
+        let compacted = compact(name);
+
+        if self.reserved.contains(&compacted) {
+            return Err("This name is reserved");
+        }
+
+        let normalized = unleet(&compacted);
+        if self.blocked.iter().any(|term| normalized.contains(term.as_str())) {
+            return Err("This name contains a word that is not allowed");
+        }
+
+        Ok(())
+    }
+}
+
+fn entries<'a>(bundled: &'a str, extra: Option<&'a str>) -> impl Iterator<Item = String> + 'a {
+    bundled
+        .lines()
+        .chain(extra.unwrap_or_default().lines())
+        .map(|line| line.trim().to_lowercase())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+// Separators are dropped so "Ad.Min" and "a d m i n" compare like "admin".
+fn compact(value: &str) -> String {
+    value
+        .to_lowercase()
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, '.' | '-' | '_'))
+        .collect()
+}
+
+fn unleet(value: &str) -> String {
+    value
+        .chars()
+        .filter_map(|c| match c {
+            '0' => Some('o'),
+            '1' | '!' | '|' => Some('i'),
+            '3' => Some('e'),
+            '4' | '@' => Some('a'),
+            '5' | '$' => Some('s'),
+            '7' | '+' => Some('t'),
+            '8' => Some('b'),
+            '9' => Some('g'),
+            c if c.is_alphanumeric() => Some(c),
+            _ => None,
+        })
+        .collect()
+}
@@ -8,12 +8,135 @@ use argon2::{
     },
     Argon2,
 };
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::{collections::HashMap, sync::Arc};
 
 use crate::error::ErrorMessage;
 
+use super::strength;
+
 const MAX_PASSWORD_LENGTH: usize = 64;
+const MIN_CONTAINED_LENGTH: usize = 3;
+const BUNDLED_COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+
+#[derive(Clone)]
+pub struct PasswordPolicy {
+    app_name: Option<String>,
+    common_passwords: Arc<HashMap<String, usize>>,
+    min_score: u8,
+}
+
+impl PasswordPolicy {
+    pub fn new(app_name: Option<&str>, extra_common_passwords: Option<&str>, min_score: u8) -> Self {
+        let mut common_passwords = HashMap::new();
+        let lines = BUNDLED_COMMON_PASSWORDS
+            .lines()
+            .chain(extra_common_passwords.unwrap_or_default().lines())
+            .map(|line| line.trim().to_lowercase())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+        for line in lines {
+            let rank = common_passwords.len() + 1;
+            common_passwords.entry(line).or_insert(rank);
+        }
+
+        PasswordPolicy {
+            app_name: app_name.map(str::to_lowercase),
+            common_passwords: Arc::new(common_passwords),
+            min_score,
+        }
+    }
+
+    pub fn check(&self, password: &str, name: &str, email: &str) -> Result<(), ErrorMessage> {
+        let password_original = password;
+        let password = password.to_lowercase();
+        let contains = |value: &str| {
+            let value = value.trim().to_lowercase();
+            value.chars().count() >= MIN_CONTAINED_LENGTH && password.contains(&value)
+        };
+
+        if self.common_passwords.contains_key(&password) {
+            return Err(ErrorMessage::PasswordTooCommon);
+        }
+
+        let email_local = email.split('@').next().unwrap_or_default();
+        if contains(email) || contains(email_local) {
+            return Err(ErrorMessage::PasswordContainsEmail);
+        }
+
+        if contains(name) || name.split_whitespace().any(contains) {
+            return Err(ErrorMessage::PasswordContainsName);
+        }
+
+        if self.app_name.as_deref().is_some_and(contains) {
+            return Err(ErrorMessage::PasswordContainsAppName);
+        }
+
+        if self.min_score > 0 {
+            let context: Vec<String> = [email, email_local, name]
+                .into_iter()
+                .chain(name.split_whitespace())
+                .chain(self.app_name.as_deref())
+                .map(|value| value.trim().to_lowercase())
+                .filter(|value| !value.is_empty())
+                .collect();
+
+            let strength = strength::estimate(password_original, &self.common_passwords, &context);
+            if strength.score < self.min_score {
+                return Err(ErrorMessage::PasswordTooWeak(strength.crack_time()));
+            }
+        }
+
+        Ok(())
+    }
+}
 
-pub fn hash(password: impl Into<String>) -> Result<String, ErrorMessage> {
+impl std::fmt::Debug for PasswordPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PasswordPolicy")
+            .field("app_name", &self.app_name)
+            .field("common_passwords", &self.common_passwords.len())
+            .field("min_score", &self.min_score)
+            .finish()
+    }
+}
+
+#[derive(Clone)]
+pub struct Pepper {
+    pub id: String,
+    secret: String,
+}
+
+impl Pepper {
+    pub fn new(id: impl Into<String>, secret: impl Into<String>) -> Self {
+        Pepper {
+            id: id.into(),
+            secret: secret.into(),
+        }
+    }
+
+    fn apply(&self, password: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(password.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+}
+
+impl std::fmt::Debug for Pepper {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pepper").field("id", &self.id).finish_non_exhaustive()
+    }
+}
+
+// Answers to security questions match regardless of case and spacing, so
+// "New  York " and "new york" hash the same.
+pub fn normalize_answer(answer: &str) -> String {
+    answer.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+pub fn hash(password: impl Into<String>, pepper: Option<&Pepper>) -> Result<String, ErrorMessage> {
     let password = password.into();
 
     if password.is_empty() {
@@ -24,6 +147,11 @@ pub fn hash(password: impl Into<String>) -> Result<String, ErrorMessage> {
         return Err(ErrorMessage::ExceededMaxPasswordLength(MAX_PASSWORD_LENGTH));
     }
 
+    let password = match pepper {
+        Some(pepper) => pepper.apply(&password),
+        None => password,
+    };
+
     let salt = SaltString::generate(&mut OsRng);
     let hashed_password = Argon2::default()
         .hash_password(password.as_bytes(), &salt)
@@ -33,7 +161,7 @@ pub fn hash(password: impl Into<String>) -> Result<String, ErrorMessage> {
     Ok(hashed_password)
 }
 
-pub fn compare(password: &str, hashed_password: &str) -> Result<bool, ErrorMessage> {
+pub fn compare(password: &str, hashed_password: &str, pepper: Option<&Pepper>) -> Result<bool, ErrorMessage> {
     if password.is_empty() {
         return Err(ErrorMessage::EmptyPassword);
     }
@@ -44,9 +172,14 @@ pub fn compare(password: &str, hashed_password: &str) -> Result<bool, ErrorMessa
     let parsed_hash = PasswordHash::new(hashed_password)
         .map_err(|_| ErrorMessage::InvalidHashFormat)?;
 
+    let password = match pepper {
+        Some(pepper) => pepper.apply(password),
+        None => password.to_string(),
+    };
+
     let password_matched = Argon2::default()
         .verify_password(password.as_bytes(), &parsed_hash)
-        .map_or(false, |_| true);
+        .is_ok();
 
     Ok(password_matched)
 }
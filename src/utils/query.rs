@@ -0,0 +1,274 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use sqlx::{Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::error::ErrorMessage;
+
+#[derive(Debug, Clone, Copy)]
+pub struct FieldMap(pub &'static [(&'static str, &'static str)]);
+
+impl FieldMap {
+    pub fn column(&self, field: &str) -> Result<&'static str, ErrorMessage> {
+        self.0
+            .iter()
+            .find(|(name, _)| *name == field)
+            .map(|(_, column)| *column)
+            .ok_or_else(|| ErrorMessage::UnsupportedQueryField(field.to_string()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum FilterValue {
+    Text(String),
+    // A value compared against a Postgres enum column, with the enum's type name.
+    Enum(String, &'static str),
+    TextList(Vec<String>),
+    Uuid(Uuid),
+    Bool(bool),
+    Timestamp(DateTime<Utc>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterOp {
+    Eq,
+    Gte,
+    Lte,
+    Contains,
+    In,
+}
+
+impl FilterOp {
+    fn to_sql(self) -> &'static str {
+        match self {
+            FilterOp::Eq => " = ",
+            FilterOp::Gte => " >= ",
+            FilterOp::Lte => " <= ",
+            FilterOp::Contains => " ILIKE ",
+            FilterOp::In => " = ANY(",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    fn to_sql(self) -> &'static str {
+        match self {
+            SortOrder::Asc => " ASC",
+            SortOrder::Desc => " DESC",
+        }
+    }
+}
+
+impl FromStr for SortOrder {
+    type Err = ErrorMessage;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "asc" => Ok(SortOrder::Asc),
+            "desc" => Ok(SortOrder::Desc),
+            _ => Err(ErrorMessage::UnsupportedQueryField(value.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Filter {
+    columns: Vec<&'static str>,
+    op: FilterOp,
+    value: FilterValue,
+}
+
+#[derive(Debug, Clone)]
+pub struct ListQuery {
+    filter_fields: FieldMap,
+    sort_fields: FieldMap,
+    filters: Vec<Filter>,
+    sort_column: &'static str,
+    sort_order: SortOrder,
+    limit: i64,
+    offset: i64,
+}
+
+impl ListQuery {
+    pub fn new(filter_fields: FieldMap, sort_fields: FieldMap, default_sort: &'static str) -> Self {
+        ListQuery {
+            filter_fields,
+            sort_fields,
+            filters: Vec::new(),
+            sort_column: default_sort,
+            sort_order: SortOrder::Desc,
+            limit: 10,
+            offset: 0,
+        }
+    }
+
+    pub fn filter(mut self, field: &str, op: FilterOp, value: Option<FilterValue>) -> Result<Self, ErrorMessage> {
+        let column = self.filter_fields.column(field)?;
+
+        if let Some(value) = value {
+            self.filters.push(Filter {
+                columns: vec![column],
+                op,
+                value,
+            });
+        }
+
+        Ok(self)
+    }
+
+    pub fn search(mut self, fields: &[&str], term: Option<&str>) -> Result<Self, ErrorMessage> {
+        let columns = fields
+            .iter()
+            .map(|field| self.filter_fields.column(field))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if let Some(term) = term.map(str::trim).filter(|term| !term.is_empty()) {
+            self.filters.push(Filter {
+                columns,
+                op: FilterOp::Contains,
+                value: FilterValue::Text(term.to_string()),
+            });
+        }
+
+        Ok(self)
+    }
+
+    pub fn sort(mut self, field: Option<&str>, order: Option<&str>) -> Result<Self, ErrorMessage> {
+        if let Some(field) = field {
+            self.sort_column = self.sort_fields.column(field)?;
+        }
+
+        if let Some(order) = order {
+            self.sort_order = order.parse()?;
+        }
+
+        Ok(self)
+    }
+
+    pub fn paginate(mut self, page: usize, limit: usize) -> Self {
+        self.limit = limit as i64;
+        self.offset = (page.saturating_sub(1) * limit) as i64;
+        self
+    }
+
+    pub fn push_filters(&self, builder: &mut QueryBuilder<'_, Postgres>) {
+        for filter in &self.filters {
+            builder.push(" AND (");
+
+            for (index, column) in filter.columns.iter().enumerate() {
+                if index > 0 {
+                    builder.push(" OR ");
+                }
+
+                builder.push(*column).push(filter.op.to_sql());
+
+                match &filter.value {
+                    FilterValue::Text(value) if filter.op == FilterOp::Contains => {
+                        builder.push_bind(format!("%{}%", escape_like(value)))
+                    }
+                    FilterValue::Text(value) => builder.push_bind(value.clone()),
+                    FilterValue::Enum(value, type_name) => builder.push_bind(value.clone()).push("::").push(*type_name),
+                    FilterValue::TextList(values) => builder.push_bind(values.clone()),
+                    FilterValue::Uuid(value) => builder.push_bind(*value),
+                    FilterValue::Bool(value) => builder.push_bind(*value),
+                    FilterValue::Timestamp(value) => builder.push_bind(*value),
+                };
+
+                if filter.op == FilterOp::In {
+                    builder.push(")");
+                }
+            }
+
+            builder.push(")");
+        }
+    }
+
+    pub fn push_sort_and_page(&self, builder: &mut QueryBuilder<'_, Postgres>) {
+        builder
+            .push(" ORDER BY ")
+            .push(self.sort_column)
+            .push(self.sort_order.to_sql())
+            .push(", id")
+            .push(" LIMIT ")
+            .push_bind(self.limit)
+            .push(" OFFSET ")
+            .push_bind(self.offset);
+    }
+}
+
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{USER_FILTER_FIELDS, USER_SORT_FIELDS};
+
+    fn user_query() -> ListQuery {
+        ListQuery::new(USER_FILTER_FIELDS, USER_SORT_FIELDS, "created_at")
+    }
+
+    fn to_sql(query: &ListQuery) -> String {
+        let mut builder = QueryBuilder::new("SELECT id FROM users WHERE TRUE");
+        query.push_filters(&mut builder);
+        query.push_sort_and_page(&mut builder);
+        builder.sql().to_string()
+    }
+
+    #[test]
+    fn sort_by_outside_the_whitelist_is_rejected() {
+        for field in ["password", "name; DROP TABLE users", "name DESC, (SELECT 1)", ""] {
+            assert!(user_query().sort(Some(field), None).is_err(), "{}", field);
+        }
+    }
+
+    #[test]
+    fn order_other_than_asc_or_desc_is_rejected() {
+        for order in ["desc; DROP TABLE users", "asc nulls first", "1", ""] {
+            assert!(user_query().sort(None, Some(order)).is_err(), "{}", order);
+        }
+    }
+
+    #[test]
+    fn sort_uses_the_mapped_column() {
+        let query = user_query().sort(Some("name"), Some("ASC")).unwrap();
+
+        assert!(to_sql(&query).contains(" ORDER BY name ASC, id LIMIT "));
+    }
+
+    #[test]
+    fn filter_values_are_bound_not_interpolated() {
+        let term = "'; DROP TABLE users; --";
+        let query = user_query()
+            .search(&["name", "email"], Some(term))
+            .unwrap()
+            .filter("role", FilterOp::Eq, Some(FilterValue::Enum("admin".to_string(), "user_role")))
+            .unwrap();
+        let sql = to_sql(&query);
+
+        assert!(!sql.contains("DROP TABLE"));
+        assert!(sql.contains("(name ILIKE $1 OR email ILIKE $2)"));
+        assert!(sql.contains("(role = $3::user_role)"));
+    }
+
+    #[test]
+    fn unknown_filter_field_is_rejected() {
+        assert!(user_query().filter("password", FilterOp::Eq, None).is_err());
+    }
+
+    #[test]
+    fn like_wildcards_are_escaped() {
+        assert_eq!(escape_like(r"100%_\"), r"100\%\_\\");
+    }
+}
@@ -0,0 +1,48 @@
+use std::time::Duration;
+
+use crate::utils::state_store::StateStore;
+
+// Store failures let requests through, an unreachable store should not lock
+// everyone out.
+#[derive(Debug)]
+pub struct RateLimiter {
+    store: Box<dyn StateStore>,
+}
+
+impl RateLimiter {
+    pub fn new(store: Box<dyn StateStore>) -> Self {
+        RateLimiter { store }
+    }
+
+    pub async fn check(&self, key: &str, max_requests: u32, window: Duration) -> bool {
+        match self.store.increment(key, window).await {
+            Ok(count) => count <= max_requests,
+            Err(e) => {
+                eprintln!("Rate limit store failed for {}, allowing: {}", key, e);
+                true
+            }
+        }
+    }
+
+    pub async fn is_exhausted(&self, key: &str, max_requests: u32) -> bool {
+        match self.store.count(key).await {
+            Ok(count) => count >= max_requests,
+            Err(e) => {
+                eprintln!("Rate limit store failed for {}, allowing: {}", key, e);
+                false
+            }
+        }
+    }
+
+    pub async fn record(&self, key: &str, window: Duration) {
+        if let Err(e) = self.store.increment(key, window).await {
+            eprintln!("Rate limit store failed to record {}: {}", key, e);
+        }
+    }
+
+    pub async fn reset(&self, key: &str) {
+        if let Err(e) = self.store.reset(key).await {
+            eprintln!("Rate limit store failed to reset {}: {}", key, e);
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use std::{
+    collections::HashMap,
+    io,
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    sync::Mutex as AsyncMutex,
+};
+use url::Url;
+
+use crate::config::StateStoreBackend;
+
+const MAX_TRACKED_KEYS: usize = 10_000;
+const REDIS_KEY_PREFIX: &str = "auth_api:";
+const REDIS_TIMEOUT: Duration = Duration::from_secs(2);
+
+// INCR and PEXPIRE run as one script so instances sharing a key can never
+// count a hit without the window that expires it.
+const INCREMENT_SCRIPT: &str = "local count = redis.call('INCR', KEYS[1]) \
+    if count == 1 then redis.call('PEXPIRE', KEYS[1], ARGV[1]) end \
+    return count";
+
+// Counters shared by rate limits, lockouts and cooldowns. A key's window starts
+// with its first hit and every hit until it expires adds to the same count.
+#[async_trait]
+pub trait StateStore: Send + Sync + std::fmt::Debug {
+    async fn increment(&self, key: &str, window: Duration) -> io::Result<u32>;
+
+    async fn count(&self, key: &str) -> io::Result<u32>;
+
+    async fn reset(&self, key: &str) -> io::Result<()>;
+}
+
+pub fn from_backend(backend: &StateStoreBackend) -> Box<dyn StateStore> {
+    match backend {
+        StateStoreBackend::Memory => Box::new(MemoryStore::new()),
+        StateStoreBackend::Redis(target) => Box::new(RedisStore::new(target.clone())),
+    }
+}
+
+#[derive(Debug)]
+struct Window {
+    expires_at: Instant,
+    count: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    windows: Mutex<HashMap<String, Window>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+}
+
+#[async_trait]
+impl StateStore for MemoryStore {
+    async fn increment(&self, key: &str, window: Duration) -> io::Result<u32> {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap();
+
+        if windows.len() > MAX_TRACKED_KEYS {
+            windows.retain(|_, entry| entry.expires_at > now);
+        }
+
+        let entry = windows.entry(key.to_string()).or_insert(Window {
+            expires_at: now + window,
+            count: 0,
+        });
+
+        if entry.expires_at <= now {
+            entry.expires_at = now + window;
+            entry.count = 0;
+        }
+
+        entry.count += 1;
+        Ok(entry.count)
+    }
+
+    async fn count(&self, key: &str) -> io::Result<u32> {
+        let windows = self.windows.lock().unwrap();
+
+        Ok(windows
+            .get(key)
+            .filter(|entry| entry.expires_at > Instant::now())
+            .map_or(0, |entry| entry.count))
+    }
+
+    async fn reset(&self, key: &str) -> io::Result<()> {
+        self.windows.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct RedisTarget {
+    host: String,
+    port: u16,
+    username: Option<String>,
+    password: Option<String>,
+    database: Option<u32>,
+}
+
+impl FromStr for RedisTarget {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let url = Url::parse(value).map_err(|e| e.to_string())?;
+
+        if url.scheme() != "redis" {
+            return Err(format!("unsupported scheme {}, expected redis://", url.scheme()));
+        }
+
+        let host = url.host_str()
+            .filter(|host| !host.is_empty())
+            .ok_or("missing host")?
+            .to_string();
+
+        let database = match url.path().trim_start_matches('/') {
+            "" => None,
+            database => Some(database.parse().map_err(|_| format!("invalid database {}", database))?),
+        };
+
+        Ok(RedisTarget {
+            host,
+            port: url.port().unwrap_or(6379),
+            username: Some(url.username().to_string()).filter(|username| !username.is_empty()),
+            password: url.password().map(|password| password.to_string()),
+            database,
+        })
+    }
+}
+
+impl std::fmt::Debug for RedisTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisTarget")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("database", &self.database)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+enum Reply {
+    Nil,
+    Integer(i64),
+    Bulk(Vec<u8>),
+    Simple,
+}
+
+#[derive(Debug)]
+pub struct RedisStore {
+    target: RedisTarget,
+    connection: AsyncMutex<Option<BufReader<TcpStream>>>,
+}
+
+impl RedisStore {
+    pub fn new(target: RedisTarget) -> Self {
+        RedisStore {
+            target,
+            connection: AsyncMutex::new(None),
+        }
+    }
+
+    async fn command(&self, args: &[&str]) -> io::Result<Reply> {
+        let mut connection = self.connection.lock().await;
+
+        let result = tokio::time::timeout(REDIS_TIMEOUT, async {
+            if connection.is_none() {
+                *connection = Some(self.connect().await?);
+            }
+
+            let stream = connection.as_mut().expect("connection was just opened");
+            send_command(stream, args).await
+        })
+        .await
+        .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::TimedOut, "Redis command timed out")));
+
+        // The reply may be half read, so the next command starts on a fresh connection.
+        if result.is_err() {
+            *connection = None;
+        }
+
+        result
+    }
+
+    async fn connect(&self) -> io::Result<BufReader<TcpStream>> {
+        let stream = TcpStream::connect((self.target.host.as_str(), self.target.port)).await?;
+        let mut stream = BufReader::new(stream);
+
+        if let Some(password) = &self.target.password {
+            match &self.target.username {
+                Some(username) => send_command(&mut stream, &["AUTH", username, password]).await?,
+                None => send_command(&mut stream, &["AUTH", password]).await?,
+            };
+        }
+
+        if let Some(database) = self.target.database {
+            send_command(&mut stream, &["SELECT", &database.to_string()]).await?;
+        }
+
+        Ok(stream)
+    }
+}
+
+#[async_trait]
+impl StateStore for RedisStore {
+    async fn increment(&self, key: &str, window: Duration) -> io::Result<u32> {
+        let key = format!("{}{}", REDIS_KEY_PREFIX, key);
+        let window_ms = window.as_millis().max(1).to_string();
+
+        match self.command(&["EVAL", INCREMENT_SCRIPT, "1", &key, &window_ms]).await? {
+            Reply::Integer(count) => Ok(count.clamp(0, u32::MAX as i64) as u32),
+            reply => Err(unexpected_reply(reply)),
+        }
+    }
+
+    async fn count(&self, key: &str) -> io::Result<u32> {
+        let key = format!("{}{}", REDIS_KEY_PREFIX, key);
+
+        match self.command(&["GET", &key]).await? {
+            Reply::Nil => Ok(0),
+            Reply::Bulk(value) => String::from_utf8_lossy(&value)
+                .parse()
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Redis counter is not a number")),
+            reply => Err(unexpected_reply(reply)),
+        }
+    }
+
+    async fn reset(&self, key: &str) -> io::Result<()> {
+        let key = format!("{}{}", REDIS_KEY_PREFIX, key);
+        self.command(&["DEL", &key]).await?;
+        Ok(())
+    }
+}
+
+async fn send_command(stream: &mut BufReader<TcpStream>, args: &[&str]) -> io::Result<Reply> {
+    let mut request = format!("*{}\r\n", args.len()).into_bytes();
+    for arg in args {
+        request.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+        request.extend_from_slice(arg.as_bytes());
+        request.extend_from_slice(b"\r\n");
+    }
+
+    stream.get_mut().write_all(&request).await?;
+    read_reply(stream).await
+}
+
+async fn read_reply(stream: &mut BufReader<TcpStream>) -> io::Result<Reply> {
+    let mut line = String::new();
+    if stream.read_line(&mut line).await? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Redis closed the connection"));
+    }
+
+    let line = line.trim_end_matches("\r\n");
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("Unexpected Redis reply: {}", line));
+
+    let Some(kind) = line.chars().next() else {
+        return Err(invalid());
+    };
+    let rest = &line[1..];
+
+    match kind {
+        '+' => Ok(Reply::Simple),
+        '-' => Err(io::Error::other(format!("Redis error: {}", rest))),
+        ':' => rest.parse().map(Reply::Integer).map_err(|_| invalid()),
+        '$' => {
+            let length: i64 = rest.parse().map_err(|_| invalid())?;
+            if length < 0 {
+                return Ok(Reply::Nil);
+            }
+
+            let mut value = vec![0; length as usize + 2];
+            stream.read_exact(&mut value).await?;
+            value.truncate(length as usize);
+
+            Ok(Reply::Bulk(value))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+fn unexpected_reply(reply: Reply) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Unexpected Redis reply: {:?}", reply))
+}
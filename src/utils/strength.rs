@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+const MIN_WORD_LENGTH: usize = 3;
+const MIN_RUN_LENGTH: usize = 3;
+const MIN_SUBMATCH_GUESSES_LOG10: f64 = 1.7;
+const GUESSES_PER_SECOND_LOG10: f64 = 4.0;
+const SCORE_THRESHOLDS_LOG10: [f64; 4] = [3.0, 6.0, 8.0, 10.0];
+
+#[derive(Debug, Clone, Copy)]
+pub struct Strength {
+    pub guesses_log10: f64,
+    pub score: u8,
+}
+
+impl Strength {
+    pub fn crack_time(&self) -> String {
+        let seconds = 10f64.powf(self.guesses_log10 - GUESSES_PER_SECOND_LOG10);
+
+        let units = [
+            (60.0 * 60.0 * 24.0 * 365.0, "year"),
+            (60.0 * 60.0 * 24.0 * 31.0, "month"),
+            (60.0 * 60.0 * 24.0, "day"),
+            (60.0 * 60.0, "hour"),
+            (60.0, "minute"),
+            (1.0, "second"),
+        ];
+
+        if seconds < 1.0 {
+            return "less than a second".to_string();
+        }
+
+        if seconds >= units[0].0 * 100.0 {
+            return "centuries".to_string();
+        }
+
+        let (size, unit) = units
+            .iter()
+            .find(|(size, _)| seconds >= *size)
+            .copied()
+            .unwrap_or(units[5]);
+        let count = (seconds / size).round() as u64;
+
+        format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" })
+    }
+}
+
+// Cheapest way to guess the password, split into dictionary words, repeated or
+// sequential runs and brute-forced characters, in the spirit of zxcvbn.
+pub fn estimate(password: &str, dictionary: &HashMap<String, usize>, context: &[String]) -> Strength {
+    let chars: Vec<char> = password.chars().collect();
+    let lower: Vec<char> = password.to_lowercase().chars().collect();
+
+    if chars.is_empty() || chars.len() != lower.len() {
+        return score(chars.iter().map(|c| char_cardinality(*c).log10()).sum());
+    }
+
+    let mut best = vec![f64::INFINITY; chars.len() + 1];
+    best[0] = 0.0;
+
+    for start in 0..chars.len() {
+        let base = best[start];
+        if base.is_infinite() {
+            continue;
+        }
+
+        let mut relax = |end: usize, guesses_log10: f64| {
+            if base + guesses_log10 < best[end] {
+                best[end] = base + guesses_log10;
+            }
+        };
+
+        relax(start + 1, char_cardinality(chars[start]).log10());
+
+        for end in (start + MIN_WORD_LENGTH)..=chars.len() {
+            let word: String = lower[start..end].iter().collect();
+            let rank = if context.contains(&word) {
+                Some(1)
+            } else {
+                dictionary.get(&word).copied()
+            };
+
+            if let Some(rank) = rank {
+                let guesses = (rank as f64).log10() + case_variations(&chars[start..end]);
+                relax(end, guesses.max(MIN_SUBMATCH_GUESSES_LOG10));
+            }
+        }
+
+        let repeat = chars[start..].iter().take_while(|c| **c == chars[start]).count();
+        if repeat >= MIN_RUN_LENGTH {
+            for end in (start + MIN_RUN_LENGTH)..=(start + repeat) {
+                let guesses = (char_cardinality(chars[start]) * (end - start) as f64).log10();
+                relax(end, guesses.max(MIN_SUBMATCH_GUESSES_LOG10));
+            }
+        }
+
+        let sequence = sequence_length(&lower[start..]);
+        if sequence >= MIN_RUN_LENGTH {
+            for end in (start + MIN_RUN_LENGTH)..=(start + sequence) {
+                let guesses = (char_cardinality(chars[start]) * 2.0 * (end - start) as f64).log10();
+                relax(end, guesses.max(MIN_SUBMATCH_GUESSES_LOG10));
+            }
+        }
+    }
+
+    score(best[chars.len()])
+}
+
+fn score(guesses_log10: f64) -> Strength {
+    let score = SCORE_THRESHOLDS_LOG10
+        .iter()
+        .filter(|threshold| guesses_log10 >= **threshold)
+        .count() as u8;
+
+    Strength { guesses_log10, score }
+}
+
+fn char_cardinality(c: char) -> f64 {
+    match c {
+        '0'..='9' => 10.0,
+        'a'..='z' | 'A'..='Z' => 26.0,
+        c if c.is_ascii() => 33.0,
+        _ => 100.0,
+    }
+}
+
+fn case_variations(word: &[char]) -> f64 {
+    let upper = word.iter().filter(|c| c.is_uppercase()).count();
+    let lower = word.iter().filter(|c| c.is_lowercase()).count();
+
+    match (upper, lower) {
+        (0, _) => 0.0,
+        (_, 0) => 2f64.log10(),
+        (1, _) if word[0].is_uppercase() => 2f64.log10(),
+        (upper, lower) => upper.min(lower) as f64 * 2f64.log10() + 1.0,
+    }
+}
+
+fn sequence_length(chars: &[char]) -> usize {
+    let Some(&first) = chars.first().filter(|c| c.is_alphanumeric()) else {
+        return 0;
+    };
+
+    let step = match chars.get(1) {
+        Some(&second) if (second as i64 - first as i64).abs() == 1 => second as i64 - first as i64,
+        _ => return 1,
+    };
+
+    1 + chars
+        .windows(2)
+        .take_while(|pair| pair[1] as i64 - pair[0] as i64 == step && pair[1].is_alphanumeric())
+        .count()
+}
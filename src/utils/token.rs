@@ -1,9 +1,11 @@
 use axum::http::StatusCode;
 use chrono::{Duration, Utc};
 use jsonwebtoken::{
-    decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation
+    decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation
 };
-use serde::{Deserialize, Serialize};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 use crate::error::{ErrorMessage, HttpError};
 
@@ -12,11 +14,129 @@ pub struct TokenClaims {
     pub sub: String, 
     pub iat: usize,
     pub exp: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purpose: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonated_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org: Option<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignedClaims<T> {
+    pub sub: String,
+    pub iat: usize,
+    pub exp: usize,
+    pub purpose: String,
+    #[serde(flatten)]
+    pub data: T,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrustedDeviceClaims {
+    pub did: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SecurityQuestionClaims {
+    pub qids: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct JwtKey {
+    pub id: String,
+    secret: String,
+}
+
+impl JwtKey {
+    pub fn new(id: impl Into<String>, secret: impl Into<String>) -> Self {
+        JwtKey {
+            id: id.into(),
+            secret: secret.into(),
+        }
+    }
+}
+
+impl std::fmt::Debug for JwtKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JwtKey").field("id", &self.id).finish_non_exhaustive()
+    }
+}
+
+trait IssuedAt {
+    fn issued_at(&self) -> usize;
+}
+
+impl IssuedAt for TokenClaims {
+    fn issued_at(&self) -> usize {
+        self.iat
+    }
+}
+
+impl<T> IssuedAt for SignedClaims<T> {
+    fn issued_at(&self) -> usize {
+        self.iat
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JwtKeys {
+    current: JwtKey,
+    retired: Vec<JwtKey>,
+    leeway_seconds: u64,
+}
+
+impl JwtKeys {
+    pub fn new(current: JwtKey, retired: Vec<JwtKey>, leeway_seconds: u64) -> Self {
+        JwtKeys { current, retired, leeway_seconds }
+    }
+
+    fn encode<T: Serialize>(&self, claims: &T) -> Result<String, jsonwebtoken::errors::Error> {
+        let header = Header {
+            kid: Some(self.current.id.clone()),
+            ..Header::default()
+        };
+
+        encode(
+            &header,
+            claims,
+            &EncodingKey::from_secret(self.current.secret.as_bytes())
+        )
+    }
+
+    // The leeway absorbs clock drift between the host that signed a token and
+    // the one verifying it, on exp and nbf as well as an iat in the future.
+    fn decode<T: DeserializeOwned + IssuedAt>(&self, token: &str) -> Option<T> {
+        let header = decode_header(token).ok()?;
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.leeway = self.leeway_seconds;
+        validation.validate_nbf = true;
+        let latest_issued_at = Utc::now().timestamp() as u64 + self.leeway_seconds;
+
+        std::iter::once(&self.current)
+            .chain(self.retired.iter())
+            .filter(|key| header.kid.as_deref().is_none_or(|kid| kid == key.id))
+            .find_map(|key| {
+                decode::<T>(token, &DecodingKey::from_secret(key.secret.as_bytes()), &validation).ok()
+            })
+            .map(|data| data.claims)
+            .filter(|claims| claims.issued_at() as u64 <= latest_issued_at)
+    }
+}
+
+pub const TWO_FACTOR_PURPOSE: &str = "2fa";
+pub const ACCOUNT_SUMMARY_PURPOSE: &str = "account_summary";
+pub const TRUSTED_DEVICE_PURPOSE: &str = "trusted_device";
+pub const REACTIVATION_PURPOSE: &str = "reactivate";
+pub const SECURITY_QUESTIONS_PURPOSE: &str = "security_questions";
+
 pub fn create_token(
     user_id: &str,
-    secret: &[u8],
+    session_id: &str,
+    org_id: &str,
+    keys: &JwtKeys,
     expires_in_seconds: i64,
 ) -> Result<String, jsonwebtoken::errors::Error> {
     if user_id.is_empty() {
@@ -30,27 +150,192 @@ pub fn create_token(
         sub: user_id.to_string(),
         iat,
         exp,
+        sid: Some(session_id.to_string()),
+        purpose: None,
+        impersonated_by: None,
+        org: Some(org_id.to_string()),
+    };
+
+    keys.encode(&claims)
+}
+
+pub fn create_impersonation_token(
+    user_id: &str,
+    session_id: &str,
+    admin_id: &str,
+    org_id: &str,
+    keys: &JwtKeys,
+    expires_in_minutes: i64,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    if user_id.is_empty() || admin_id.is_empty() {
+        return Err(jsonwebtoken::errors::ErrorKind::InvalidSubject.into());
+    }
+
+    let now = Utc::now();
+    let claims = TokenClaims {
+        sub: user_id.to_string(),
+        iat: now.timestamp() as usize,
+        exp: (now+Duration::minutes(expires_in_minutes)).timestamp() as usize,
+        sid: Some(session_id.to_string()),
+        purpose: None,
+        impersonated_by: Some(admin_id.to_string()),
+        org: Some(org_id.to_string()),
+    };
+
+    keys.encode(&claims)
+}
+
+pub fn create_purpose_token(
+    user_id: &str,
+    purpose: &str,
+    keys: &JwtKeys,
+    expires_in_minutes: i64,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    if user_id.is_empty() {
+        return Err(jsonwebtoken::errors::ErrorKind::InvalidSubject.into());
+    }
+
+    let now = Utc::now();
+    let claims = TokenClaims {
+        sub: user_id.to_string(),
+        iat: now.timestamp() as usize,
+        exp: (now+Duration::minutes(expires_in_minutes)).timestamp() as usize,
+        sid: None,
+        purpose: Some(purpose.to_string()),
+        impersonated_by: None,
+        org: None,
     };
 
-    encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(secret)
-    )
+    keys.encode(&claims)
+}
+
+pub fn decode_purpose_token<T: Into<String>>(
+    token: T,
+    purpose: &str,
+    keys: &JwtKeys
+) -> Result<TokenClaims, HttpError> {
+    let claims = decode_token(token, keys)?;
+
+    if claims.purpose.as_deref() != Some(purpose) {
+        return Err(HttpError::new(ErrorMessage::InvalidToken, StatusCode::UNAUTHORIZED));
+    }
+
+    Ok(claims)
+}
+
+pub fn sign_payload<T: Serialize>(
+    user_id: &str,
+    purpose: &str,
+    data: T,
+    keys: &JwtKeys,
+    expires_in_minutes: i64,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let now = Utc::now();
+    let claims = SignedClaims {
+        sub: user_id.to_string(),
+        iat: now.timestamp() as usize,
+        exp: (now+Duration::minutes(expires_in_minutes)).timestamp() as usize,
+        purpose: purpose.to_string(),
+        data,
+    };
+
+    keys.encode(&claims)
+}
+
+pub fn verify_payload<T: DeserializeOwned>(
+    token: &str,
+    purpose: &str,
+    keys: &JwtKeys
+) -> Result<SignedClaims<T>, HttpError> {
+    match keys.decode::<SignedClaims<T>>(token) {
+        Some(claims) if claims.purpose == purpose => Ok(claims),
+        _ => Err(HttpError::new(ErrorMessage::InvalidToken, StatusCode::UNAUTHORIZED))
+    }
 }
 
 pub fn decode_token<T: Into<String>>(
     token: T,
-    secret: &[u8]
-) -> Result<String, HttpError> {
-    let decode = decode::<TokenClaims>(
-        &token.into(), 
-        &DecodingKey::from_secret(secret), 
-        &Validation::new(Algorithm::HS256)
-    );
-
-    match decode {
-        Ok(token) => Ok(token.claims.sub),
-        Err(_) => Err(HttpError::new(ErrorMessage::InvalidToken.to_string(), StatusCode::UNAUTHORIZED))
+    keys: &JwtKeys
+) -> Result<TokenClaims, HttpError> {
+    keys.decode::<TokenClaims>(&token.into())
+        .ok_or(HttpError::new(ErrorMessage::InvalidToken, StatusCode::UNAUTHORIZED))
+}
+
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+pub const API_KEY_PREFIX_LEN: usize = 11;
+
+pub fn generate_invite_code() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(12)
+        .map(char::from)
+        .collect::<String>()
+        .to_uppercase()
+}
+
+pub fn generate_api_key() -> String {
+    let secret: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(40)
+        .map(char::from)
+        .collect();
+    format!("ak_{}", secret)
+}
+
+pub fn generate_numeric_code() -> String {
+    format!("{:06}", rand::thread_rng().gen_range(0..1_000_000))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEEWAY_SECONDS: u64 = 30;
+
+    fn keys() -> JwtKeys {
+        JwtKeys::new(JwtKey::new("1", "test-secret"), Vec::new(), LEEWAY_SECONDS)
+    }
+
+    fn token_with(keys: &JwtKeys, iat_offset: i64, exp_offset: i64) -> String {
+        let now = Utc::now().timestamp();
+
+        keys.encode(&TokenClaims {
+            sub: "user".to_string(),
+            iat: (now + iat_offset) as usize,
+            exp: (now + exp_offset) as usize,
+            sid: None,
+            purpose: None,
+            impersonated_by: None,
+            org: None,
+        }).unwrap()
+    }
+
+    #[test]
+    fn expired_token_within_leeway_validates() {
+        let keys = keys();
+        let token = token_with(&keys, -600, -(LEEWAY_SECONDS as i64) + 5);
+
+        assert!(decode_token(token, &keys).is_ok());
+    }
+
+    #[test]
+    fn expired_token_beyond_leeway_is_rejected() {
+        let keys = keys();
+        let token = token_with(&keys, -600, -(LEEWAY_SECONDS as i64) - 5);
+
+        assert!(decode_token(token, &keys).is_err());
+    }
+
+    #[test]
+    fn future_issued_at_beyond_leeway_is_rejected() {
+        let keys = keys();
+        let within = token_with(&keys, LEEWAY_SECONDS as i64 - 5, 600);
+        let beyond = token_with(&keys, LEEWAY_SECONDS as i64 + 5, 600);
+
+        assert!(decode_token(within, &keys).is_ok());
+        assert!(decode_token(beyond, &keys).is_err());
     }
 }